@@ -20,7 +20,7 @@ pub use egui_renderer::EguiRenderer;
 pub use overlay::{
     DebugOverlay, PanelState, DebugBox, CollisionDebugData,
     ZOrderLabel, LayerInfo, ZOrderDebugData,
-    EntityInfo, ComponentInfo, ComponentValue, EcsInspectorData,
+    EntityInfo, ComponentInfo, ComponentValue, ComponentEdit, EcsInspectorData,
     DisplayRenderStats,
     EventType, EventLogEntry, EventLogFilter,
     ConsoleCommand,
@@ -5,6 +5,7 @@
 use crate::DebugConfig;
 use egui::{Context, RichText, Window, Color32, Stroke, Rect, Pos2};
 use engine_core::GameTime;
+use engine_ecs::{Entity, FieldValue};
 use glam::{Vec2, Mat4};
 
 /// A debug collision box to render
@@ -47,6 +48,8 @@ pub struct CollisionDebugData {
     pub entity_boxes: Vec<DebugBox>,
     /// Tile collision boxes
     pub tile_boxes: Vec<DebugBox>,
+    /// Outlines of sub-tile collision polygons
+    pub tile_outlines: Vec<Vec<Vec2>>,
     /// Camera view matrix for world-to-screen conversion
     pub view_matrix: Mat4,
     /// Screen size
@@ -234,6 +237,22 @@ pub enum ComponentValue {
     String(String),
     /// Size (width, height)
     Size { width: f32, height: f32 },
+    /// Named fields of a reflected component
+    Fields(Vec<(String, FieldValue)>),
+}
+
+/// A field edited in the inspector, to be written back by the game
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentEdit {
+    /// Edited entity, with its generation so a despawned one isn't mistaken
+    /// for whatever reuses its index
+    pub entity: Entity,
+    /// Component name
+    pub component: String,
+    /// Field name
+    pub field: String,
+    /// New field value
+    pub value: FieldValue,
 }
 
 /// Component information for the inspector
@@ -270,6 +289,12 @@ impl ComponentInfo {
         Self::new(name, ComponentValue::Size { width, height }, false)
     }
 
+    /// Create a reflected component from its field values
+    #[must_use]
+    pub fn fields(name: impl Into<String>, fields: Vec<(String, FieldValue)>, editable: bool) -> Self {
+        Self::new(name, ComponentValue::Fields(fields), editable)
+    }
+
     /// Create a Bool component
     #[must_use]
     pub fn bool(name: impl Into<String>, value: bool) -> Self {
@@ -281,7 +306,7 @@ impl ComponentInfo {
 #[derive(Debug, Clone)]
 pub struct EntityInfo {
     /// Entity ID
-    pub id: u32,
+    pub id: Entity,
     /// Entity name/label
     pub name: String,
     /// Components on this entity
@@ -291,7 +316,7 @@ pub struct EntityInfo {
 impl EntityInfo {
     /// Create a new entity info
     #[must_use]
-    pub fn new(id: u32, name: impl Into<String>) -> Self {
+    pub fn new(id: Entity, name: impl Into<String>) -> Self {
         Self {
             id,
             name: name.into(),
@@ -378,7 +403,7 @@ pub struct DebugOverlay {
     /// ECS inspector data
     ecs_data: EcsInspectorData,
     /// Currently selected entity ID
-    selected_entity: Option<u32>,
+    selected_entity: Option<Entity>,
     /// Event log entries
     event_log: Vec<EventLogEntry>,
     /// Event log filter
@@ -387,6 +412,8 @@ pub struct DebugOverlay {
     event_log_auto_scroll: bool,
     /// Pending console commands for game execution
     pending_commands: Vec<ConsoleCommand>,
+    /// Pending inspector edits for game execution
+    pending_edits: Vec<ComponentEdit>,
     /// Command history for up/down navigation
     command_history: Vec<String>,
     /// Current position in command history
@@ -425,6 +452,7 @@ impl DebugOverlay {
             event_filter: EventLogFilter::default(),
             event_log_auto_scroll: true,
             pending_commands: Vec::new(),
+            pending_edits: Vec::new(),
             command_history: Vec::new(),
             history_index: None,
         }
@@ -435,6 +463,11 @@ impl DebugOverlay {
         std::mem::take(&mut self.pending_commands)
     }
 
+    /// Take pending inspector edits for game execution (drains the queue)
+    pub fn take_pending_edits(&mut self) -> Vec<ComponentEdit> {
+        std::mem::take(&mut self.pending_edits)
+    }
+
     /// Add a response line to the console output
     pub fn console_print(&mut self, message: impl Into<String>) {
         self.console_output.push(message.into());
@@ -507,6 +540,7 @@ impl DebugOverlay {
         self.collision_data.screen_size = screen_size;
         self.collision_data.entity_boxes.clear();
         self.collision_data.tile_boxes.clear();
+        self.collision_data.tile_outlines.clear();
     }
 
     /// Add an entity collision box
@@ -521,6 +555,11 @@ impl DebugOverlay {
         );
     }
 
+    /// Add the outline of a tile collision polygon
+    pub fn add_tile_outline(&mut self, points: Vec<Vec2>) {
+        self.collision_data.tile_outlines.push(points);
+    }
+
     /// Check if z-order visualization should be rendered
     #[must_use]
     pub fn should_show_zorder(&self) -> bool {
@@ -570,7 +609,7 @@ impl DebugOverlay {
 
     /// Get the currently selected entity ID
     #[must_use]
-    pub fn selected_entity(&self) -> Option<u32> {
+    pub fn selected_entity(&self) -> Option<Entity> {
         self.selected_entity
     }
 
@@ -715,6 +754,15 @@ impl DebugOverlay {
             );
         }
 
+        // Render tile collision polygons as their outline
+        for outline in &self.collision_data.tile_outlines {
+            let points = outline.iter().map(|&point| self.world_to_screen(point)).collect();
+            painter.add(egui::Shape::closed_line(
+                points,
+                Stroke::new(1.0, Color32::from_rgb(255, 165, 0)),
+            ));
+        }
+
         // Render entity collision boxes (colored based on type)
         for debug_box in &self.collision_data.entity_boxes {
             let min = self.world_to_screen(debug_box.min);
@@ -935,6 +983,7 @@ impl DebugOverlay {
 
     /// Render ECS inspector panel
     fn render_ecs_inspector(&mut self, ctx: &Context) {
+        let mut edits = Vec::new();
         Window::new("🔍 ECS Inspector")
             .default_size([380.0, 450.0])
             .show(ctx, |ui| {
@@ -959,7 +1008,7 @@ impl DebugOverlay {
                             .show(ui, |ui| {
                                 for entity in &self.ecs_data.entities {
                                     let is_selected = self.selected_entity == Some(entity.id);
-                                    let label = format!("[{}] {}", entity.id, entity.name);
+                                    let label = format!("[{}] {}", entity.id.index, entity.name);
 
                                     if ui.selectable_label(is_selected, &label).clicked() {
                                         self.selected_entity = Some(entity.id);
@@ -976,7 +1025,7 @@ impl DebugOverlay {
 
                         if let Some(selected_id) = self.selected_entity {
                             if let Some(entity) = self.ecs_data.entities.iter().find(|e| e.id == selected_id) {
-                                ui.heading(format!("{} (ID: {})", entity.name, entity.id));
+                                ui.heading(format!("{} (ID: {})", entity.name, entity.id.index));
                                 ui.separator();
 
                                 egui::ScrollArea::vertical()
@@ -985,7 +1034,19 @@ impl DebugOverlay {
                                     .show(ui, |ui| {
                                         for component in &entity.components {
                                             ui.collapsing(&component.name, |ui| {
-                                                self.render_component_value(ui, &component.value);
+                                                let edited = self.render_component_value(
+                                                    ui,
+                                                    &component.value,
+                                                    component.editable,
+                                                );
+                                                if let Some((field, value)) = edited {
+                                                    edits.push(ComponentEdit {
+                                                        entity: entity.id,
+                                                        component: component.name.clone(),
+                                                        field,
+                                                        value,
+                                                    });
+                                                }
                                             });
                                         }
                                     });
@@ -999,10 +1060,18 @@ impl DebugOverlay {
                     });
                 });
             });
+        self.pending_edits.extend(edits);
     }
 
     /// Render a component value in the inspector
-    fn render_component_value(&self, ui: &mut egui::Ui, value: &ComponentValue) {
+    ///
+    /// Returns the field changed by the user, if any.
+    fn render_component_value(
+        &self,
+        ui: &mut egui::Ui,
+        value: &ComponentValue,
+        editable: bool,
+    ) -> Option<(String, FieldValue)> {
         match value {
             ComponentValue::Vec2 { x, y } => {
                 ui.horizontal(|ui| {
@@ -1032,7 +1101,44 @@ impl DebugOverlay {
             ComponentValue::String(s) => {
                 ui.label(s);
             }
+            ComponentValue::Fields(fields) => {
+                let mut edited = None;
+                for (name, field) in fields {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", name));
+                        if let Some(value) = self.render_field(ui, field, editable) {
+                            edited = Some((name.clone(), value));
+                        }
+                    });
+                }
+                return edited;
+            }
         }
+        None
+    }
+
+    /// Render one reflected field, editable when allowed
+    ///
+    /// Returns the new value when the user changed it.
+    fn render_field(&self, ui: &mut egui::Ui, field: &FieldValue, editable: bool) -> Option<FieldValue> {
+        if !editable {
+            ui.label(field.to_string());
+            return None;
+        }
+
+        let mut value = field.clone();
+        let changed = match &mut value {
+            FieldValue::Bool(v) => ui.checkbox(v, "").changed(),
+            FieldValue::Int(v) => ui.add(egui::DragValue::new(v)).changed(),
+            FieldValue::Float(v) => ui.add(egui::DragValue::new(v).speed(0.1)).changed(),
+            FieldValue::Vec2(v) => {
+                let x = ui.add(egui::DragValue::new(&mut v.x).speed(0.5).prefix("x: ")).changed();
+                let y = ui.add(egui::DragValue::new(&mut v.y).speed(0.5).prefix("y: ")).changed();
+                x || y
+            }
+            FieldValue::String(v) => ui.text_edit_singleline(v).lost_focus(),
+        };
+        changed.then_some(value)
     }
 
     /// Render collision panel
@@ -0,0 +1,246 @@
+//! Sparse-set vs table storage on a typical farm workload
+//!
+//! Run with `cargo bench -p engine_ecs`. Each benchmark builds the same farm
+//! (crops, NPCs and particles) under three storage layouts: everything in
+//! sparse sets, everything in tables, and a hybrid with data components in
+//! tables and the frequently toggled `Watered` marker in a sparse set.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use engine_ecs::{StorageType, With, World};
+
+const CROPS: usize = 600;
+const NPCS: usize = 40;
+const PARTICLES: usize = 800;
+
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Velocity {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Growth {
+    stage: u8,
+    progress: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lifetime(f32);
+
+#[derive(Debug, Clone, Copy)]
+struct Npc {
+    schedule_slot: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Watered;
+
+#[derive(Debug, Clone, Copy)]
+enum Layout {
+    Sparse,
+    Table,
+    Hybrid,
+}
+
+impl Layout {
+    const ALL: [Self; 3] = [Self::Sparse, Self::Table, Self::Hybrid];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Sparse => "sparse",
+            Self::Table => "table",
+            Self::Hybrid => "hybrid",
+        }
+    }
+
+    fn world(self) -> World {
+        let mut world = World::new();
+        let (data, markers) = match self {
+            Self::Sparse => (StorageType::SparseSet, StorageType::SparseSet),
+            Self::Table => (StorageType::Table, StorageType::Table),
+            Self::Hybrid => (StorageType::Table, StorageType::SparseSet),
+        };
+        world.set_storage_type::<Position>(data);
+        world.set_storage_type::<Velocity>(data);
+        world.set_storage_type::<Growth>(data);
+        world.set_storage_type::<Lifetime>(data);
+        world.set_storage_type::<Npc>(data);
+        world.set_storage_type::<Watered>(markers);
+        world
+    }
+}
+
+/// Spawn the farm: a field of crops, villagers and ambient particles
+fn spawn_farm(world: &mut World) {
+    for i in 0..CROPS {
+        let crop = world.spawn();
+        world.insert(
+            crop,
+            Position {
+                x: (i % 30) as f32 * 16.0,
+                y: (i / 30) as f32 * 16.0,
+            },
+        );
+        world.insert(
+            crop,
+            Growth {
+                stage: 0,
+                progress: 0.0,
+            },
+        );
+        if i % 2 == 0 {
+            world.insert(crop, Watered);
+        }
+    }
+
+    for i in 0..NPCS {
+        let npc = world.spawn();
+        world.insert(
+            npc,
+            Position {
+                x: i as f32,
+                y: 0.0,
+            },
+        );
+        world.insert(npc, Velocity { x: 1.0, y: 0.5 });
+        world.insert(npc, Npc { schedule_slot: 0 });
+    }
+
+    for i in 0..PARTICLES {
+        let particle = world.spawn();
+        world.insert(
+            particle,
+            Position {
+                x: 0.0,
+                y: i as f32,
+            },
+        );
+        world.insert(particle, Velocity { x: 0.0, y: -2.0 });
+        world.insert(particle, Lifetime(1.0));
+    }
+}
+
+fn farm(layout: Layout) -> World {
+    let mut world = layout.world();
+    spawn_farm(&mut world);
+    world
+}
+
+fn bench_spawn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spawn_farm");
+    for layout in Layout::ALL {
+        group.bench_function(BenchmarkId::from_parameter(layout.name()), |b| {
+            b.iter(|| black_box(farm(layout)));
+        });
+    }
+    group.finish();
+}
+
+fn bench_movement(c: &mut Criterion) {
+    let mut group = c.benchmark_group("movement");
+    for layout in Layout::ALL {
+        let mut world = farm(layout);
+        group.bench_function(BenchmarkId::from_parameter(layout.name()), |b| {
+            b.iter(|| {
+                for (_, (pos, vel)) in world.query_mut::<(&mut Position, &Velocity)>() {
+                    pos.x += vel.x * 0.016;
+                    pos.y += vel.y * 0.016;
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_crop_growth(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crop_growth");
+    for layout in Layout::ALL {
+        let mut world = farm(layout);
+        group.bench_function(BenchmarkId::from_parameter(layout.name()), |b| {
+            b.iter(|| {
+                for (_, (growth, pos)) in
+                    world.query_filtered_mut::<(&mut Growth, &Position), With<Watered>>()
+                {
+                    // Rows further north get less sun
+                    growth.progress += if pos.y < 160.0 { 0.1 } else { 0.08 };
+                    if growth.progress >= 1.0 {
+                        growth.progress = 0.0;
+                        growth.stage = (growth.stage + 1) % 4;
+                    }
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_particles(c: &mut Criterion) {
+    let mut group = c.benchmark_group("particles");
+    for layout in Layout::ALL {
+        let mut world = farm(layout);
+        group.bench_function(BenchmarkId::from_parameter(layout.name()), |b| {
+            b.iter(|| {
+                for (_, (pos, vel, life)) in
+                    world.query_mut::<(&mut Position, &Velocity, &mut Lifetime)>()
+                {
+                    pos.y += vel.y * 0.016;
+                    life.0 = (life.0 - 0.016).max(0.0);
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_npc_schedule(c: &mut Criterion) {
+    let mut group = c.benchmark_group("npc_schedule");
+    for layout in Layout::ALL {
+        let mut world = farm(layout);
+        group.bench_function(BenchmarkId::from_parameter(layout.name()), |b| {
+            b.iter(|| {
+                for (_, (npc, pos)) in world.query_mut::<(&mut Npc, &Position)>() {
+                    npc.schedule_slot = (npc.schedule_slot + pos.x as u32) % 24;
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Morning watering and overnight drying: toggles a marker on every crop
+fn bench_watering(c: &mut Criterion) {
+    let mut group = c.benchmark_group("watering");
+    for layout in Layout::ALL {
+        let mut world = farm(layout);
+        let crops: Vec<_> = world.query::<&Growth>().map(|(entity, _)| entity).collect();
+        group.bench_function(BenchmarkId::from_parameter(layout.name()), |b| {
+            b.iter(|| {
+                for &crop in &crops {
+                    world.insert(crop, Watered);
+                }
+                for &crop in &crops {
+                    world.remove::<Watered>(crop);
+                }
+                world.clear_trackers();
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_spawn,
+    bench_movement,
+    bench_crop_growth,
+    bench_particles,
+    bench_npc_schedule,
+    bench_watering
+);
+criterion_main!(benches);
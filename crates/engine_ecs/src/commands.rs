@@ -0,0 +1,226 @@
+//! Deferred world changes
+//!
+//! [`Commands`] records spawns, despawns, inserts, removals and observer
+//! triggers without borrowing the world, so structural changes can be
+//! queued while a query is iterating and applied at a sync point afterwards.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::entity::EntityAllocator;
+use crate::{Component, Entity, World};
+
+/// A queued world change
+enum Command {
+    /// Bring a reserved entity to life
+    Spawn(Entity),
+    /// Any other change
+    Apply(Box<dyn FnOnce(&mut World) + Send>),
+}
+
+/// Queue of world changes applied in order by [`Commands::apply`]
+///
+/// Created with [`World::commands`]. Spawning hands back the entity ID
+/// immediately so later commands can refer to it. Dropping the buffer
+/// without applying it discards the queued changes and frees the IDs it
+/// reserved.
+pub struct Commands {
+    allocator: Arc<Mutex<EntityAllocator>>,
+    queue: Vec<Command>,
+}
+
+impl Commands {
+    pub(crate) fn new(allocator: Arc<Mutex<EntityAllocator>>) -> Self {
+        Self {
+            allocator,
+            queue: Vec::new(),
+        }
+    }
+
+    /// Reserve an entity, spawned when the buffer is applied
+    pub fn spawn(&mut self) -> Entity {
+        let entity = self
+            .allocator
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .alloc();
+        self.queue.push(Command::Spawn(entity));
+        entity
+    }
+
+    /// Despawn an entity
+    pub fn despawn(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.despawn(entity);
+        });
+    }
+
+    /// Add a component to an entity
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) {
+        self.add(move |world| world.insert(entity, component));
+    }
+
+    /// Remove a component from an entity
+    pub fn remove<T: Component>(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.remove::<T>(entity);
+        });
+    }
+
+    /// Send an event to an entity's observers
+    pub fn trigger<E: Send + 'static>(&mut self, entity: Entity, event: E) {
+        self.add(move |world| {
+            world.trigger(entity, event);
+        });
+    }
+
+    /// Queue an arbitrary change
+    pub fn add<F>(&mut self, command: F)
+    where
+        F: FnOnce(&mut World) + Send + 'static,
+    {
+        self.queue.push(Command::Apply(Box::new(command)));
+    }
+
+    /// Get the number of queued commands
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Check if no command is queued
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Apply every queued command to the world, in the order recorded
+    ///
+    /// # Panics
+    /// Panics if the buffer was created by a different world.
+    pub fn apply(mut self, world: &mut World) {
+        assert!(
+            world.owns_allocator(&self.allocator),
+            "commands applied to a world they were not created from"
+        );
+
+        for command in self.queue.drain(..) {
+            match command {
+                Command::Spawn(entity) => world.spawn_reserved(entity),
+                Command::Apply(apply) => apply(world),
+            }
+        }
+    }
+}
+
+impl Drop for Commands {
+    fn drop(&mut self) {
+        // Return IDs reserved by spawns that were never applied
+        let mut allocator = self
+            .allocator
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for command in &self.queue {
+            if let Command::Spawn(entity) = command {
+                allocator.free(*entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(f32);
+
+    #[derive(Debug, PartialEq)]
+    struct Projectile;
+
+    #[test]
+    fn test_commands_during_iteration() {
+        let mut world = World::new();
+        let a = world.spawn();
+        world.insert(a, Position(1.0));
+        let b = world.spawn();
+        world.insert(b, Position(-1.0));
+
+        let mut commands = world.commands();
+        for (entity, pos) in world.query_mut::<&mut Position>() {
+            pos.0 *= 2.0;
+            if pos.0 < 0.0 {
+                commands.despawn(entity);
+            } else {
+                let shot = commands.spawn();
+                commands.insert(shot, Position(pos.0));
+                commands.insert(shot, Projectile);
+            }
+        }
+        assert_eq!(commands.len(), 4);
+        commands.apply(&mut world);
+
+        assert!(!world.is_alive(b));
+        assert_eq!(world.entity_count(), 2);
+        let shots: Vec<_> = world.query::<(&Position, &Projectile)>().collect();
+        assert_eq!(shots.len(), 1);
+        assert_eq!(shots[0].1 .0, &Position(2.0));
+    }
+
+    #[test]
+    fn test_reserved_entity_not_alive_until_applied() {
+        let mut world = World::new();
+        let mut commands = world.commands();
+        let reserved = commands.spawn();
+
+        // The world doesn't hand out the reserved ID in the meantime
+        let direct = world.spawn();
+        assert_ne!(direct, reserved);
+        assert!(!world.is_alive(reserved));
+        world.insert(reserved, Position(0.0));
+        assert!(!world.has::<Position>(reserved));
+
+        commands.insert(reserved, Position(3.0));
+        commands.apply(&mut world);
+        assert!(world.is_alive(reserved));
+        assert_eq!(world.get::<Position>(reserved), Some(&Position(3.0)));
+        assert_eq!(world.entities().count(), 2);
+    }
+
+    #[test]
+    fn test_commands_reuse_despawned_ids() {
+        let mut world = World::new();
+        let old = world.spawn();
+        world.despawn(old);
+
+        let mut commands = world.commands();
+        let reused = commands.spawn();
+        assert_eq!(reused.index, old.index);
+        assert_eq!(reused.generation, old.generation + 1);
+        commands.remove::<Position>(reused);
+        commands.apply(&mut world);
+
+        assert!(world.is_alive(reused));
+        assert!(!world.is_alive(old));
+    }
+
+    #[test]
+    fn test_dropped_commands_free_reserved_ids() {
+        let mut world = World::new();
+        let reserved = {
+            let mut commands = world.commands();
+            commands.spawn()
+        };
+
+        let next = world.spawn();
+        assert_eq!(next, reserved);
+        assert_eq!(world.entity_count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "not created from")]
+    fn test_commands_rejects_other_world() {
+        let world = World::new();
+        let mut other = World::new();
+        world.commands().apply(&mut other);
+    }
+}
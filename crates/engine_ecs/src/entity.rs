@@ -0,0 +1,48 @@
+//! Entity identifiers
+
+use serde::{Deserialize, Serialize};
+
+/// Entity identifier with generation for safe references
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Entity {
+    /// Index in the entity array
+    pub index: u32,
+    /// Generation to detect stale references
+    pub generation: u32,
+}
+
+impl Entity {
+    /// Create a new entity with given index and generation
+    #[must_use]
+    pub const fn new(index: u32, generation: u32) -> Self {
+        Self { index, generation }
+    }
+}
+
+/// Hands out entity IDs, shared between a world and its command buffers
+///
+/// Command buffers reserve IDs while the world is borrowed elsewhere, so
+/// the free list lives behind a lock rather than in the world itself.
+#[derive(Debug, Default)]
+pub(crate) struct EntityAllocator {
+    /// Freed IDs, already carrying their next generation
+    free: Vec<Entity>,
+    /// First index never handed out
+    next_index: u32,
+}
+
+impl EntityAllocator {
+    /// Take a free ID, or a fresh index if none is free
+    pub(crate) fn alloc(&mut self) -> Entity {
+        self.free.pop().unwrap_or_else(|| {
+            let index = self.next_index;
+            self.next_index += 1;
+            Entity::new(index, 0)
+        })
+    }
+
+    /// Return an ID for reuse
+    pub(crate) fn free(&mut self, entity: Entity) {
+        self.free.push(entity);
+    }
+}
@@ -0,0 +1,326 @@
+//! Typed event channels
+//!
+//! [`Events<T>`] is a double-buffered queue stored as a world resource.
+//! Writers append to the current frame's buffer; [`Events::update`] (run
+//! once per frame by [`World::update_events`]) drops the older buffer, so
+//! every event stays readable for two frames. Each [`EventReader`] keeps its
+//! own cursor and sees every event once.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::{Resource, World};
+
+/// An event with its sequence number
+#[derive(Debug)]
+struct EventInstance<T> {
+    id: usize,
+    event: T,
+}
+
+/// Double-buffered event queue for one event type
+pub struct Events<T> {
+    /// Events sent during the previous frame
+    previous: Vec<EventInstance<T>>,
+    /// Events sent during the current frame
+    current: Vec<EventInstance<T>>,
+    /// Total number of events ever sent; the next event's id
+    event_count: usize,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            event_count: 0,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Events<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Events")
+            .field("type", &std::any::type_name::<T>())
+            .field("previous", &self.previous.len())
+            .field("current", &self.current.len())
+            .field("event_count", &self.event_count)
+            .finish()
+    }
+}
+
+impl<T> Events<T> {
+    /// Create an empty queue
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send an event
+    pub fn send(&mut self, event: T) {
+        self.current.push(EventInstance {
+            id: self.event_count,
+            event,
+        });
+        self.event_count += 1;
+    }
+
+    /// Swap buffers, dropping events sent two frames ago
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// Get the number of events currently stored
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    /// Check if no event is stored
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Id of the oldest stored event
+    fn oldest_id(&self) -> usize {
+        self.event_count - self.len()
+    }
+
+    /// Stored events in the order they were sent
+    fn instances(&self) -> impl Iterator<Item = &EventInstance<T>> {
+        self.previous.iter().chain(&self.current)
+    }
+}
+
+/// Appends events to an [`Events<T>`] resource
+pub struct EventWriter<'w, T> {
+    events: &'w mut Events<T>,
+}
+
+impl<T> EventWriter<'_, T> {
+    /// Send an event
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+
+    /// Send several events in order
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        for event in events {
+            self.events.send(event);
+        }
+    }
+}
+
+/// Read cursor into an [`Events<T>`] resource
+///
+/// Each reader sees every event once, as long as it reads at least once
+/// every two frames. Keep one per consumer, e.g. captured in a system
+/// closure.
+pub struct EventReader<T> {
+    /// Id of the next unread event
+    next_id: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for EventReader<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventReader")
+            .field("type", &std::any::type_name::<T>())
+            .field("next_id", &self.next_id)
+            .finish()
+    }
+}
+
+impl<T> EventReader<T> {
+    /// Create a reader that sees every event still stored
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Iterate over events not yet seen by this reader
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let oldest = events.oldest_id();
+        if self.next_id < oldest {
+            log::warn!(
+                "EventReader<{}> missed {} events",
+                std::any::type_name::<T>(),
+                oldest - self.next_id
+            );
+        }
+
+        let start = self.next_id;
+        self.next_id = events.event_count;
+        events
+            .instances()
+            .filter(move |instance| instance.id >= start)
+            .map(|instance| &instance.event)
+    }
+
+    /// Get the number of events not yet seen by this reader
+    #[must_use]
+    pub fn len(&self, events: &Events<T>) -> usize {
+        events.event_count - self.next_id.max(events.oldest_id())
+    }
+
+    /// Check if every stored event has been seen
+    #[must_use]
+    pub fn is_empty(&self, events: &Events<T>) -> bool {
+        self.len(events) == 0
+    }
+
+    /// Mark every stored event as seen
+    pub fn clear(&mut self, events: &Events<T>) {
+        self.next_id = events.event_count;
+    }
+}
+
+impl World {
+    /// Register an event type, adding its `Events<T>` resource
+    ///
+    /// Registered queues are swapped by [`World::update_events`].
+    pub fn add_event<T: Resource>(&mut self) {
+        if self.has_resource::<Events<T>>() {
+            return;
+        }
+        self.insert_resource(Events::<T>::new());
+        self.event_updaters.push(|world| {
+            if let Some(events) = world.get_resource_mut::<Events<T>>() {
+                events.update();
+            }
+        });
+    }
+
+    /// Swap the buffers of every registered event type
+    ///
+    /// Call once per frame.
+    pub fn update_events(&mut self) {
+        for i in 0..self.event_updaters.len() {
+            (self.event_updaters[i])(self);
+        }
+    }
+
+    /// Get a writer for an event type
+    ///
+    /// # Panics
+    /// Panics if `T` was not registered with [`World::add_event`].
+    pub fn event_writer<T: Resource>(&mut self) -> EventWriter<'_, T> {
+        let events = self.get_resource_mut::<Events<T>>().unwrap_or_else(|| {
+            panic!(
+                "event type `{}` was not registered with `World::add_event`",
+                std::any::type_name::<T>()
+            )
+        });
+        EventWriter { events }
+    }
+
+    /// Send a single event
+    ///
+    /// # Panics
+    /// Panics if `T` was not registered with [`World::add_event`].
+    pub fn send_event<T: Resource>(&mut self, event: T) {
+        self.event_writer::<T>().send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct MapTransition(&'static str);
+
+    #[test]
+    fn test_events_live_for_two_frames() {
+        let mut events = Events::new();
+        let mut reader = EventReader::new();
+
+        events.send(1);
+        events.update();
+        events.send(2);
+        assert_eq!(events.len(), 2);
+
+        events.update();
+        assert_eq!(events.len(), 1);
+        assert_eq!(reader.read(&events).copied().collect::<Vec<_>>(), [2]);
+
+        events.update();
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_readers_have_separate_cursors() {
+        let mut events = Events::new();
+        let mut early = EventReader::new();
+        let mut late = EventReader::new();
+
+        events.send("harvest");
+        assert_eq!(early.read(&events).collect::<Vec<_>>(), [&"harvest"]);
+        assert_eq!(early.read(&events).count(), 0);
+
+        events.send("dialogue");
+        assert_eq!(early.len(&events), 1);
+        assert_eq!(late.len(&events), 2);
+        assert_eq!(
+            late.read(&events).collect::<Vec<_>>(),
+            [&"harvest", &"dialogue"]
+        );
+        assert_eq!(early.read(&events).collect::<Vec<_>>(), [&"dialogue"]);
+
+        events.send("day");
+        late.clear(&events);
+        assert!(late.is_empty(&events));
+    }
+
+    #[test]
+    fn test_reader_skips_dropped_events() {
+        let mut events = Events::new();
+        let mut reader = EventReader::new();
+
+        events.send(1);
+        events.update();
+        events.update();
+        events.send(2);
+
+        assert_eq!(reader.len(&events), 1);
+        assert_eq!(reader.read(&events).copied().collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn test_world_events() {
+        let mut world = World::new();
+        world.add_event::<MapTransition>();
+        let mut reader = EventReader::<MapTransition>::new();
+
+        world.send_event(MapTransition("farm"));
+        world
+            .event_writer()
+            .send_batch([MapTransition("town"), MapTransition("beach")]);
+
+        let events = world.get_resource::<Events<MapTransition>>().unwrap();
+        let targets: Vec<_> = reader.read(events).map(|e| e.0).collect();
+        assert_eq!(targets, ["farm", "town", "beach"]);
+
+        world.update_events();
+        world.update_events();
+        let events = world.get_resource::<Events<MapTransition>>().unwrap();
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "was not registered")]
+    fn test_unregistered_event_panics() {
+        let mut world = World::new();
+        world.send_event(MapTransition("farm"));
+    }
+}
@@ -0,0 +1,273 @@
+//! Parent/child hierarchy with transform propagation
+//!
+//! Links are made with [`World::set_parent`], which keeps [`Parent`] and
+//! [`Children`] in sync. Each entity's [`Transform`] is relative to its
+//! parent; [`propagate_transforms`] computes the world-space
+//! [`GlobalTransform`] of every entity below a root.
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{impl_reflect, Entity, Without, World};
+
+/// Parent of an entity in the hierarchy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parent(pub(crate) Entity);
+
+impl Parent {
+    /// Get the parent entity
+    #[must_use]
+    pub const fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+/// Children of an entity, in the order they were attached
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Children(pub(crate) Vec<Entity>);
+
+impl Children {
+    /// Get the child entities
+    #[must_use]
+    pub fn entities(&self) -> &[Entity] {
+        &self.0
+    }
+
+    /// Get the number of children
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if there are no children
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Position relative to the parent (or to the world for roots)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub translation: Vec2,
+}
+
+impl Transform {
+    /// Create a transform at the given offset
+    #[must_use]
+    pub const fn from_xy(x: f32, y: f32) -> Self {
+        Self {
+            translation: Vec2::new(x, y),
+        }
+    }
+}
+
+/// World-space position, written by [`propagate_transforms`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GlobalTransform {
+    pub translation: Vec2,
+}
+
+impl_reflect!(Transform { translation: Vec2 });
+impl_reflect!(GlobalTransform { translation: Vec2 });
+
+impl World {
+    /// Attach `child` to `parent`, detaching it from any previous parent
+    ///
+    /// Returns false if either entity is dead, or if the link would make
+    /// an entity its own ancestor.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> bool {
+        if !self.is_alive(child) || !self.is_alive(parent) || self.is_ancestor(child, parent) {
+            return false;
+        }
+
+        self.detach_from_parent(child);
+        self.insert(child, Parent(parent));
+        match self.get_mut::<Children>(parent) {
+            Some(children) => children.0.push(child),
+            None => self.insert(parent, Children(vec![child])),
+        }
+        true
+    }
+
+    /// Detach an entity from its parent, making it a root
+    pub fn remove_parent(&mut self, child: Entity) {
+        self.detach_from_parent(child);
+    }
+
+    /// Get the children of an entity
+    #[must_use]
+    pub fn children(&self, entity: Entity) -> &[Entity] {
+        self.get::<Children>(entity).map_or(&[], Children::entities)
+    }
+
+    /// Check if `ancestor` is `entity` or one of its ancestors
+    fn is_ancestor(&self, ancestor: Entity, entity: Entity) -> bool {
+        let mut current = Some(entity);
+        while let Some(e) = current {
+            if e == ancestor {
+                return true;
+            }
+            current = self.get::<Parent>(e).map(Parent::get);
+        }
+        false
+    }
+
+    /// Remove the `Parent` link and the matching `Children` entry
+    pub(crate) fn detach_from_parent(&mut self, child: Entity) {
+        let Some(Parent(parent)) = self.remove::<Parent>(child) else {
+            return;
+        };
+
+        let now_empty = self.get_mut::<Children>(parent).is_some_and(|children| {
+            children.0.retain(|&c| c != child);
+            children.0.is_empty()
+        });
+        if now_empty {
+            self.remove::<Children>(parent);
+        }
+    }
+}
+
+/// Compute the `GlobalTransform` of every entity below a root
+///
+/// Roots are entities with a `Transform` and no `Parent`. A child without a
+/// `Transform` sits exactly on its parent. Global transforms are only
+/// written when they move, so `Changed<GlobalTransform>` stays meaningful.
+pub fn propagate_transforms(world: &mut World) {
+    let mut stack: Vec<(Entity, Vec2)> = world
+        .query_filtered::<&Transform, Without<Parent>>()
+        .map(|(entity, _)| (entity, Vec2::ZERO))
+        .collect();
+
+    while let Some((entity, parent_translation)) = stack.pop() {
+        let local = world
+            .get::<Transform>(entity)
+            .map_or(Vec2::ZERO, |t| t.translation);
+        let global = GlobalTransform {
+            translation: parent_translation + local,
+        };
+
+        if world.get::<GlobalTransform>(entity) != Some(&global) {
+            world.insert(entity, global);
+        }
+
+        stack.extend(
+            world
+                .children(entity)
+                .iter()
+                .map(|&child| (child, global.translation)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Changed;
+
+    fn spawn_at(world: &mut World, x: f32, y: f32) -> Entity {
+        let entity = world.spawn();
+        world.insert(entity, Transform::from_xy(x, y));
+        entity
+    }
+
+    #[test]
+    fn test_propagation_follows_parent() {
+        let mut world = World::new();
+        let player = spawn_at(&mut world, 100.0, 50.0);
+        let tool = spawn_at(&mut world, 8.0, 0.0);
+        let sparkle = spawn_at(&mut world, 0.0, -4.0);
+        assert!(world.set_parent(tool, player));
+        assert!(world.set_parent(sparkle, tool));
+
+        propagate_transforms(&mut world);
+        assert_eq!(
+            world.get::<GlobalTransform>(sparkle).unwrap().translation,
+            Vec2::new(108.0, 46.0)
+        );
+
+        world.get_mut::<Transform>(player).unwrap().translation.x = 0.0;
+        propagate_transforms(&mut world);
+        assert_eq!(
+            world.get::<GlobalTransform>(tool).unwrap().translation,
+            Vec2::new(8.0, 50.0)
+        );
+        assert_eq!(
+            world.get::<GlobalTransform>(sparkle).unwrap().translation,
+            Vec2::new(8.0, 46.0)
+        );
+    }
+
+    #[test]
+    fn test_unchanged_globals_are_not_rewritten() {
+        let mut world = World::new();
+        let root = spawn_at(&mut world, 1.0, 1.0);
+        propagate_transforms(&mut world);
+
+        world.clear_trackers();
+        propagate_transforms(&mut world);
+        let changed = world
+            .query_filtered::<&GlobalTransform, Changed<GlobalTransform>>()
+            .count();
+        assert_eq!(changed, 0);
+        assert!(world.has::<GlobalTransform>(root));
+    }
+
+    #[test]
+    fn test_reparenting_updates_children() {
+        let mut world = World::new();
+        let a = spawn_at(&mut world, 0.0, 0.0);
+        let b = spawn_at(&mut world, 0.0, 0.0);
+        let child = spawn_at(&mut world, 1.0, 0.0);
+
+        world.set_parent(child, a);
+        assert_eq!(world.children(a), &[child]);
+
+        world.set_parent(child, b);
+        assert!(world.children(a).is_empty());
+        assert!(!world.has::<Children>(a));
+        assert_eq!(world.children(b), &[child]);
+        assert_eq!(world.get::<Parent>(child).map(Parent::get), Some(b));
+
+        world.remove_parent(child);
+        assert!(!world.has::<Parent>(child));
+        assert!(world.children(b).is_empty());
+    }
+
+    #[test]
+    fn test_set_parent_rejects_cycles() {
+        let mut world = World::new();
+        let a = world.spawn();
+        let b = world.spawn();
+        let c = world.spawn();
+        world.set_parent(b, a);
+        world.set_parent(c, b);
+
+        assert!(!world.set_parent(a, c));
+        assert!(!world.set_parent(a, a));
+        assert!(!world.has::<Parent>(a));
+    }
+
+    #[test]
+    fn test_despawn_is_recursive() {
+        let mut world = World::new();
+        let parent = world.spawn();
+        let child = world.spawn();
+        let grandchild = world.spawn();
+        let sibling = world.spawn();
+        world.set_parent(child, parent);
+        world.set_parent(grandchild, child);
+        world.set_parent(sibling, parent);
+
+        // Despawning a child only detaches it from its parent
+        world.despawn(sibling);
+        assert_eq!(world.children(parent), &[child]);
+
+        world.despawn(parent);
+        assert!(!world.is_alive(child));
+        assert!(!world.is_alive(grandchild));
+        assert_eq!(world.entity_count(), 0);
+    }
+}
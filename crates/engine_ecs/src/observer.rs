@@ -0,0 +1,332 @@
+//! Component lifecycle hooks and entity observers
+//!
+//! Hooks run whenever a component of a given type is inserted on or removed
+//! from an entity, whatever made the change: direct calls, [`Commands`],
+//! prefabs, snapshot restores or despawns. `on_insert` hooks run after the
+//! component is stored (including when it replaces a previous value);
+//! `on_remove` hooks run while the component can still be read.
+//!
+//! Observers react to custom events sent to one entity with
+//! [`World::trigger`], and are dropped when that entity is despawned.
+//!
+//! [`Commands`]: crate::Commands
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{Component, Entity, World};
+
+/// Callback run when a component is inserted or removed
+type Hook = Arc<dyn Fn(&mut World, Entity) + Send + Sync>;
+
+/// Type-erased observer callback
+type ObserverFn = Arc<dyn Fn(&mut World, Entity, &dyn Any) + Send + Sync>;
+
+/// Hooks registered for one component type
+struct ComponentHooks {
+    /// Whether an entity has the component, without knowing its type
+    has: fn(&World, Entity) -> bool,
+    on_insert: Vec<Hook>,
+    on_remove: Vec<Hook>,
+}
+
+/// Lifecycle hooks of every component type
+#[derive(Default)]
+pub(crate) struct Hooks {
+    by_type: HashMap<TypeId, ComponentHooks>,
+}
+
+impl Hooks {
+    fn entry<T: Component>(&mut self) -> &mut ComponentHooks {
+        self.by_type
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| ComponentHooks {
+                has: World::has::<T>,
+                on_insert: Vec::new(),
+                on_remove: Vec::new(),
+            })
+    }
+
+    fn on_insert(&self, type_id: TypeId) -> Vec<Hook> {
+        self.by_type
+            .get(&type_id)
+            .map_or_else(Vec::new, |hooks| hooks.on_insert.clone())
+    }
+}
+
+/// Handle to a registered observer, used to remove it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+struct Observer {
+    id: ObserverId,
+    event: TypeId,
+    callback: ObserverFn,
+}
+
+/// Observers by the entity they watch
+#[derive(Default)]
+pub(crate) struct Observers {
+    by_entity: HashMap<Entity, Vec<Observer>>,
+    next_id: u64,
+}
+
+impl World {
+    /// Run `hook` whenever a `T` is inserted on an entity
+    ///
+    /// Hooks of one type run in registration order. Inserting a `T` from
+    /// within its own `on_insert` hook recurses.
+    pub fn on_insert<T: Component>(
+        &mut self,
+        hook: impl Fn(&mut World, Entity) + Send + Sync + 'static,
+    ) {
+        self.hooks.entry::<T>().on_insert.push(Arc::new(hook));
+    }
+
+    /// Run `hook` whenever a `T` is about to be removed from an entity
+    ///
+    /// This includes despawning the entity. The component is still in place
+    /// while the hook runs.
+    pub fn on_remove<T: Component>(
+        &mut self,
+        hook: impl Fn(&mut World, Entity) + Send + Sync + 'static,
+    ) {
+        self.hooks.entry::<T>().on_remove.push(Arc::new(hook));
+    }
+
+    pub(crate) fn run_insert_hooks(&mut self, type_id: TypeId, entity: Entity) {
+        for hook in self.hooks.on_insert(type_id) {
+            hook(self, entity);
+        }
+    }
+
+    /// Run the `on_remove` hooks of a type if the entity has the component
+    pub(crate) fn run_remove_hooks(&mut self, type_id: TypeId, entity: Entity) {
+        let hooks = match self.hooks.by_type.get(&type_id) {
+            Some(hooks) if !hooks.on_remove.is_empty() && (hooks.has)(self, entity) => {
+                hooks.on_remove.clone()
+            }
+            _ => return,
+        };
+        for hook in hooks {
+            hook(self, entity);
+        }
+    }
+
+    /// Run the `on_remove` hooks of every component an entity has
+    pub(crate) fn run_despawn_hooks(&mut self, entity: Entity) {
+        let types: Vec<TypeId> = self
+            .hooks
+            .by_type
+            .iter()
+            .filter(|(_, hooks)| !hooks.on_remove.is_empty())
+            .map(|(type_id, _)| *type_id)
+            .collect();
+        for type_id in types {
+            self.run_remove_hooks(type_id, entity);
+        }
+    }
+
+    /// Call `observer` whenever an `E` is triggered on `entity`
+    ///
+    /// Returns `None` if the entity is dead. The observer is dropped when
+    /// the entity is despawned.
+    pub fn observe<E: 'static>(
+        &mut self,
+        entity: Entity,
+        observer: impl Fn(&mut World, Entity, &E) + Send + Sync + 'static,
+    ) -> Option<ObserverId> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        let id = ObserverId(self.observers.next_id);
+        self.observers.next_id += 1;
+        let callback: ObserverFn = Arc::new(move |world, entity, event| {
+            if let Some(event) = event.downcast_ref::<E>() {
+                observer(world, entity, event);
+            }
+        });
+        self.observers
+            .by_entity
+            .entry(entity)
+            .or_default()
+            .push(Observer {
+                id,
+                event: TypeId::of::<E>(),
+                callback,
+            });
+        Some(id)
+    }
+
+    /// Remove an observer, returning whether it was registered
+    pub fn unobserve(&mut self, id: ObserverId) -> bool {
+        for observers in self.observers.by_entity.values_mut() {
+            if let Some(pos) = observers.iter().position(|observer| observer.id == id) {
+                observers.remove(pos);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Send `event` to the observers of `entity`, in registration order
+    ///
+    /// Returns the number of observers that ran.
+    pub fn trigger<E: 'static>(&mut self, entity: Entity, event: E) -> usize {
+        if !self.is_alive(entity) {
+            return 0;
+        }
+
+        let callbacks: Vec<ObserverFn> = self
+            .observers
+            .by_entity
+            .get(&entity)
+            .into_iter()
+            .flatten()
+            .filter(|observer| observer.event == TypeId::of::<E>())
+            .map(|observer| Arc::clone(&observer.callback))
+            .collect();
+        for callback in &callbacks {
+            callback(self, entity, &event);
+        }
+        callbacks.len()
+    }
+
+    /// Drop the observers watching a despawned entity
+    pub(crate) fn remove_observers(&mut self, entity: Entity) {
+        self.observers.by_entity.remove(&entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, PartialEq)]
+    struct Collider(f32);
+
+    /// Entities currently registered, like a spatial grid
+    #[derive(Default)]
+    struct Registered(Vec<(Entity, f32)>);
+
+    struct Damage(u32);
+
+    struct Health(u32);
+
+    fn world_with_registry() -> World {
+        let mut world = World::new();
+        world.insert_resource(Registered::default());
+        world.on_insert::<Collider>(|world, entity| {
+            let size = world.get::<Collider>(entity).unwrap().0;
+            let registered = world.get_resource_mut::<Registered>().unwrap();
+            registered.0.retain(|&(e, _)| e != entity);
+            registered.0.push((entity, size));
+        });
+        world.on_remove::<Collider>(|world, entity| {
+            // The component is still readable
+            assert!(world.has::<Collider>(entity));
+            let registered = world.get_resource_mut::<Registered>().unwrap();
+            registered.0.retain(|&(e, _)| e != entity);
+        });
+        world
+    }
+
+    #[test]
+    fn test_hooks_follow_insert_and_remove() {
+        let mut world = world_with_registry();
+        let a = world.spawn();
+        let b = world.spawn();
+        world.insert(a, Collider(1.0));
+        world.insert(b, Collider(2.0));
+        world.insert(a, Collider(3.0));
+        assert_eq!(
+            world.get_resource::<Registered>().unwrap().0,
+            vec![(b, 2.0), (a, 3.0)]
+        );
+
+        world.remove::<Collider>(b);
+        assert_eq!(
+            world.get_resource::<Registered>().unwrap().0,
+            vec![(a, 3.0)]
+        );
+        world.despawn(a);
+        assert!(world.get_resource::<Registered>().unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn test_hooks_run_for_commands_and_tables() {
+        let mut world = world_with_registry();
+        world.set_storage_type::<Collider>(crate::StorageType::Table);
+
+        let mut commands = world.commands();
+        let entity = commands.spawn();
+        commands.insert(entity, Collider(4.0));
+        commands.apply(&mut world);
+        assert_eq!(
+            world.get_resource::<Registered>().unwrap().0,
+            vec![(entity, 4.0)]
+        );
+
+        let mut commands = world.commands();
+        commands.despawn(entity);
+        commands.apply(&mut world);
+        assert!(world.get_resource::<Registered>().unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn test_observers_target_one_entity() {
+        let mut world = World::new();
+        let a = world.spawn();
+        let b = world.spawn();
+        world.insert(a, Health(10));
+        world.insert(b, Health(10));
+        let observer = |world: &mut World, entity: Entity, damage: &Damage| {
+            if let Some(health) = world.get_mut::<Health>(entity) {
+                health.0 = health.0.saturating_sub(damage.0);
+            }
+        };
+        world.observe(a, observer).unwrap();
+        world.observe(b, observer).unwrap();
+
+        assert_eq!(world.trigger(a, Damage(3)), 1);
+        assert_eq!(world.get::<Health>(a).unwrap().0, 7);
+        assert_eq!(world.get::<Health>(b).unwrap().0, 10);
+
+        // Other event types aren't delivered
+        assert_eq!(world.trigger(a, Collider(0.0)), 0);
+    }
+
+    #[test]
+    fn test_observers_removed() {
+        let mut world = World::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let entity = world.spawn();
+        let counter = Arc::clone(&calls);
+        let id = world
+            .observe(entity, move |_, _, _: &Damage| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            })
+            .unwrap();
+        let counter = Arc::clone(&calls);
+        world.observe(entity, move |_, _, _: &Damage| {
+            counter.fetch_add(10, Ordering::Relaxed);
+        });
+
+        world.trigger(entity, Damage(1));
+        assert!(world.unobserve(id));
+        assert!(!world.unobserve(id));
+        world.trigger(entity, Damage(1));
+        assert_eq!(calls.load(Ordering::Relaxed), 21);
+
+        // Despawning drops the rest; a reused index starts with none
+        world.despawn(entity);
+        let reused = world.spawn();
+        assert_eq!(reused.index, entity.index);
+        assert_eq!(world.trigger(reused, Damage(1)), 0);
+        assert_eq!(world.trigger(entity, Damage(1)), 0);
+        assert!(world.observe(entity, |_, _, _: &Damage| {}).is_none());
+    }
+}
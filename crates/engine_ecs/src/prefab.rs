@@ -0,0 +1,520 @@
+//! Entity prefabs loaded from TOML
+//!
+//! A prefab file lists components as top-level tables, keyed by the name
+//! they were registered under:
+//!
+//! ```toml
+//! [Velocity]
+//! x = 0.0
+//! y = 0.0
+//!
+//! [CameraTarget]
+//! ```
+//!
+//! Component types are registered on a [`PrefabRegistry`] before prefabs
+//! are loaded, so unknown names and bad field values are reported at load
+//! time rather than when spawning.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::de::value::UnitDeserializer;
+use serde::de::{DeserializeOwned, Error as _};
+
+use crate::{Component, Entity, FieldValue, Reflect, World};
+
+/// Inserts one component into an entity
+type Insert = Box<dyn FnOnce(&mut World, Entity)>;
+
+/// Builds a component inserter from its TOML value
+type ComponentLoader = Box<dyn Fn(toml::Value) -> Result<Insert, toml::de::Error> + Send + Sync>;
+
+/// A named list of components with their field values
+#[derive(Debug, Clone)]
+pub struct Prefab {
+    name: String,
+    components: Vec<(String, toml::Value)>,
+}
+
+impl Prefab {
+    /// Get prefab name
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the names of the components this prefab inserts
+    pub fn component_names(&self) -> impl Iterator<Item = &str> {
+        self.components.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+/// Component types and prefabs known by name
+///
+/// Stored as a world resource so [`World::spawn_prefab`] can find it.
+#[derive(Default)]
+pub struct PrefabRegistry {
+    components: HashMap<String, ComponentLoader>,
+    prefabs: HashMap<String, Prefab>,
+}
+
+impl PrefabRegistry {
+    /// Create an empty registry
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a component type deserialized directly from its table
+    pub fn register_component<T>(&mut self, name: impl Into<String>)
+    where
+        T: Component + DeserializeOwned,
+    {
+        self.register_component_with::<T, T>(name, |component| component);
+    }
+
+    /// Register a component type read through an intermediate format
+    ///
+    /// Useful when the file should not mirror the component's fields,
+    /// e.g. `x`/`y` in the file for a position that stores two `Vec2`s.
+    pub fn register_component_with<T, D>(&mut self, name: impl Into<String>, convert: fn(D) -> T)
+    where
+        T: Component,
+        D: DeserializeOwned + 'static,
+    {
+        let loader: ComponentLoader = Box::new(move |value| {
+            let component = convert(deserialize::<D>(value)?);
+            Ok(Box::new(move |world: &mut World, entity| {
+                world.insert(entity, component)
+            }))
+        });
+        self.components.insert(name.into(), loader);
+    }
+
+    /// Register a component type filled field by field through [`Reflect`]
+    ///
+    /// Fields missing from the file keep their `Default` value. A `Vec2`
+    /// field is written as a two-number array.
+    pub fn register_reflected<T>(&mut self, name: impl Into<String>)
+    where
+        T: Reflect + Default,
+    {
+        let loader: ComponentLoader = Box::new(|value| {
+            let component = reflect_from_toml::<T>(value)?;
+            Ok(Box::new(move |world: &mut World, entity| {
+                world.insert(entity, component)
+            }))
+        });
+        self.components.insert(name.into(), loader);
+    }
+
+    /// Check if a component name is registered
+    #[must_use]
+    pub fn has_component(&self, name: &str) -> bool {
+        self.components.contains_key(name)
+    }
+
+    /// Load a prefab from a TOML string
+    pub fn load_from_str(&mut self, name: &str, content: &str) -> Result<(), PrefabError> {
+        let table: toml::Table = toml::from_str(content).map_err(|e| PrefabError::Parse {
+            prefab: name.to_string(),
+            error: e.to_string(),
+        })?;
+
+        let mut components = Vec::with_capacity(table.len());
+        for (component, value) in table {
+            let loader =
+                self.components
+                    .get(&component)
+                    .ok_or_else(|| PrefabError::UnknownComponent {
+                        prefab: name.to_string(),
+                        component: component.clone(),
+                    })?;
+
+            // Deserialize once now so mistakes show up at load time
+            if let Err(e) = loader(value.clone()) {
+                return Err(PrefabError::InvalidComponent {
+                    prefab: name.to_string(),
+                    component,
+                    error: e.to_string(),
+                });
+            }
+            components.push((component, value));
+        }
+
+        self.prefabs.insert(
+            name.to_string(),
+            Prefab {
+                name: name.to_string(),
+                components,
+            },
+        );
+        Ok(())
+    }
+
+    /// Load a prefab file, named after its file stem
+    pub fn load_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<String, PrefabError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| PrefabError::Io {
+            path: path.to_string_lossy().to_string(),
+            error: e.to_string(),
+        })?;
+
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.load_from_str(&name, &content)?;
+        Ok(name)
+    }
+
+    /// Load every `*.toml` file of a directory
+    ///
+    /// Returns the number of prefabs loaded.
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<usize, PrefabError> {
+        let dir = dir.as_ref();
+        let io_error = |e: std::io::Error| PrefabError::Io {
+            path: dir.to_string_lossy().to_string(),
+            error: e.to_string(),
+        };
+
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            if path.extension().is_some_and(|ext| ext == "toml") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        for path in &paths {
+            self.load_from_file(path)?;
+        }
+        Ok(paths.len())
+    }
+
+    /// Get a prefab by name
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    /// Get the number of loaded prefabs
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.prefabs.len()
+    }
+
+    /// Check if no prefab is loaded
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.prefabs.is_empty()
+    }
+
+    /// Spawn an entity with the components of a prefab
+    pub fn spawn(&self, world: &mut World, name: &str) -> Result<Entity, PrefabError> {
+        let prefab = self
+            .get(name)
+            .ok_or_else(|| PrefabError::UnknownPrefab(name.to_string()))?;
+
+        // Build every component before spawning so a failure leaves no entity
+        let mut inserts = Vec::with_capacity(prefab.components.len());
+        for (component, value) in &prefab.components {
+            let insert = self.components[component](value.clone()).map_err(|e| {
+                PrefabError::InvalidComponent {
+                    prefab: name.to_string(),
+                    component: component.clone(),
+                    error: e.to_string(),
+                }
+            })?;
+            inserts.push(insert);
+        }
+
+        let entity = world.spawn();
+        for insert in inserts {
+            insert(world, entity);
+        }
+        Ok(entity)
+    }
+}
+
+/// Deserialize a component value, accepting an empty table for unit structs
+fn deserialize<D: DeserializeOwned>(value: toml::Value) -> Result<D, toml::de::Error> {
+    if value.as_table().is_some_and(toml::Table::is_empty) {
+        if let Ok(unit) = D::deserialize(UnitDeserializer::<toml::de::Error>::new()) {
+            return Ok(unit);
+        }
+    }
+    value.try_into()
+}
+
+/// Build a reflected component from a TOML table
+fn reflect_from_toml<T: Reflect + Default>(value: toml::Value) -> Result<T, toml::de::Error> {
+    let toml::Value::Table(table) = value else {
+        return Err(toml::de::Error::custom("expected a table"));
+    };
+
+    let mut component = T::default();
+    for (field, value) in table {
+        let value = field_from_toml(value)
+            .ok_or_else(|| toml::de::Error::custom(format!("unsupported value for '{}'", field)))?;
+        component
+            .set_field(&field, value)
+            .map_err(toml::de::Error::custom)?;
+    }
+    Ok(component)
+}
+
+/// Convert a TOML value to a reflected field value
+fn field_from_toml(value: toml::Value) -> Option<FieldValue> {
+    #[allow(clippy::cast_possible_truncation)]
+    match value {
+        toml::Value::Boolean(v) => Some(FieldValue::Bool(v)),
+        toml::Value::Integer(v) => Some(FieldValue::Int(v)),
+        toml::Value::Float(v) => Some(FieldValue::Float(v as f32)),
+        toml::Value::String(v) => Some(FieldValue::String(v)),
+        toml::Value::Array(items) => match items.as_slice() {
+            [x, y] => {
+                let number =
+                    |v: &toml::Value| v.as_float().or_else(|| v.as_integer().map(|i| i as f64));
+                Some(FieldValue::Vec2(glam::Vec2::new(
+                    number(x)? as f32,
+                    number(y)? as f32,
+                )))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl World {
+    /// Spawn an entity from a prefab of the [`PrefabRegistry`] resource
+    pub fn spawn_prefab(&mut self, name: &str) -> Result<Entity, PrefabError> {
+        let registry = self
+            .remove_resource::<PrefabRegistry>()
+            .ok_or(PrefabError::NoRegistry)?;
+        let result = registry.spawn(self, name);
+        self.insert_resource(registry);
+        result
+    }
+}
+
+/// Prefab loading and spawning errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrefabError {
+    Io {
+        path: String,
+        error: String,
+    },
+    Parse {
+        prefab: String,
+        error: String,
+    },
+    UnknownComponent {
+        prefab: String,
+        component: String,
+    },
+    InvalidComponent {
+        prefab: String,
+        component: String,
+        error: String,
+    },
+    UnknownPrefab(String),
+    NoRegistry,
+}
+
+impl fmt::Display for PrefabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "Failed to read {}: {}", path, error),
+            Self::Parse { prefab, error } => {
+                write!(f, "Failed to parse prefab '{}': {}", prefab, error)
+            }
+            Self::UnknownComponent { prefab, component } => {
+                write!(
+                    f,
+                    "Prefab '{}' uses unregistered component '{}'",
+                    prefab, component
+                )
+            }
+            Self::InvalidComponent {
+                prefab,
+                component,
+                error,
+            } => write!(
+                f,
+                "Prefab '{}' has invalid component '{}': {}",
+                prefab, component, error
+            ),
+            Self::UnknownPrefab(name) => write!(f, "Unknown prefab '{}'", name),
+            Self::NoRegistry => write!(f, "No PrefabRegistry resource in the world"),
+        }
+    }
+}
+
+impl std::error::Error for PrefabError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Velocity {
+        x: f32,
+        y: f32,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Marker;
+
+    #[derive(Debug, PartialEq)]
+    struct Collider {
+        half_width: f32,
+        half_height: f32,
+    }
+
+    #[derive(Deserialize)]
+    struct ColliderDef {
+        width: f32,
+        height: f32,
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Npc {
+        home: glam::Vec2,
+        speed: f32,
+        friendly: bool,
+    }
+
+    crate::impl_reflect!(Npc {
+        home: glam::Vec2,
+        speed: f32,
+        friendly: bool,
+    });
+
+    const PLAYER: &str = r#"
+        [Velocity]
+        x = 1.0
+        y = -2.0
+
+        [Marker]
+
+        [Collider]
+        width = 12.0
+        height = 8.0
+    "#;
+
+    fn registry() -> PrefabRegistry {
+        let mut registry = PrefabRegistry::new();
+        registry.register_component::<Velocity>("Velocity");
+        registry.register_component::<Marker>("Marker");
+        registry.register_component_with("Collider", |def: ColliderDef| Collider {
+            half_width: def.width * 0.5,
+            half_height: def.height * 0.5,
+        });
+        registry
+    }
+
+    #[test]
+    fn test_spawn_prefab() {
+        let mut registry = registry();
+        registry.load_from_str("player", PLAYER).unwrap();
+        assert_eq!(registry.len(), 1);
+
+        let mut world = World::new();
+        world.insert_resource(registry);
+        let player = world.spawn_prefab("player").unwrap();
+
+        assert_eq!(
+            world.get::<Velocity>(player),
+            Some(&Velocity { x: 1.0, y: -2.0 })
+        );
+        assert!(world.has::<Marker>(player));
+        assert_eq!(
+            world.get::<Collider>(player),
+            Some(&Collider {
+                half_width: 6.0,
+                half_height: 4.0
+            })
+        );
+
+        // Each spawn is a fresh entity
+        let other = world.spawn_prefab("player").unwrap();
+        assert_ne!(player, other);
+        assert!(world.has_resource::<PrefabRegistry>());
+    }
+
+    #[test]
+    fn test_unknown_component_is_reported_at_load() {
+        let mut registry = registry();
+        let err = registry
+            .load_from_str("crate", "[Health]\nvalue = 3")
+            .unwrap_err();
+        assert_eq!(
+            err,
+            PrefabError::UnknownComponent {
+                prefab: "crate".into(),
+                component: "Health".into(),
+            }
+        );
+        assert!(registry.get("crate").is_none());
+    }
+
+    #[test]
+    fn test_invalid_fields_are_reported_at_load() {
+        let mut registry = registry();
+        let err = registry
+            .load_from_str("npc", "[Velocity]\nx = \"fast\"\ny = 0.0")
+            .unwrap_err();
+        assert!(
+            matches!(err, PrefabError::InvalidComponent { ref component, .. } if component == "Velocity")
+        );
+        assert!(err
+            .to_string()
+            .starts_with("Prefab 'npc' has invalid component 'Velocity'"));
+    }
+
+    #[test]
+    fn test_reflected_component() {
+        let mut registry = registry();
+        registry.register_reflected::<Npc>("Npc");
+        registry
+            .load_from_str("villager", "[Npc]\nhome = [4, 2.5]\nspeed = 30")
+            .unwrap();
+
+        let mut world = World::new();
+        let villager = registry.spawn(&mut world, "villager").unwrap();
+        assert_eq!(
+            world.get::<Npc>(villager),
+            Some(&Npc {
+                home: glam::Vec2::new(4.0, 2.5),
+                speed: 30.0,
+                friendly: false,
+            })
+        );
+
+        let err = registry
+            .load_from_str("grumpy", "[Npc]\nfriendly = \"no\"")
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Field 'friendly' expects bool, got String"));
+    }
+
+    #[test]
+    fn test_spawn_errors() {
+        let mut world = World::new();
+        assert_eq!(world.spawn_prefab("player"), Err(PrefabError::NoRegistry));
+
+        world.insert_resource(registry());
+        assert_eq!(
+            world.spawn_prefab("player"),
+            Err(PrefabError::UnknownPrefab("player".into()))
+        );
+        assert_eq!(world.entity_count(), 0);
+    }
+}
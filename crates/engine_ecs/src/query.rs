@@ -0,0 +1,1124 @@
+//! Queries over one or more component types
+//!
+//! A query names the components it wants as a type: `&T` for shared access,
+//! `&mut T` for mutable access, `Option<&T>` for components that may be
+//! missing, or a tuple of those. An optional filter (`With<T>`, `Without<T>`,
+//! `Added<T>`, `Changed<T>`) restricts which entities match without fetching
+//! anything. Iteration walks the shortest entity list involved (a sparse
+//! set, or the archetype tables holding a table component) and joins the
+//! others by entity index.
+
+use std::any::{type_name, TypeId};
+use std::marker::PhantomData;
+
+use crate::storage::{ComponentTicks, ComponentView, ComponentViewMut, EntityList};
+use crate::table::{EntityLocation, Tables};
+use crate::{Component, Entity, World};
+
+/// Component types borrowed by a query
+#[derive(Debug, Clone, Default)]
+pub struct Access {
+    /// Types borrowed immutably
+    reads: Vec<(TypeId, &'static str)>,
+    /// Types borrowed mutably
+    writes: Vec<(TypeId, &'static str)>,
+}
+
+impl Access {
+    /// Create an empty access set
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a shared borrow of `T`
+    pub fn add_read<T: 'static>(&mut self) {
+        self.reads.push((TypeId::of::<T>(), type_name::<T>()));
+    }
+
+    /// Record a mutable borrow of `T`
+    pub fn add_write<T: 'static>(&mut self) {
+        self.writes.push((TypeId::of::<T>(), type_name::<T>()));
+    }
+
+    /// Find a type borrowed mutably alongside any other borrow of itself
+    ///
+    /// Returns the name of the first offending type.
+    #[must_use]
+    pub fn self_conflict(&self) -> Option<&'static str> {
+        self.writes.iter().enumerate().find_map(|(i, &(id, name))| {
+            let written_twice = self.writes[i + 1..].iter().any(|&(other, _)| other == id);
+            let also_read = self.reads.iter().any(|&(other, _)| other == id);
+            (written_twice || also_read).then_some(name)
+        })
+    }
+
+    /// Add every borrow of another access set
+    pub fn extend(&mut self, other: &Self) {
+        self.reads.extend_from_slice(&other.reads);
+        self.writes.extend_from_slice(&other.writes);
+    }
+
+    /// Names of the types one set writes and the other reads or writes
+    #[must_use]
+    pub fn conflicts_with(&self, other: &Self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        let pairs = [
+            (&self.writes, &other.reads),
+            (&self.writes, &other.writes),
+            (&self.reads, &other.writes),
+        ];
+        for (ours, theirs) in pairs {
+            for &(id, name) in ours {
+                if theirs.iter().any(|&(other, _)| other == id) && !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Find a borrow not allowed by a declared access set
+    ///
+    /// Reads are covered by declared reads or writes; writes only by
+    /// declared writes. Returns the name of the first uncovered type.
+    #[must_use]
+    pub fn first_undeclared(&self, declared: &Self) -> Option<&'static str> {
+        let covers = |list: &[(TypeId, &'static str)], id: TypeId| {
+            list.iter().any(|&(other, _)| other == id)
+        };
+        self.reads
+            .iter()
+            .find(|&&(id, _)| !covers(&declared.reads, id) && !covers(&declared.writes, id))
+            .or_else(|| {
+                self.writes
+                    .iter()
+                    .find(|&&(id, _)| !covers(&declared.writes, id))
+            })
+            .map(|&(_, name)| name)
+    }
+
+    /// Check if nothing is borrowed
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.reads.is_empty() && self.writes.is_empty()
+    }
+}
+
+/// Entity indices every match of a query must appear in
+///
+/// A sparse-set component gives a single list; a table component gives one
+/// per archetype storing it.
+#[derive(Default)]
+pub struct Driver<'w> {
+    lists: Vec<EntityList<'w>>,
+}
+
+impl<'w> Driver<'w> {
+    fn new(lists: Vec<EntityList<'w>>) -> Self {
+        Self { lists }
+    }
+
+    /// Total number of entity indices
+    fn len(&self) -> usize {
+        self.lists.iter().map(|list| list.entities.len()).sum()
+    }
+
+    /// Keep the shorter of two drivers, the first one on a tie
+    fn shortest(current: Option<Self>, other: Option<Self>) -> Option<Self> {
+        match (current, other) {
+            (Some(current), Some(other)) if other.len() < current.len() => Some(other),
+            (current, other) => current.or(other),
+        }
+    }
+}
+
+/// Data fetched by a query for each matching entity
+///
+/// Implemented for `&T`, `&mut T` and tuples of up to eight query types.
+///
+/// Borrowing the same component mutably twice (`(&mut A, &mut A)`) can't
+/// be rejected by the compiler: the tuple impls are generic over their
+/// elements, and stable Rust can neither require two type parameters to
+/// differ nor compare `TypeId`s in constants. It is caught as early as the
+/// types allow instead. A parallel system declaring such a query through
+/// [`SystemAccess::query`](crate::SystemAccess::query) fails
+/// [`Schedule::build`](crate::Schedule::build), which runs before the first
+/// frame, and every query checks its [`Access`] when created, panicking
+/// before any reference is handed out.
+///
+/// ```should_panic
+/// # use engine_ecs::World;
+/// struct Velocity(f32);
+///
+/// let mut world = World::new();
+/// world.query_mut::<(&mut Velocity, &mut Velocity)>();
+/// ```
+///
+/// What the types do enforce is which world borrow a query needs: a query
+/// with any `&mut T` is not [`ReadOnlyQueryData`], so it can only be made
+/// through `&mut World`.
+///
+/// ```compile_fail
+/// # use engine_ecs::World;
+/// struct Velocity(f32);
+///
+/// fn nudge(world: &World) {
+///     for (_, velocity) in world.query_mut::<&mut Velocity>() {
+///         velocity.0 += 1.0;
+///     }
+/// }
+/// ```
+///
+/// ```compile_fail
+/// # use engine_ecs::World;
+/// struct Velocity(f32);
+///
+/// fn nudge(world: &World) {
+///     for (_, velocity) in world.query::<&mut Velocity>() {
+///         velocity.0 += 1.0;
+///     }
+/// }
+/// ```
+///
+/// Entities are visited grouped by archetype where possible: the query is
+/// told each time the archetype changes, so table components can look up
+/// their column once and then fetch by row.
+///
+/// # Safety
+/// Implementations must declare every component they borrow in
+/// [`QueryData::access`], and must only write to storages declared as
+/// writes.
+pub unsafe trait QueryData {
+    /// Item yielded for each matching entity
+    type Item<'w>;
+    /// Storages borrowed for the duration of the query
+    type Fetch<'w>;
+
+    /// Record the component types this query borrows
+    fn access(access: &mut Access);
+
+    /// Look up the storages, returning `None` if a required one is missing
+    ///
+    /// # Safety
+    /// If this query declares writes, nothing else may borrow those
+    /// storages for `'w`.
+    unsafe fn init_fetch<'w>(world: &'w World) -> Option<Self::Fetch<'w>>;
+
+    /// Shortest list of entity indices every match must appear in
+    fn driver<'w>(fetch: &Self::Fetch<'w>) -> Option<Driver<'w>>;
+
+    /// Prepare for entities of another archetype (`None` for entities
+    /// without table components), returning `false` if none of them can
+    /// match
+    fn set_archetype(fetch: &mut Self::Fetch<'_>, archetype: Option<usize>) -> bool;
+
+    /// Check whether an entity of the current archetype has everything
+    /// this query needs
+    fn matches(fetch: &Self::Fetch<'_>, entity_index: u32, row: usize) -> bool;
+
+    /// Fetch the item of a matching entity
+    ///
+    /// # Safety
+    /// The entity must satisfy [`QueryData::matches`] with `row` as its row
+    /// in the current archetype, and must not be fetched again while a
+    /// mutable item for it is still alive.
+    unsafe fn fetch<'w>(fetch: &Self::Fetch<'w>, entity_index: u32, row: usize) -> Self::Item<'w>;
+}
+
+/// Marker for queries that never borrow mutably
+///
+/// # Safety
+/// Implementations must not declare any write in [`QueryData::access`].
+pub unsafe trait ReadOnlyQueryData: QueryData {}
+
+/// Fetch state for `&T`
+pub struct ReadFetch<'w, T> {
+    view: ComponentView<'w, T>,
+}
+
+/// Fetch state for `&mut T`
+pub struct WriteFetch<'w, T> {
+    view: ComponentViewMut<'w, T>,
+    change_tick: u32,
+}
+
+unsafe impl<T: Component> QueryData for &T {
+    type Item<'w> = &'w T;
+    type Fetch<'w> = ReadFetch<'w, T>;
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    unsafe fn init_fetch<'w>(world: &'w World) -> Option<Self::Fetch<'w>> {
+        world.view::<T>().map(|view| ReadFetch { view })
+    }
+
+    fn driver<'w>(fetch: &Self::Fetch<'w>) -> Option<Driver<'w>> {
+        Some(Driver::new(fetch.view.entities()))
+    }
+
+    fn set_archetype(fetch: &mut Self::Fetch<'_>, archetype: Option<usize>) -> bool {
+        fetch.view.set_archetype(archetype)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity_index: u32, _row: usize) -> bool {
+        fetch.view.contains(entity_index)
+    }
+
+    unsafe fn fetch<'w>(fetch: &Self::Fetch<'w>, entity_index: u32, row: usize) -> Self::Item<'w> {
+        fetch
+            .view
+            .get(entity_index, row)
+            .expect("entity matched the query")
+    }
+}
+
+unsafe impl<T: Component> ReadOnlyQueryData for &T {}
+
+unsafe impl<T: Component> QueryData for &mut T {
+    type Item<'w> = &'w mut T;
+    type Fetch<'w> = WriteFetch<'w, T>;
+
+    fn access(access: &mut Access) {
+        access.add_write::<T>();
+    }
+
+    unsafe fn init_fetch<'w>(world: &'w World) -> Option<Self::Fetch<'w>> {
+        let change_tick = world.change_tick();
+        world
+            .view_mut::<T>()
+            .map(|view| WriteFetch { view, change_tick })
+    }
+
+    fn driver<'w>(fetch: &Self::Fetch<'w>) -> Option<Driver<'w>> {
+        Some(Driver::new(fetch.view.entities()))
+    }
+
+    fn set_archetype(fetch: &mut Self::Fetch<'_>, archetype: Option<usize>) -> bool {
+        fetch.view.set_archetype(archetype)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity_index: u32, _row: usize) -> bool {
+        fetch.view.contains(entity_index)
+    }
+
+    unsafe fn fetch<'w>(fetch: &Self::Fetch<'w>, entity_index: u32, row: usize) -> Self::Item<'w> {
+        // Handing out a mutable borrow counts as a change
+        fetch.view.get_mut(entity_index, row, fetch.change_tick)
+    }
+}
+
+macro_rules! impl_query_data_tuple {
+    ($($name:ident),+) => {
+        #[allow(non_snake_case)]
+        unsafe impl<$($name: QueryData),+> QueryData for ($($name,)+) {
+            type Item<'w> = ($($name::Item<'w>,)+);
+            type Fetch<'w> = ($($name::Fetch<'w>,)+);
+
+            fn access(access: &mut Access) {
+                $($name::access(access);)+
+            }
+
+            unsafe fn init_fetch<'w>(world: &'w World) -> Option<Self::Fetch<'w>> {
+                Some(($($name::init_fetch(world)?,)+))
+            }
+
+            fn driver<'w>(fetch: &Self::Fetch<'w>) -> Option<Driver<'w>> {
+                let ($($name,)+) = fetch;
+                let mut driver = None;
+                $(driver = Driver::shortest(driver, $name::driver($name));)+
+                driver
+            }
+
+            fn set_archetype(fetch: &mut Self::Fetch<'_>, archetype: Option<usize>) -> bool {
+                let ($($name,)+) = fetch;
+                $($name::set_archetype($name, archetype))&&+
+            }
+
+            fn matches(fetch: &Self::Fetch<'_>, entity_index: u32, row: usize) -> bool {
+                let ($($name,)+) = fetch;
+                $($name::matches($name, entity_index, row))&&+
+            }
+
+            unsafe fn fetch<'w>(
+                fetch: &Self::Fetch<'w>,
+                entity_index: u32,
+                row: usize,
+            ) -> Self::Item<'w> {
+                let ($($name,)+) = fetch;
+                ($($name::fetch($name, entity_index, row),)+)
+            }
+        }
+
+        unsafe impl<$($name: ReadOnlyQueryData),+> ReadOnlyQueryData for ($($name,)+) {}
+    };
+}
+
+impl_query_data_tuple!(A);
+impl_query_data_tuple!(A, B);
+impl_query_data_tuple!(A, B, C);
+impl_query_data_tuple!(A, B, C, D);
+impl_query_data_tuple!(A, B, C, D, E);
+impl_query_data_tuple!(A, B, C, D, E, F);
+impl_query_data_tuple!(A, B, C, D, E, F, G);
+impl_query_data_tuple!(A, B, C, D, E, F, G, H);
+
+/// Fetch state for `Option<Q>`
+pub struct OptionFetch<F> {
+    fetch: Option<F>,
+    /// Whether the current archetype can have `Q` at all
+    archetype_matches: bool,
+}
+
+unsafe impl<Q: QueryData> QueryData for Option<Q> {
+    type Item<'w> = Option<Q::Item<'w>>;
+    type Fetch<'w> = OptionFetch<Q::Fetch<'w>>;
+
+    fn access(access: &mut Access) {
+        Q::access(access);
+    }
+
+    unsafe fn init_fetch<'w>(world: &'w World) -> Option<Self::Fetch<'w>> {
+        Some(OptionFetch {
+            fetch: Q::init_fetch(world),
+            archetype_matches: false,
+        })
+    }
+
+    fn driver<'w>(_fetch: &Self::Fetch<'w>) -> Option<Driver<'w>> {
+        None
+    }
+
+    fn set_archetype(fetch: &mut Self::Fetch<'_>, archetype: Option<usize>) -> bool {
+        fetch.archetype_matches = fetch
+            .fetch
+            .as_mut()
+            .is_some_and(|inner| Q::set_archetype(inner, archetype));
+        true
+    }
+
+    fn matches(_fetch: &Self::Fetch<'_>, _entity_index: u32, _row: usize) -> bool {
+        true
+    }
+
+    unsafe fn fetch<'w>(fetch: &Self::Fetch<'w>, entity_index: u32, row: usize) -> Self::Item<'w> {
+        fetch
+            .fetch
+            .as_ref()
+            .filter(|inner| fetch.archetype_matches && Q::matches(inner, entity_index, row))
+            .map(|inner| Q::fetch(inner, entity_index, row))
+    }
+}
+
+unsafe impl<Q: ReadOnlyQueryData> ReadOnlyQueryData for Option<Q> {}
+
+/// Restricts which entities a query matches without fetching data
+///
+/// Implemented for [`With`], [`Without`], [`Added`], [`Changed`], `()`
+/// (no filter) and tuples of up to eight filters, which must all pass.
+pub trait QueryFilter {
+    /// Storages looked at while filtering
+    type Fetch<'w>;
+
+    /// Record the component types this filter looks at
+    fn access(access: &mut Access);
+
+    /// Look up the storages this filter checks
+    ///
+    /// `last_run` is the tick [`Added`] and [`Changed`] compare against.
+    fn init_fetch(world: &World, last_run: u32) -> Self::Fetch<'_>;
+
+    /// Shortest list of entity indices every match must appear in
+    fn driver<'w>(fetch: &Self::Fetch<'w>) -> Option<Driver<'w>>;
+
+    /// Prepare for entities of another archetype, returning `false` if
+    /// none of them can pass
+    fn set_archetype(fetch: &mut Self::Fetch<'_>, archetype: Option<usize>) -> bool;
+
+    /// Check whether an entity of the current archetype passes the filter
+    fn matches(fetch: &Self::Fetch<'_>, entity_index: u32, row: usize) -> bool;
+}
+
+/// Filter matching entities that have component `T`
+pub struct With<T>(PhantomData<T>);
+
+/// Filter matching entities that do not have component `T`
+pub struct Without<T>(PhantomData<T>);
+
+/// Fetch state for [`With`] and [`Without`]
+pub struct FilterFetch<'w, T> {
+    view: Option<ComponentView<'w, T>>,
+}
+
+impl<'w, T: Component> FilterFetch<'w, T> {
+    fn new(world: &'w World) -> Self {
+        Self {
+            view: world.view::<T>(),
+        }
+    }
+
+    /// Switch archetype, returning whether it can have `T`
+    fn set_archetype(&mut self, archetype: Option<usize>) -> bool {
+        self.view
+            .as_mut()
+            .is_some_and(|view| view.set_archetype(archetype))
+    }
+
+    fn contains(&self, entity_index: u32) -> bool {
+        self.view
+            .as_ref()
+            .is_some_and(|view| view.contains(entity_index))
+    }
+
+    fn driver(&self) -> Driver<'w> {
+        Driver::new(
+            self.view
+                .as_ref()
+                .map_or_else(Vec::new, ComponentView::entities),
+        )
+    }
+}
+
+impl<T: Component> QueryFilter for With<T> {
+    type Fetch<'w> = FilterFetch<'w, T>;
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn init_fetch(world: &World, _last_run: u32) -> Self::Fetch<'_> {
+        FilterFetch::new(world)
+    }
+
+    fn driver<'w>(fetch: &Self::Fetch<'w>) -> Option<Driver<'w>> {
+        Some(fetch.driver())
+    }
+
+    fn set_archetype(fetch: &mut Self::Fetch<'_>, archetype: Option<usize>) -> bool {
+        fetch.set_archetype(archetype)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity_index: u32, _row: usize) -> bool {
+        fetch.contains(entity_index)
+    }
+}
+
+impl<T: Component> QueryFilter for Without<T> {
+    type Fetch<'w> = FilterFetch<'w, T>;
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn init_fetch(world: &World, _last_run: u32) -> Self::Fetch<'_> {
+        FilterFetch::new(world)
+    }
+
+    fn driver<'w>(_fetch: &Self::Fetch<'w>) -> Option<Driver<'w>> {
+        None
+    }
+
+    fn set_archetype(fetch: &mut Self::Fetch<'_>, archetype: Option<usize>) -> bool {
+        // Sparse-set components are only known entity by entity
+        fetch.set_archetype(archetype);
+        true
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity_index: u32, _row: usize) -> bool {
+        !fetch.contains(entity_index)
+    }
+}
+
+/// Filter matching entities whose `T` was added since the last run
+///
+/// "Last run" is the previous run of the current system in a
+/// [`Schedule`](crate::Schedule), or the previous
+/// [`World::clear_trackers`] call outside of one.
+pub struct Added<T>(PhantomData<T>);
+
+/// Filter matching entities whose `T` was added or mutably accessed since
+/// the last run
+///
+/// Any `&mut T` query item or [`World::get_mut`] call counts as a change,
+/// even if nothing is written. Like every filter, it reads the storage of
+/// `T`, so it can't be combined with `&mut T` in the same query.
+pub struct Changed<T>(PhantomData<T>);
+
+/// Fetch state for [`Added`] and [`Changed`]
+pub struct TickFetch<'w, T> {
+    filter: FilterFetch<'w, T>,
+    last_run: u32,
+}
+
+impl<'w, T: Component> TickFetch<'w, T> {
+    fn new(world: &'w World, last_run: u32) -> Self {
+        Self {
+            filter: FilterFetch::new(world),
+            last_run,
+        }
+    }
+
+    fn ticks(&self, entity_index: u32, row: usize) -> Option<ComponentTicks> {
+        self.filter
+            .view
+            .as_ref()
+            .and_then(|view| view.ticks(entity_index, row))
+    }
+}
+
+impl<T: Component> QueryFilter for Added<T> {
+    type Fetch<'w> = TickFetch<'w, T>;
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn init_fetch(world: &World, last_run: u32) -> Self::Fetch<'_> {
+        TickFetch::new(world, last_run)
+    }
+
+    fn driver<'w>(fetch: &Self::Fetch<'w>) -> Option<Driver<'w>> {
+        Some(fetch.filter.driver())
+    }
+
+    fn set_archetype(fetch: &mut Self::Fetch<'_>, archetype: Option<usize>) -> bool {
+        fetch.filter.set_archetype(archetype)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity_index: u32, row: usize) -> bool {
+        fetch
+            .ticks(entity_index, row)
+            .is_some_and(|ticks| ticks.is_added(fetch.last_run))
+    }
+}
+
+impl<T: Component> QueryFilter for Changed<T> {
+    type Fetch<'w> = TickFetch<'w, T>;
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn init_fetch(world: &World, last_run: u32) -> Self::Fetch<'_> {
+        TickFetch::new(world, last_run)
+    }
+
+    fn driver<'w>(fetch: &Self::Fetch<'w>) -> Option<Driver<'w>> {
+        Some(fetch.filter.driver())
+    }
+
+    fn set_archetype(fetch: &mut Self::Fetch<'_>, archetype: Option<usize>) -> bool {
+        fetch.filter.set_archetype(archetype)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity_index: u32, row: usize) -> bool {
+        fetch
+            .ticks(entity_index, row)
+            .is_some_and(|ticks| ticks.is_changed(fetch.last_run))
+    }
+}
+
+impl QueryFilter for () {
+    type Fetch<'w> = ();
+
+    fn access(_access: &mut Access) {}
+
+    fn init_fetch(_world: &World, _last_run: u32) -> Self::Fetch<'_> {}
+
+    fn driver<'w>(_fetch: &Self::Fetch<'w>) -> Option<Driver<'w>> {
+        None
+    }
+
+    fn set_archetype(_fetch: &mut Self::Fetch<'_>, _archetype: Option<usize>) -> bool {
+        true
+    }
+
+    fn matches(_fetch: &Self::Fetch<'_>, _entity_index: u32, _row: usize) -> bool {
+        true
+    }
+}
+
+macro_rules! impl_query_filter_tuple {
+    ($($name:ident),+) => {
+        #[allow(non_snake_case)]
+        impl<$($name: QueryFilter),+> QueryFilter for ($($name,)+) {
+            type Fetch<'w> = ($($name::Fetch<'w>,)+);
+
+            fn access(access: &mut Access) {
+                $($name::access(access);)+
+            }
+
+            fn init_fetch(world: &World, last_run: u32) -> Self::Fetch<'_> {
+                ($($name::init_fetch(world, last_run),)+)
+            }
+
+            fn driver<'w>(fetch: &Self::Fetch<'w>) -> Option<Driver<'w>> {
+                let ($($name,)+) = fetch;
+                let mut driver = None;
+                $(driver = Driver::shortest(driver, $name::driver($name));)+
+                driver
+            }
+
+            fn set_archetype(fetch: &mut Self::Fetch<'_>, archetype: Option<usize>) -> bool {
+                let ($($name,)+) = fetch;
+                $($name::set_archetype($name, archetype))&&+
+            }
+
+            fn matches(fetch: &Self::Fetch<'_>, entity_index: u32, row: usize) -> bool {
+                let ($($name,)+) = fetch;
+                $($name::matches($name, entity_index, row))&&+
+            }
+        }
+    };
+}
+
+impl_query_filter_tuple!(A);
+impl_query_filter_tuple!(A, B);
+impl_query_filter_tuple!(A, B, C);
+impl_query_filter_tuple!(A, B, C, D);
+impl_query_filter_tuple!(A, B, C, D, E);
+impl_query_filter_tuple!(A, B, C, D, E, F);
+impl_query_filter_tuple!(A, B, C, D, E, F, G);
+impl_query_filter_tuple!(A, B, C, D, E, F, G, H);
+
+/// Entity indices a query iterator walks through
+enum Candidates<'w> {
+    /// Entities of the shortest driver involved
+    Listed {
+        lists: std::vec::IntoIter<EntityList<'w>>,
+        current: std::iter::Enumerate<std::slice::Iter<'w, u32>>,
+        /// Archetype of the current list, if it is a table's rows
+        archetype: Option<usize>,
+        remaining: usize,
+    },
+    /// Every entity slot, for queries with no required component
+    All(std::ops::Range<u32>),
+}
+
+impl<'w> Candidates<'w> {
+    fn listed(driver: Driver<'w>) -> Self {
+        Self::Listed {
+            remaining: driver.len(),
+            lists: driver.lists.into_iter(),
+            current: [].iter().enumerate(),
+            archetype: None,
+        }
+    }
+
+    /// Next entity index, with its table location when walking a table
+    fn next(&mut self) -> Option<(u32, Option<EntityLocation>)> {
+        match self {
+            Self::Listed {
+                lists,
+                current,
+                archetype,
+                remaining,
+            } => loop {
+                if let Some((row, &entity_index)) = current.next() {
+                    *remaining -= 1;
+                    let location = archetype.map(|archetype| EntityLocation { archetype, row });
+                    return Some((entity_index, location));
+                }
+                let list = lists.next()?;
+                *current = list.entities.iter().enumerate();
+                *archetype = list.archetype;
+            },
+            Self::All(range) => range.next().map(|entity_index| (entity_index, None)),
+        }
+    }
+
+    /// Skip the rest of the current table, whose archetype can't match
+    fn skip_table(&mut self) {
+        if let Self::Listed {
+            current,
+            archetype: Some(_),
+            remaining,
+            ..
+        } = self
+        {
+            *remaining -= current.len();
+            *current = [].iter().enumerate();
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Listed { remaining, .. } => *remaining,
+            Self::All(range) => range.len(),
+        }
+    }
+}
+
+/// Iterator over the entities matching a query
+pub struct QueryIter<'w, Q: QueryData, F: QueryFilter = ()> {
+    fetch: Option<Q::Fetch<'w>>,
+    filter: F::Fetch<'w>,
+    candidates: Candidates<'w>,
+    /// Archetype the fetches are set to, and whether it can match
+    archetype: Option<(Option<usize>, bool)>,
+    tables: &'w Tables,
+    generations: &'w [u32],
+    alive: &'w [bool],
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> QueryIter<'w, Q, F> {
+    /// Create a query iterator
+    ///
+    /// # Safety
+    /// If `Q` declares writes, nothing else may borrow those storages for
+    /// `'w`.
+    pub(crate) unsafe fn new(world: &'w World, last_run: u32) -> Self {
+        // Filters read the storages they check, so they count as borrows too
+        let mut access = Access::new();
+        Q::access(&mut access);
+        F::access(&mut access);
+        if let Some(name) = access.self_conflict() {
+            panic!(
+                "query `{}` filtered by `{}` borrows `{}` mutably along with another borrow",
+                type_name::<Q>(),
+                type_name::<F>(),
+                name
+            );
+        }
+
+        let fetch = Q::init_fetch(world);
+        let filter = F::init_fetch(world, last_run);
+
+        // Walk the shortest entity list any required component or filter gives us
+        let driver = Driver::shortest(fetch.as_ref().and_then(Q::driver), F::driver(&filter));
+        let candidates = match (&fetch, driver) {
+            (None, _) => Candidates::listed(Driver::default()),
+            (Some(_), Some(driver)) => Candidates::listed(driver),
+            (Some(_), None) => Candidates::All(0..world.generations.len() as u32),
+        };
+
+        Self {
+            fetch,
+            filter,
+            candidates,
+            archetype: None,
+            tables: world.tables(),
+            generations: &world.generations,
+            alive: &world.alive,
+        }
+    }
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Iterator for QueryIter<'w, Q, F> {
+    type Item = (Entity, Q::Item<'w>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let fetch = self.fetch.as_mut()?;
+
+        while let Some((entity_index, location)) = self.candidates.next() {
+            let index = entity_index as usize;
+            if !self.alive[index] {
+                continue;
+            }
+
+            // Entities listed by a sparse set are located one by one
+            let location = location.or_else(|| self.tables.location(entity_index));
+            let archetype = location.map(|location| location.archetype);
+            let archetype_matches = match self.archetype {
+                Some((current, matches)) if current == archetype => matches,
+                _ => {
+                    let matches = Q::set_archetype(fetch, archetype)
+                        && F::set_archetype(&mut self.filter, archetype);
+                    self.archetype = Some((archetype, matches));
+                    matches
+                }
+            };
+            if !archetype_matches {
+                self.candidates.skip_table();
+                continue;
+            }
+
+            let row = location.map_or(0, |location| location.row);
+            if Q::matches(fetch, entity_index, row) && F::matches(&self.filter, entity_index, row) {
+                let generation = self.generations[index];
+                // Safety: candidates list each entity once, so no item is fetched twice
+                let item = unsafe { Q::fetch(fetch, entity_index, row) };
+                return Some((Entity::new(entity_index, generation), item));
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.candidates.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(f32);
+
+    #[derive(Debug, PartialEq)]
+    struct Velocity(f32);
+
+    #[derive(Debug, PartialEq)]
+    struct Collider;
+
+    #[test]
+    fn test_tuple_query_joins_components() {
+        let mut world = World::new();
+
+        let moving = world.spawn();
+        world.insert(moving, Position(0.0));
+        world.insert(moving, Velocity(1.0));
+        world.insert(moving, Collider);
+
+        let still = world.spawn();
+        world.insert(still, Position(5.0));
+        world.insert(still, Collider);
+
+        let ghost = world.spawn();
+        world.insert(ghost, Position(9.0));
+        world.insert(ghost, Velocity(2.0));
+
+        let matches: Vec<_> = world
+            .query::<(&Position, &Velocity, &Collider)>()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(matches, vec![moving]);
+
+        let pairs = world.query::<(&Position, &Velocity)>().count();
+        assert_eq!(pairs, 2);
+    }
+
+    #[test]
+    fn test_tuple_query_mut() {
+        let mut world = World::new();
+
+        for i in 0..4 {
+            let entity = world.spawn();
+            world.insert(entity, Position(0.0));
+            if i % 2 == 0 {
+                world.insert(entity, Velocity(i as f32));
+            }
+        }
+
+        for (_, (pos, vel)) in world.query_mut::<(&mut Position, &Velocity)>() {
+            pos.0 += vel.0;
+        }
+
+        let mut positions: Vec<f32> = world.query::<&Position>().map(|(_, p)| p.0).collect();
+        positions.sort_by(f32::total_cmp);
+        assert_eq!(positions, vec![0.0, 0.0, 0.0, 2.0]);
+    }
+
+    #[test]
+    fn test_query_missing_storage_is_empty() {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.insert(entity, Position(1.0));
+
+        assert_eq!(world.query::<(&Position, &Velocity)>().count(), 0);
+        assert_eq!(world.query_mut::<&mut Velocity>().count(), 0);
+    }
+
+    #[test]
+    fn test_query_drives_from_smallest_storage() {
+        let mut world = World::new();
+
+        for _ in 0..10 {
+            let entity = world.spawn();
+            world.insert(entity, Position(0.0));
+        }
+        let tagged = world.spawn();
+        world.insert(tagged, Position(1.0));
+        world.insert(tagged, Collider);
+
+        let iter = world.query::<(&Position, &Collider)>();
+        assert_eq!(iter.size_hint(), (0, Some(1)));
+        assert_eq!(iter.count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "mutably")]
+    fn test_query_rejects_aliasing_mut() {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.insert(entity, Position(0.0));
+
+        let _ = world.query_mut::<(&mut Position, &Position)>();
+    }
+
+    #[test]
+    #[should_panic(expected = "mutably")]
+    fn test_query_rejects_filter_on_mut_component() {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.insert(entity, Position(0.0));
+
+        let _ = world.query_filtered_mut::<&mut Position, Changed<Position>>();
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Npc;
+
+    #[test]
+    fn test_query_with_without_filters() {
+        let mut world = World::new();
+
+        let player = world.spawn();
+        world.insert(player, Position(0.0));
+        world.insert(player, Velocity(1.0));
+
+        let npc = world.spawn();
+        world.insert(npc, Position(5.0));
+        world.insert(npc, Velocity(1.0));
+        world.insert(npc, Npc);
+
+        let prop = world.spawn();
+        world.insert(prop, Position(9.0));
+        world.insert(prop, Npc);
+
+        let movers: Vec<_> = world
+            .query_filtered::<&Position, (With<Velocity>, Without<Npc>)>()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(movers, vec![player]);
+
+        let npcs: Vec<_> = world
+            .query_filtered::<&Position, With<Npc>>()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(npcs, vec![npc, prop]);
+
+        // Filtering on a component no entity has
+        assert_eq!(
+            world.query_filtered::<&Position, With<Collider>>().count(),
+            0
+        );
+        assert_eq!(
+            world
+                .query_filtered::<&Position, Without<Collider>>()
+                .count(),
+            3
+        );
+
+        for (_, vel) in world.query_filtered_mut::<&mut Velocity, Without<Npc>>() {
+            vel.0 = 0.0;
+        }
+        assert_eq!(world.get::<Velocity>(player), Some(&Velocity(0.0)));
+        assert_eq!(world.get::<Velocity>(npc), Some(&Velocity(1.0)));
+    }
+
+    #[test]
+    fn test_query_optional_component() {
+        let mut world = World::new();
+
+        let a = world.spawn();
+        world.insert(a, Position(1.0));
+        world.insert(a, Collider);
+
+        let b = world.spawn();
+        world.insert(b, Position(2.0));
+
+        let results: Vec<_> = world
+            .query::<(&Position, Option<&Collider>)>()
+            .map(|(entity, (_, collider))| (entity, collider.is_some()))
+            .collect();
+        assert_eq!(results, vec![(a, true), (b, false)]);
+
+        for (_, (pos, collider)) in world.query_mut::<(&mut Position, Option<&Collider>)>() {
+            if collider.is_none() {
+                pos.0 = 0.0;
+            }
+        }
+        assert_eq!(world.get::<Position>(b), Some(&Position(0.0)));
+    }
+
+    #[test]
+    fn test_query_only_optional_visits_all_entities() {
+        let mut world = World::new();
+
+        let a = world.spawn();
+        let b = world.spawn();
+        let c = world.spawn();
+        world.insert(b, Velocity(3.0));
+        world.despawn(c);
+
+        let results: Vec<_> = world
+            .query::<Option<&Velocity>>()
+            .map(|(entity, vel)| (entity, vel.map(|v| v.0)))
+            .collect();
+        assert_eq!(results, vec![(a, None), (b, Some(3.0))]);
+    }
+
+    #[test]
+    fn test_query_added_changed_filters() {
+        let mut world = World::new();
+
+        let a = world.spawn();
+        world.insert(a, Position(0.0));
+        let b = world.spawn();
+        world.insert(b, Position(1.0));
+
+        assert_eq!(
+            world.query_filtered::<&Position, Added<Position>>().count(),
+            2
+        );
+        world.clear_trackers();
+        assert_eq!(
+            world.query_filtered::<&Position, Added<Position>>().count(),
+            0
+        );
+        assert_eq!(
+            world
+                .query_filtered::<&Position, Changed<Position>>()
+                .count(),
+            0
+        );
+
+        // Mutable access marks a change, shared access does not
+        world.get_mut::<Position>(b).unwrap().0 = 2.0;
+        let _ = world.get::<Position>(a);
+        let changed: Vec<_> = world
+            .query_filtered::<&Position, Changed<Position>>()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(changed, vec![b]);
+        assert_eq!(
+            world.query_filtered::<&Position, Added<Position>>().count(),
+            0
+        );
+
+        world.clear_trackers();
+        let c = world.spawn();
+        world.insert(c, Position(3.0));
+        for (_, pos) in world.query_filtered_mut::<&mut Position, Without<Velocity>>() {
+            pos.0 += 1.0;
+        }
+        assert_eq!(
+            world
+                .query_filtered::<&Position, Changed<Position>>()
+                .count(),
+            3
+        );
+        let added: Vec<_> = world
+            .query_filtered::<&Position, Added<Position>>()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(added, vec![c]);
+    }
+
+    #[test]
+    fn test_access_self_conflict() {
+        let mut access = Access::new();
+        access.add_read::<Position>();
+        access.add_read::<Position>();
+        access.add_write::<Velocity>();
+        assert!(access.self_conflict().is_none());
+
+        access.add_write::<Velocity>();
+        assert!(access.self_conflict().unwrap().ends_with("Velocity"));
+    }
+}
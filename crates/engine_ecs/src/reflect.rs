@@ -0,0 +1,556 @@
+//! Runtime reflection for components
+//!
+//! Components implementing [`Reflect`] (usually through [`impl_reflect!`])
+//! expose their field names, types and values by name. A [`TypeRegistry`]
+//! maps component names to those accessors so tools such as the debug
+//! inspector can list and edit components without knowing their types.
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt;
+
+use glam::Vec2;
+
+use crate::{Component, Entity, World};
+
+/// Type of a reflected field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Bool,
+    Int,
+    Float,
+    Vec2,
+    String,
+}
+
+impl FieldType {
+    /// Get display name
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Bool => "bool",
+            Self::Int => "int",
+            Self::Float => "float",
+            Self::Vec2 => "Vec2",
+            Self::String => "String",
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Value of a reflected field
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    Vec2(Vec2),
+    String(String),
+}
+
+impl FieldValue {
+    /// Get the type of this value
+    #[must_use]
+    pub const fn field_type(&self) -> FieldType {
+        match self {
+            Self::Bool(_) => FieldType::Bool,
+            Self::Int(_) => FieldType::Int,
+            Self::Float(_) => FieldType::Float,
+            Self::Vec2(_) => FieldType::Vec2,
+            Self::String(_) => FieldType::String,
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(v) => write!(f, "{}", v),
+            Self::Int(v) => write!(f, "{}", v),
+            Self::Float(v) => write!(f, "{:.2}", v),
+            Self::Vec2(v) => write!(f, "({:.2}, {:.2})", v.x, v.y),
+            Self::String(v) => write!(f, "{:?}", v),
+        }
+    }
+}
+
+/// Name and type of a reflected field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub field_type: FieldType,
+}
+
+/// Field types that can be reflected
+pub trait ReflectValue: Sized {
+    /// Reflected type of this field
+    const TYPE: FieldType;
+
+    /// Convert to a dynamic value
+    fn to_field(&self) -> FieldValue;
+
+    /// Convert from a dynamic value, if the type fits
+    fn from_field(value: FieldValue) -> Option<Self>;
+}
+
+impl ReflectValue for bool {
+    const TYPE: FieldType = FieldType::Bool;
+
+    fn to_field(&self) -> FieldValue {
+        FieldValue::Bool(*self)
+    }
+
+    fn from_field(value: FieldValue) -> Option<Self> {
+        match value {
+            FieldValue::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_reflect_value_int {
+    ($($ty:ty),+) => {
+        $(
+            impl ReflectValue for $ty {
+                const TYPE: FieldType = FieldType::Int;
+
+                fn to_field(&self) -> FieldValue {
+                    FieldValue::Int(i64::from(*self))
+                }
+
+                fn from_field(value: FieldValue) -> Option<Self> {
+                    match value {
+                        FieldValue::Int(v) => Self::try_from(v).ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_reflect_value_int!(i32, u32, i64);
+
+impl ReflectValue for f32 {
+    const TYPE: FieldType = FieldType::Float;
+
+    fn to_field(&self) -> FieldValue {
+        FieldValue::Float(*self)
+    }
+
+    fn from_field(value: FieldValue) -> Option<Self> {
+        match value {
+            FieldValue::Float(v) => Some(v),
+            // Whole numbers are accepted where a float is expected
+            #[allow(clippy::cast_precision_loss)]
+            FieldValue::Int(v) => Some(v as f32),
+            _ => None,
+        }
+    }
+}
+
+impl ReflectValue for Vec2 {
+    const TYPE: FieldType = FieldType::Vec2;
+
+    fn to_field(&self) -> FieldValue {
+        FieldValue::Vec2(*self)
+    }
+
+    fn from_field(value: FieldValue) -> Option<Self> {
+        match value {
+            FieldValue::Vec2(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl ReflectValue for String {
+    const TYPE: FieldType = FieldType::String;
+
+    fn to_field(&self) -> FieldValue {
+        FieldValue::String(self.clone())
+    }
+
+    fn from_field(value: FieldValue) -> Option<Self> {
+        match value {
+            FieldValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// Components whose fields can be read and written by name
+pub trait Reflect: Component {
+    /// Field names and types, in declaration order
+    fn fields() -> &'static [FieldInfo];
+
+    /// Get a field value by name
+    fn field(&self, name: &str) -> Option<FieldValue>;
+
+    /// Set a field value by name
+    fn set_field(&mut self, name: &str, value: FieldValue) -> Result<(), ReflectError>;
+}
+
+/// Implement [`Reflect`] for a struct by listing its fields and their types
+///
+/// ```ignore
+/// impl_reflect!(Velocity { x: f32, y: f32 });
+/// impl_reflect!(CameraTarget {});
+/// ```
+#[macro_export]
+macro_rules! impl_reflect {
+    ($ty:ty { $($field:ident: $field_ty:ty),* $(,)? }) => {
+        impl $crate::Reflect for $ty {
+            fn fields() -> &'static [$crate::FieldInfo] {
+                const FIELDS: &[$crate::FieldInfo] = &[$(
+                    $crate::FieldInfo {
+                        name: stringify!($field),
+                        field_type: <$field_ty as $crate::ReflectValue>::TYPE,
+                    },
+                )*];
+                FIELDS
+            }
+
+            fn field(&self, name: &str) -> Option<$crate::FieldValue> {
+                match name {
+                    $(stringify!($field) => Some($crate::ReflectValue::to_field(&self.$field)),)*
+                    _ => None,
+                }
+            }
+
+            #[allow(unused_variables)]
+            fn set_field(
+                &mut self,
+                name: &str,
+                value: $crate::FieldValue,
+            ) -> Result<(), $crate::ReflectError> {
+                match name {
+                    $(stringify!($field) => {
+                        let found = value.field_type();
+                        self.$field = <$field_ty as $crate::ReflectValue>::from_field(value)
+                            .ok_or_else(|| $crate::ReflectError::TypeMismatch {
+                                field: name.to_string(),
+                                expected: <$field_ty as $crate::ReflectValue>::TYPE,
+                                found,
+                            })?;
+                        Ok(())
+                    })*
+                    _ => Err($crate::ReflectError::UnknownField(name.to_string())),
+                }
+            }
+        }
+    };
+}
+
+/// Reflection errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReflectError {
+    UnknownComponent(String),
+    UnknownField(String),
+    MissingComponent(String),
+    TypeMismatch {
+        field: String,
+        expected: FieldType,
+        found: FieldType,
+    },
+}
+
+impl fmt::Display for ReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownComponent(name) => write!(f, "Unknown component '{}'", name),
+            Self::UnknownField(name) => write!(f, "Unknown field '{}'", name),
+            Self::MissingComponent(name) => write!(f, "Entity has no '{}' component", name),
+            Self::TypeMismatch {
+                field,
+                expected,
+                found,
+            } => write!(f, "Field '{}' expects {}, got {}", field, expected, found),
+        }
+    }
+}
+
+impl std::error::Error for ReflectError {}
+
+/// Type-erased accessors for one registered component
+pub struct ComponentRegistration {
+    name: String,
+    type_id: TypeId,
+    fields: &'static [FieldInfo],
+    contains: fn(&World, Entity) -> bool,
+    get: fn(&World, Entity, &str) -> Option<FieldValue>,
+    set: fn(&mut World, Entity, &str, FieldValue) -> Result<(), ReflectError>,
+}
+
+impl ComponentRegistration {
+    fn new<T: Reflect>(name: String) -> Self {
+        Self {
+            name,
+            type_id: TypeId::of::<T>(),
+            fields: T::fields(),
+            contains: |world, entity| world.has::<T>(entity),
+            get: |world, entity, field| world.get::<T>(entity)?.field(field),
+            set: |world, entity, field, value| match world.get_mut::<T>(entity) {
+                Some(component) => component.set_field(field, value),
+                None => Err(ReflectError::MissingComponent(
+                    std::any::type_name::<T>().to_string(),
+                )),
+            },
+        }
+    }
+
+    /// Get the registered name
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the component's type id
+    #[must_use]
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Get field names and types
+    #[must_use]
+    pub fn fields(&self) -> &'static [FieldInfo] {
+        self.fields
+    }
+
+    /// Check if an entity has this component
+    #[must_use]
+    pub fn is_on(&self, world: &World, entity: Entity) -> bool {
+        (self.contains)(world, entity)
+    }
+
+    /// Read one field of an entity's component
+    #[must_use]
+    pub fn get_field(&self, world: &World, entity: Entity, field: &str) -> Option<FieldValue> {
+        (self.get)(world, entity, field)
+    }
+
+    /// Read every field of an entity's component, in declaration order
+    #[must_use]
+    pub fn values(&self, world: &World, entity: Entity) -> Option<Vec<(FieldInfo, FieldValue)>> {
+        if !self.is_on(world, entity) {
+            return None;
+        }
+        self.fields
+            .iter()
+            .map(|info| Some((*info, self.get_field(world, entity, info.name)?)))
+            .collect()
+    }
+
+    /// Write one field of an entity's component
+    pub fn set_field(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        field: &str,
+        value: FieldValue,
+    ) -> Result<(), ReflectError> {
+        (self.set)(world, entity, field, value).map_err(|e| match e {
+            ReflectError::MissingComponent(_) => ReflectError::MissingComponent(self.name.clone()),
+            e => e,
+        })
+    }
+}
+
+/// Reflected component types, by name
+#[derive(Default)]
+pub struct TypeRegistry {
+    registrations: Vec<ComponentRegistration>,
+    by_name: HashMap<String, usize>,
+}
+
+impl TypeRegistry {
+    /// Create an empty registry
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a component type under a name
+    ///
+    /// Registering the same name again replaces the previous type.
+    pub fn register<T: Reflect>(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        let registration = ComponentRegistration::new::<T>(name.clone());
+        match self.by_name.get(&name) {
+            Some(&i) => self.registrations[i] = registration,
+            None => {
+                self.by_name.insert(name, self.registrations.len());
+                self.registrations.push(registration);
+            }
+        }
+    }
+
+    /// Get a registration by component name
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&ComponentRegistration> {
+        self.by_name.get(name).map(|&i| &self.registrations[i])
+    }
+
+    /// Iterate over registrations in registration order
+    pub fn iter(&self) -> impl Iterator<Item = &ComponentRegistration> {
+        self.registrations.iter()
+    }
+
+    /// Registrations of the components an entity has
+    pub fn components_of<'a>(
+        &'a self,
+        world: &'a World,
+        entity: Entity,
+    ) -> impl Iterator<Item = &'a ComponentRegistration> {
+        self.iter().filter(move |r| r.is_on(world, entity))
+    }
+
+    /// Write a field of a named component
+    pub fn set_field(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        component: &str,
+        field: &str,
+        value: FieldValue,
+    ) -> Result<(), ReflectError> {
+        self.get(component)
+            .ok_or_else(|| ReflectError::UnknownComponent(component.to_string()))?
+            .set_field(world, entity, field, value)
+    }
+
+    /// Get the number of registered components
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.registrations.len()
+    }
+
+    /// Check if nothing is registered
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.registrations.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Npc {
+        name: String,
+        position: Vec2,
+        speed: f32,
+        hearts: u32,
+        asleep: bool,
+    }
+
+    impl_reflect!(Npc {
+        name: String,
+        position: Vec2,
+        speed: f32,
+        hearts: u32,
+        asleep: bool,
+    });
+
+    #[derive(Debug)]
+    struct Marker;
+
+    impl_reflect!(Marker {});
+
+    #[test]
+    fn test_reflect_fields() {
+        let fields: Vec<_> = Npc::fields()
+            .iter()
+            .map(|f| (f.name, f.field_type))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("name", FieldType::String),
+                ("position", FieldType::Vec2),
+                ("speed", FieldType::Float),
+                ("hearts", FieldType::Int),
+                ("asleep", FieldType::Bool),
+            ]
+        );
+        assert!(Marker::fields().is_empty());
+    }
+
+    #[test]
+    fn test_get_set_by_name() {
+        let mut npc = Npc::default();
+        npc.set_field("name", FieldValue::String("Robin".into()))
+            .unwrap();
+        npc.set_field("speed", FieldValue::Int(55)).unwrap();
+        npc.set_field("hearts", FieldValue::Int(3)).unwrap();
+        npc.set_field("position", FieldValue::Vec2(Vec2::new(1.0, 2.0)))
+            .unwrap();
+
+        assert_eq!(npc.field("name"), Some(FieldValue::String("Robin".into())));
+        assert_eq!(npc.field("speed"), Some(FieldValue::Float(55.0)));
+        assert_eq!(npc.field("hearts"), Some(FieldValue::Int(3)));
+        assert_eq!(npc.field("missing"), None);
+
+        assert_eq!(
+            npc.set_field("asleep", FieldValue::Float(1.0)),
+            Err(ReflectError::TypeMismatch {
+                field: "asleep".into(),
+                expected: FieldType::Bool,
+                found: FieldType::Float,
+            })
+        );
+        assert!(npc.set_field("hearts", FieldValue::Int(-1)).is_err());
+        assert_eq!(
+            npc.set_field("age", FieldValue::Int(1)),
+            Err(ReflectError::UnknownField("age".into()))
+        );
+    }
+
+    #[test]
+    fn test_registry_reads_and_writes_world() {
+        let mut registry = TypeRegistry::new();
+        registry.register::<Npc>("Npc");
+        registry.register::<Marker>("Marker");
+
+        let mut world = World::new();
+        let robin = world.spawn();
+        world.insert(robin, Npc::default());
+        let rock = world.spawn();
+        world.insert(rock, Marker);
+
+        let names: Vec<_> = registry
+            .components_of(&world, robin)
+            .map(|r| r.name())
+            .collect();
+        assert_eq!(names, ["Npc"]);
+
+        registry
+            .set_field(&mut world, robin, "Npc", "speed", FieldValue::Float(40.0))
+            .unwrap();
+        assert_eq!(world.get::<Npc>(robin).unwrap().speed, 40.0);
+
+        let values = registry.get("Npc").unwrap().values(&world, robin).unwrap();
+        assert_eq!(values.len(), 5);
+        assert_eq!(values[2].0.name, "speed");
+        assert_eq!(values[2].1, FieldValue::Float(40.0));
+
+        assert_eq!(
+            registry.get("Marker").unwrap().values(&world, rock),
+            Some(Vec::new())
+        );
+        assert_eq!(
+            registry.set_field(&mut world, rock, "Npc", "speed", FieldValue::Float(1.0)),
+            Err(ReflectError::MissingComponent("Npc".into()))
+        );
+        assert_eq!(
+            registry.set_field(&mut world, rock, "Tree", "age", FieldValue::Int(1)),
+            Err(ReflectError::UnknownComponent("Tree".into()))
+        );
+    }
+}
@@ -0,0 +1,1011 @@
+//! System scheduling
+//!
+//! A [`Schedule`] groups systems into [`Stage`]s that run in a fixed order
+//! each frame. Within a stage, systems run in registration order unless
+//! `before`/`after` constraints say otherwise.
+//!
+//! Systems registered with [`Schedule::add_parallel_system`] declare their
+//! data access. Consecutive systems in run order whose accesses don't
+//! conflict, and that aren't ordered against each other, form a batch that
+//! runs on the rayon thread pool. [`Schedule::conflicts`] explains why two
+//! systems were kept apart.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::system::{ConflictReason, SystemAccess, SystemConflict, SystemWorld};
+use crate::{Commands, World};
+
+/// Frame phases, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stage {
+    /// Before anything else this frame (input polling, timers)
+    PreUpdate,
+    /// Gameplay at the fixed timestep, possibly several times per frame
+    FixedUpdate,
+    /// Once per frame, after the fixed steps
+    Update,
+    /// Reacting to this frame's changes (camera, cleanup)
+    PostUpdate,
+    /// Preparing data for the renderer
+    RenderPrep,
+}
+
+impl Stage {
+    /// All stages in execution order
+    pub const ALL: [Self; 5] = [
+        Self::PreUpdate,
+        Self::FixedUpdate,
+        Self::Update,
+        Self::PostUpdate,
+        Self::RenderPrep,
+    ];
+
+    /// Get display name
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::PreUpdate => "PreUpdate",
+            Self::FixedUpdate => "FixedUpdate",
+            Self::Update => "Update",
+            Self::PostUpdate => "PostUpdate",
+            Self::RenderPrep => "RenderPrep",
+        }
+    }
+
+    const fn slot(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Errors found while ordering a schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// Two systems in the same stage share a name
+    DuplicateSystem { stage: Stage, name: String },
+    /// A `before`/`after` constraint names a system not in the same stage
+    MissingDependency {
+        stage: Stage,
+        system: String,
+        dependency: String,
+        /// Stage the dependency was registered in, if any
+        found_in: Option<Stage>,
+    },
+    /// Ordering constraints form a loop (first system repeated at the end)
+    Cycle { stage: Stage, systems: Vec<String> },
+    /// A system declares a query borrowing a component mutably along with
+    /// another borrow of it, which would panic when run
+    AliasingQuery {
+        stage: Stage,
+        system: String,
+        /// Query and filter types, as a tuple
+        query: &'static str,
+        component: &'static str,
+    },
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateSystem { stage, name } => {
+                write!(
+                    f,
+                    "system `{}` is registered twice in stage {}",
+                    name, stage
+                )
+            }
+            Self::MissingDependency {
+                stage,
+                system,
+                dependency,
+                found_in: Some(other),
+            } => write!(
+                f,
+                "system `{}` in stage {} is ordered against `{}`, which is in stage {}; \
+                 ordering constraints only apply within a stage",
+                system, stage, dependency, other
+            ),
+            Self::MissingDependency {
+                stage,
+                system,
+                dependency,
+                found_in: None,
+            } => write!(
+                f,
+                "system `{}` in stage {} is ordered against unknown system `{}`",
+                system, stage, dependency
+            ),
+            Self::Cycle { stage, systems } => write!(
+                f,
+                "ordering cycle in stage {}: {}",
+                stage,
+                systems.join(" -> ")
+            ),
+            Self::AliasingQuery {
+                stage,
+                system,
+                query,
+                component,
+            } => write!(
+                f,
+                "system `{}` in stage {} declares query `{}`, which borrows `{}` mutably \
+                 along with another borrow",
+                system, stage, query, component
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// How a system reaches the world
+enum SystemFn {
+    /// Takes the whole world; never runs alongside another system
+    Exclusive(Box<dyn FnMut(&mut World) + Send>),
+    /// Limited to its declared access; may share a batch
+    Parallel {
+        access: SystemAccess,
+        run: Box<dyn FnMut(&mut SystemWorld) + Send>,
+    },
+}
+
+/// A registered system with its ordering constraints
+pub struct SystemDescriptor {
+    name: String,
+    run: SystemFn,
+    /// Systems that must run before this one
+    after: Vec<String>,
+    /// Systems that must run after this one
+    before: Vec<String>,
+    /// World change tick at the end of the previous run
+    last_run: u32,
+}
+
+impl SystemDescriptor {
+    /// Run this system after the named system of the same stage
+    pub fn after(&mut self, name: impl Into<String>) -> &mut Self {
+        self.after.push(name.into());
+        self
+    }
+
+    /// Run this system before the named system of the same stage
+    pub fn before(&mut self, name: impl Into<String>) -> &mut Self {
+        self.before.push(name.into());
+        self
+    }
+
+    /// Get system name
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Reasons this system can't run alongside another
+    fn conflicts_with(&self, other: &Self) -> Vec<ConflictReason> {
+        match (&self.run, &other.run) {
+            (SystemFn::Exclusive(_), _) => vec![ConflictReason::Exclusive(self.name.clone())],
+            (_, SystemFn::Exclusive(_)) => vec![ConflictReason::Exclusive(other.name.clone())],
+            (SystemFn::Parallel { access, .. }, SystemFn::Parallel { access: other, .. }) => {
+                access.conflicts_with(other)
+            }
+        }
+    }
+
+    /// Run the system on its own, returning the commands it queued
+    fn run_alone(&mut self, world: &mut World) -> Option<Commands> {
+        match &mut self.run {
+            SystemFn::Exclusive(run) => {
+                world.set_last_change_tick(self.last_run);
+                run(world);
+                None
+            }
+            SystemFn::Parallel { access, run } => {
+                // Safety: nothing else borrows the world while this runs
+                let mut view =
+                    unsafe { SystemWorld::new(world, access, &self.name, self.last_run) };
+                run(&mut view);
+                Some(view.into_commands())
+            }
+        }
+    }
+}
+
+/// Systems of one stage and their resolved run order
+#[derive(Default)]
+struct StageSystems {
+    systems: Vec<SystemDescriptor>,
+    /// Indices into `systems`, valid when the schedule is not dirty
+    order: Vec<usize>,
+    /// `order` split into groups that may run at the same time
+    batches: Vec<Vec<usize>>,
+}
+
+/// Ordered collection of systems, grouped by stage
+#[derive(Default)]
+pub struct Schedule {
+    stages: [StageSystems; 5],
+    /// Set when systems changed since the order was last resolved
+    dirty: bool,
+}
+
+impl Schedule {
+    /// Create an empty schedule
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a system in a stage
+    ///
+    /// Returns the descriptor so ordering constraints can be chained:
+    /// `schedule.add_system(Stage::FixedUpdate, "movement", movement).after("input")`.
+    pub fn add_system<S>(
+        &mut self,
+        stage: Stage,
+        name: impl Into<String>,
+        system: S,
+    ) -> &mut SystemDescriptor
+    where
+        S: FnMut(&mut World) + Send + 'static,
+    {
+        self.push_system(stage, name.into(), SystemFn::Exclusive(Box::new(system)))
+    }
+
+    /// Register a system that only touches the data it declares
+    ///
+    /// Systems of a stage whose accesses don't conflict run in parallel:
+    ///
+    /// ```ignore
+    /// let access = SystemAccess::new().write::<Crop>().read_resource::<GameClock>();
+    /// schedule.add_parallel_system(Stage::Update, "crop_growth", access, crop_growth);
+    /// ```
+    pub fn add_parallel_system<S>(
+        &mut self,
+        stage: Stage,
+        name: impl Into<String>,
+        access: SystemAccess,
+        system: S,
+    ) -> &mut SystemDescriptor
+    where
+        S: FnMut(&mut SystemWorld) + Send + 'static,
+    {
+        let run = SystemFn::Parallel {
+            access,
+            run: Box::new(system),
+        };
+        self.push_system(stage, name.into(), run)
+    }
+
+    fn push_system(&mut self, stage: Stage, name: String, run: SystemFn) -> &mut SystemDescriptor {
+        self.dirty = true;
+        let systems = &mut self.stages[stage.slot()].systems;
+        systems.push(SystemDescriptor {
+            name,
+            run,
+            after: Vec::new(),
+            before: Vec::new(),
+            last_run: 0,
+        });
+        systems.last_mut().unwrap()
+    }
+
+    /// Get the number of registered systems
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.stages.iter().map(|stage| stage.systems.len()).sum()
+    }
+
+    /// Check if no system is registered
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Resolve the run order of every stage
+    ///
+    /// Called automatically before running; call it at startup to report
+    /// ordering mistakes without panicking.
+    ///
+    /// # Errors
+    /// Returns the first duplicate name, unknown dependency, cycle or
+    /// aliasing query found.
+    pub fn build(&mut self) -> Result<(), ScheduleError> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let mut orders = Vec::with_capacity(Stage::ALL.len());
+        for stage in Stage::ALL {
+            orders.push(self.resolve_order(stage)?);
+        }
+        for (stage, (order, batches)) in self.stages.iter_mut().zip(orders) {
+            stage.order = order;
+            stage.batches = batches;
+        }
+
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// System names of a stage in run order
+    ///
+    /// # Errors
+    /// Returns the ordering error if the schedule cannot be built.
+    pub fn system_order(&mut self, stage: Stage) -> Result<Vec<&str>, ScheduleError> {
+        self.build()?;
+        let stage = &self.stages[stage.slot()];
+        Ok(stage
+            .order
+            .iter()
+            .map(|&i| stage.systems[i].name())
+            .collect())
+    }
+
+    /// System names of a stage grouped by the batches they run in
+    ///
+    /// # Errors
+    /// Returns the ordering error if the schedule cannot be built.
+    pub fn batches(&mut self, stage: Stage) -> Result<Vec<Vec<&str>>, ScheduleError> {
+        self.build()?;
+        let stage = &self.stages[stage.slot()];
+        Ok(stage
+            .batches
+            .iter()
+            .map(|batch| batch.iter().map(|&i| stage.systems[i].name()).collect())
+            .collect())
+    }
+
+    /// Every pair of systems in a stage that may not run at the same time
+    ///
+    /// Pairs are listed in run order, with every overlapping access.
+    ///
+    /// # Errors
+    /// Returns the ordering error if the schedule cannot be built.
+    pub fn conflicts(&mut self, stage: Stage) -> Result<Vec<SystemConflict>, ScheduleError> {
+        self.build()?;
+        let stage = &self.stages[stage.slot()];
+        let mut conflicts = Vec::new();
+        for (pos, &i) in stage.order.iter().enumerate() {
+            for &j in &stage.order[pos + 1..] {
+                let (first, second) = (&stage.systems[i], &stage.systems[j]);
+                let reasons = first.conflicts_with(second);
+                if !reasons.is_empty() {
+                    conflicts.push(SystemConflict {
+                        first: first.name.clone(),
+                        second: second.name.clone(),
+                        reasons,
+                    });
+                }
+            }
+        }
+        Ok(conflicts)
+    }
+
+    /// Run every system of one stage
+    ///
+    /// Each system sees `Added`/`Changed` components and removals made since
+    /// its own previous run. Systems of a batch run on the rayon thread
+    /// pool; their commands are applied in run order once all have finished,
+    /// at a later tick, so the whole batch sees them on its next run.
+    ///
+    /// # Panics
+    /// Panics if the ordering constraints are invalid; see [`Schedule::build`].
+    pub fn run_stage(&mut self, stage: Stage, world: &mut World) {
+        if let Err(e) = self.build() {
+            panic!("{}", e);
+        }
+
+        let outside_last_change_tick = world.last_change_tick();
+        let stage = &mut self.stages[stage.slot()];
+        for batch in &stage.batches {
+            let tick = world.increment_change_tick();
+            let commands = if let [i] = batch[..] {
+                stage.systems[i].run_alone(world).into_iter().collect()
+            } else {
+                run_batch(&mut stage.systems, batch, world)
+            };
+            for &i in batch {
+                stage.systems[i].last_run = tick;
+            }
+
+            // Commands land after the batch's tick, or its systems would
+            // never see them as added or changed
+            world.increment_change_tick();
+            for commands in commands {
+                commands.apply(world);
+            }
+        }
+
+        // Changes made between stages must be newer than every system's last run
+        world.increment_change_tick();
+        world.set_last_change_tick(outside_last_change_tick);
+    }
+
+    /// Run every stage once, in order
+    ///
+    /// # Panics
+    /// Panics if the ordering constraints are invalid; see [`Schedule::build`].
+    pub fn run(&mut self, world: &mut World) {
+        for stage in Stage::ALL {
+            self.run_stage(stage, world);
+        }
+    }
+
+    /// Find the stage a system name was registered in
+    fn stage_of(&self, name: &str) -> Option<Stage> {
+        Stage::ALL.into_iter().find(|stage| {
+            self.stages[stage.slot()]
+                .systems
+                .iter()
+                .any(|system| system.name == name)
+        })
+    }
+
+    /// Topologically sort one stage, keeping registration order where free,
+    /// then group the order into batches
+    fn resolve_order(&self, stage: Stage) -> Result<(Vec<usize>, Vec<Vec<usize>>), ScheduleError> {
+        let systems = &self.stages[stage.slot()].systems;
+
+        let mut index_of = HashMap::with_capacity(systems.len());
+        for (i, system) in systems.iter().enumerate() {
+            if index_of.insert(system.name.as_str(), i).is_some() {
+                return Err(ScheduleError::DuplicateSystem {
+                    stage,
+                    name: system.name.clone(),
+                });
+            }
+            if let SystemFn::Parallel { access, .. } = &system.run {
+                if let Some((query, component)) = access.aliasing_query() {
+                    return Err(ScheduleError::AliasingQuery {
+                        stage,
+                        system: system.name.clone(),
+                        query,
+                        component,
+                    });
+                }
+            }
+        }
+
+        // Edges point from a system to the systems that must wait for it
+        let mut successors = vec![Vec::new(); systems.len()];
+        let mut pending = vec![0usize; systems.len()];
+        for (i, system) in systems.iter().enumerate() {
+            let lookup = |dependency: &String| {
+                index_of.get(dependency.as_str()).copied().ok_or_else(|| {
+                    ScheduleError::MissingDependency {
+                        stage,
+                        system: system.name.clone(),
+                        dependency: dependency.clone(),
+                        found_in: self.stage_of(dependency),
+                    }
+                })
+            };
+            for dependency in &system.after {
+                let j = lookup(dependency)?;
+                successors[j].push(i);
+                pending[i] += 1;
+            }
+            for dependency in &system.before {
+                let j = lookup(dependency)?;
+                successors[i].push(j);
+                pending[j] += 1;
+            }
+        }
+
+        // Kahn's algorithm, always taking the earliest registered ready system
+        let mut order = Vec::with_capacity(systems.len());
+        let mut done = vec![false; systems.len()];
+        while let Some(next) = (0..systems.len()).find(|&i| !done[i] && pending[i] == 0) {
+            done[next] = true;
+            order.push(next);
+            for &successor in &successors[next] {
+                pending[successor] -= 1;
+            }
+        }
+
+        if order.len() < systems.len() {
+            let cycle = find_cycle(&successors, &done);
+            return Err(ScheduleError::Cycle {
+                stage,
+                systems: cycle.into_iter().map(|i| systems[i].name.clone()).collect(),
+            });
+        }
+
+        let batches = batch_systems(systems, &order, &successors);
+        Ok((order, batches))
+    }
+}
+
+/// Split a run order into consecutive groups that may run at the same time
+///
+/// A system joins the current batch if it conflicts with none of its
+/// systems and none of them must run before it.
+fn batch_systems(
+    systems: &[SystemDescriptor],
+    order: &[usize],
+    successors: &[Vec<usize>],
+) -> Vec<Vec<usize>> {
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+    for &i in order {
+        match batches.last_mut() {
+            Some(batch)
+                if batch.iter().all(|&j| {
+                    !successors[j].contains(&i) && systems[j].conflicts_with(&systems[i]).is_empty()
+                }) =>
+            {
+                batch.push(i);
+            }
+            _ => batches.push(vec![i]),
+        }
+    }
+    batches
+}
+
+/// Run a batch of parallel systems on the thread pool
+///
+/// Returns the command buffers of the batch, in batch order.
+fn run_batch(systems: &mut [SystemDescriptor], batch: &[usize], world: &World) -> Vec<Commands> {
+    let mut results: Vec<Option<Commands>> = systems.iter().map(|_| None).collect();
+    rayon::scope(|scope| {
+        for (i, (system, result)) in systems.iter_mut().zip(&mut results).enumerate() {
+            if !batch.contains(&i) {
+                continue;
+            }
+            let SystemFn::Parallel { access, run } = &mut system.run else {
+                unreachable!("exclusive systems always run alone");
+            };
+            let (name, last_run) = (system.name.as_str(), system.last_run);
+            scope.spawn(move |_| {
+                // Safety: systems of a batch have no conflicting access, and
+                // the world is not otherwise mutated until the scope ends
+                let mut view = unsafe { SystemWorld::new(world, access, name, last_run) };
+                run(&mut view);
+                *result = Some(view.into_commands());
+            });
+        }
+    });
+    batch
+        .iter()
+        .map(|&i| results[i].take().expect("batched system ran"))
+        .collect()
+}
+
+/// Walk the unsorted systems until one repeats, returning that loop
+///
+/// Every system left over by the sort waits on another leftover system,
+/// so following those edges backwards must eventually revisit a node.
+fn find_cycle(successors: &[Vec<usize>], done: &[bool]) -> Vec<usize> {
+    let start = done.iter().position(|&d| !d).expect("unsorted system");
+
+    let mut path = vec![start];
+    loop {
+        let current = *path.last().unwrap();
+        let previous = (0..successors.len())
+            .find(|&j| !done[j] && successors[j].contains(&current))
+            .expect("unsorted system has an unsorted dependency");
+
+        if let Some(pos) = path.iter().position(|&i| i == previous) {
+            // Path was built backwards; reverse it into run order and
+            // start from the earliest registered system for stable output
+            let mut cycle: Vec<usize> = path[pos..].iter().rev().copied().collect();
+            let first = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+            cycle.rotate_left(first);
+            cycle.push(cycle[0]);
+            return cycle;
+        }
+        path.push(previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Added, Changed, SystemAccess};
+
+    use std::sync::{Arc, Mutex};
+
+    /// Record system runs into a shared log
+    fn logger(log: &Arc<Mutex<Vec<&'static str>>>, name: &'static str) -> impl FnMut(&mut World) {
+        let log = Arc::clone(log);
+        move |_| log.lock().unwrap().push(name)
+    }
+
+    #[test]
+    fn test_stages_run_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::RenderPrep, "render", logger(&log, "render"));
+        schedule.add_system(Stage::Update, "update", logger(&log, "update"));
+        schedule.add_system(Stage::PreUpdate, "pre", logger(&log, "pre"));
+        schedule.add_system(Stage::FixedUpdate, "fixed", logger(&log, "fixed"));
+        schedule.add_system(Stage::PostUpdate, "post", logger(&log, "post"));
+
+        let mut world = World::new();
+        schedule.run(&mut world);
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["pre", "fixed", "update", "post", "render"]
+        );
+        assert_eq!(schedule.len(), 5);
+    }
+
+    #[test]
+    fn test_before_after_constraints() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::FixedUpdate, "movement", |_| {})
+            .after("input");
+        schedule.add_system(Stage::FixedUpdate, "input", |_| {});
+        schedule
+            .add_system(Stage::FixedUpdate, "physics", |_| {})
+            .before("movement");
+        schedule.add_system(Stage::FixedUpdate, "audio", |_| {});
+
+        let order = schedule.system_order(Stage::FixedUpdate).unwrap();
+        assert_eq!(order, ["input", "physics", "movement", "audio"]);
+    }
+
+    #[test]
+    fn test_systems_mutate_world() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, "double", |world: &mut World| {
+            *world.get_resource_mut::<u32>().unwrap() *= 2;
+        });
+        schedule
+            .add_system(Stage::Update, "increment", |world: &mut World| {
+                *world.get_resource_mut::<u32>().unwrap() += 1;
+            })
+            .before("double");
+
+        let mut world = World::new();
+        world.insert_resource(1u32);
+        schedule.run_stage(Stage::Update, &mut world);
+        schedule.run_stage(Stage::PreUpdate, &mut world);
+
+        assert_eq!(world.get_resource::<u32>(), Some(&4));
+    }
+
+    #[test]
+    fn test_cycle_is_reported() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, "a", |_| {}).after("c");
+        schedule.add_system(Stage::Update, "b", |_| {}).after("a");
+        schedule.add_system(Stage::Update, "c", |_| {}).after("b");
+        schedule.add_system(Stage::Update, "d", |_| {});
+
+        let err = schedule.build().unwrap_err();
+        assert_eq!(
+            err,
+            ScheduleError::Cycle {
+                stage: Stage::Update,
+                systems: vec!["a".into(), "b".into(), "c".into(), "a".into()],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "ordering cycle in stage Update: a -> b -> c -> a"
+        );
+    }
+
+    #[test]
+    fn test_missing_dependency_is_reported() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::FixedUpdate, "input", |_| {});
+        schedule
+            .add_system(Stage::PostUpdate, "camera", |_| {})
+            .after("input");
+        schedule
+            .add_system(Stage::PostUpdate, "hud", |_| {})
+            .after("nothing");
+
+        let err = schedule.build().unwrap_err();
+        assert_eq!(
+            err,
+            ScheduleError::MissingDependency {
+                stage: Stage::PostUpdate,
+                system: "camera".into(),
+                dependency: "input".into(),
+                found_in: Some(Stage::FixedUpdate),
+            }
+        );
+        assert!(err.to_string().contains("which is in stage FixedUpdate"));
+    }
+
+    #[test]
+    fn test_duplicate_system_is_reported() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, "a", |_| {});
+        schedule.add_system(Stage::Update, "a", |_| {});
+
+        assert!(matches!(
+            schedule.build(),
+            Err(ScheduleError::DuplicateSystem { .. })
+        ));
+    }
+
+    #[test]
+    fn test_systems_see_changes_since_their_last_run() {
+        #[derive(Debug)]
+        struct Health(u32);
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, "damage", |world: &mut World| {
+            let targets: Vec<_> = world
+                .query::<&Health>()
+                .filter(|(_, health)| health.0 > 50)
+                .map(|(entity, _)| entity)
+                .collect();
+            for entity in targets {
+                world.get_mut::<Health>(entity).unwrap().0 -= 10;
+            }
+        });
+        let log = Arc::clone(&seen);
+        schedule
+            .add_system(Stage::Update, "report", move |world: &mut World| {
+                let changed = world.query_filtered::<&Health, Changed<Health>>().count();
+                let added = world.query_filtered::<&Health, Added<Health>>().count();
+                let removed = world.removed::<Health>().count();
+                log.lock().unwrap().push((changed, added, removed));
+            })
+            .after("damage");
+
+        let mut world = World::new();
+        let a = world.spawn();
+        world.insert(a, Health(60));
+        let b = world.spawn();
+        world.insert(b, Health(10));
+
+        // First run: both are new, and `a` drops to 50
+        schedule.run(&mut world);
+        // Second run: nothing is above 50, so nothing changes
+        schedule.run(&mut world);
+        // Outside the schedule, `b` loses its health
+        world.remove::<Health>(b);
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [(2, 2, 0), (0, 0, 0), (0, 0, 1), (0, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn test_aliasing_query_is_reported() {
+        let mut schedule = Schedule::new();
+        schedule.add_parallel_system(
+            Stage::FixedUpdate,
+            "growth",
+            SystemAccess::new().query::<&mut Crop, Changed<Crop>>(),
+            |_| {},
+        );
+
+        let err = schedule.build().unwrap_err();
+        assert_eq!(
+            err,
+            ScheduleError::AliasingQuery {
+                stage: Stage::FixedUpdate,
+                system: "growth".into(),
+                query: std::any::type_name::<(&mut Crop, Changed<Crop>)>(),
+                component: std::any::type_name::<Crop>(),
+            }
+        );
+        assert!(err
+            .to_string()
+            .starts_with("system `growth` in stage FixedUpdate"));
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown system `missing`")]
+    fn test_run_panics_on_invalid_schedule() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, "a", |_| {})
+            .after("missing");
+        schedule.run(&mut World::new());
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Position(f32);
+
+    #[derive(Debug, PartialEq)]
+    struct Crop(u32);
+
+    struct Clock(u32);
+
+    fn farm_schedule() -> Schedule {
+        let mut schedule = Schedule::new();
+        schedule.add_parallel_system(
+            Stage::Update,
+            "movement",
+            SystemAccess::new().query::<&mut Position, ()>(),
+            |world| {
+                for (_, pos) in world.query_mut::<&mut Position>() {
+                    pos.0 += 1.0;
+                }
+            },
+        );
+        schedule.add_parallel_system(
+            Stage::Update,
+            "crop_growth",
+            SystemAccess::new().write::<Crop>().read_resource::<Clock>(),
+            |world| {
+                let days = world.resource::<Clock>().map_or(0, |clock| clock.0);
+                let mut ripe = Vec::new();
+                for (entity, crop) in world.query_mut::<&mut Crop>() {
+                    crop.0 += days;
+                    if crop.0 >= 3 {
+                        ripe.push(entity);
+                    }
+                }
+                for entity in ripe {
+                    world.commands().remove::<Crop>(entity);
+                }
+            },
+        );
+        schedule.add_parallel_system(
+            Stage::Update,
+            "camera",
+            SystemAccess::new().read::<Position>(),
+            |_| {},
+        );
+        schedule.add_system(Stage::Update, "save", |_| {});
+        schedule
+    }
+
+    #[test]
+    fn test_parallel_batches() {
+        let mut schedule = farm_schedule();
+        assert_eq!(
+            schedule.batches(Stage::Update).unwrap(),
+            [
+                vec!["movement", "crop_growth"],
+                vec!["camera"],
+                vec!["save"]
+            ]
+        );
+
+        let mut world = World::new();
+        world.insert_resource(Clock(2));
+        let player = world.spawn();
+        world.insert(player, Position(0.0));
+        let turnip = world.spawn();
+        world.insert(turnip, Crop(0));
+
+        schedule.run(&mut world);
+        assert_eq!(world.get::<Position>(player), Some(&Position(1.0)));
+        assert_eq!(world.get::<Crop>(turnip), Some(&Crop(2)));
+
+        // Commands queued by a batched system are applied after the batch
+        schedule.run(&mut world);
+        assert_eq!(world.get::<Position>(player), Some(&Position(2.0)));
+        assert!(!world.has::<Crop>(turnip));
+    }
+
+    #[test]
+    fn test_ordering_splits_batches() {
+        let mut schedule = farm_schedule();
+        schedule
+            .add_parallel_system(Stage::Update, "weather", SystemAccess::new(), |_| {})
+            .after("movement")
+            .before("camera");
+
+        // `camera` now waits for `weather`, which waits for `movement`
+        assert_eq!(
+            schedule.batches(Stage::Update).unwrap(),
+            [
+                vec!["movement", "crop_growth"],
+                vec!["save"],
+                vec!["weather"],
+                vec!["camera"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conflict_report() {
+        let mut schedule = farm_schedule();
+        let conflicts = schedule.conflicts(Stage::Update).unwrap();
+        let report: Vec<String> = conflicts.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            report,
+            [
+                format!(
+                    "`movement` and `camera` are serialized: component `{}` is written",
+                    std::any::type_name::<Position>()
+                ),
+                "`movement` and `save` are serialized: `save` needs exclusive world access".into(),
+                "`crop_growth` and `save` are serialized: `save` needs exclusive world access"
+                    .into(),
+                "`camera` and `save` are serialized: `save` needs exclusive world access".into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parallel_systems_see_changes_since_their_last_run() {
+        let changed = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        let log = Arc::clone(&changed);
+        schedule.add_parallel_system(
+            Stage::Update,
+            "watch",
+            SystemAccess::new().query::<&Position, Changed<Position>>(),
+            move |world| {
+                let count = world
+                    .query_filtered::<&Position, Changed<Position>>()
+                    .count();
+                log.lock().unwrap().push(count);
+            },
+        );
+
+        let mut world = World::new();
+        let player = world.spawn();
+        world.insert(player, Position(0.0));
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        world.get_mut::<Position>(player).unwrap().0 = 4.0;
+        schedule.run(&mut world);
+
+        assert_eq!(*changed.lock().unwrap(), [1, 0, 1]);
+    }
+
+    #[test]
+    fn test_batch_sees_its_own_commands() {
+        let added = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        let planted = Arc::new(Mutex::new(false));
+        schedule.add_parallel_system(Stage::Update, "plant", SystemAccess::new(), move |world| {
+            let mut planted = planted.lock().unwrap();
+            if !*planted {
+                let crop = world.commands().spawn();
+                world.commands().insert(crop, Crop(0));
+                *planted = true;
+            }
+        });
+        let log = Arc::clone(&added);
+        schedule.add_parallel_system(
+            Stage::Update,
+            "watch",
+            SystemAccess::new().query::<&Crop, Added<Crop>>(),
+            move |world| {
+                let count = world.query_filtered::<&Crop, Added<Crop>>().count();
+                log.lock().unwrap().push(count);
+            },
+        );
+        assert_eq!(
+            schedule.batches(Stage::Update).unwrap(),
+            [vec!["plant", "watch"]]
+        );
+
+        let mut world = World::new();
+        for _ in 0..3 {
+            schedule.run(&mut world);
+        }
+
+        assert_eq!(*added.lock().unwrap(), [0, 1, 0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "system `sneaky` accesses component")]
+    fn test_undeclared_access_panics() {
+        let mut schedule = Schedule::new();
+        schedule.add_parallel_system(
+            Stage::Update,
+            "sneaky",
+            SystemAccess::new().read::<Crop>(),
+            |world| {
+                world.query_mut::<&mut Crop>().count();
+            },
+        );
+        schedule.run(&mut World::new());
+    }
+}
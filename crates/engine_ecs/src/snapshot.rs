@@ -0,0 +1,504 @@
+//! Whole-world snapshots
+//!
+//! [`World::snapshot`] captures every entity, its components and the
+//! resources registered on a [`SnapshotRegistry`], as a serde-friendly
+//! [`WorldSnapshot`]. [`World::restore`] rebuilds the world from one,
+//! handing out fresh entity IDs and remapping references to them: the
+//! hierarchy is rebuilt automatically, and components holding entities
+//! implement [`MapEntities`].
+//!
+//! Components and resources are stored as JSON values keyed by their
+//! registered name, in a stable order, so snapshots diff cleanly and can be
+//! embedded in save files or test fixtures.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::{Component, Entity, Resource, World};
+
+/// Current snapshot format version
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Inserts one deserialized value once entity IDs are known
+type Insert = Box<dyn FnOnce(&mut World, Entity, &EntityMap)>;
+
+/// Inserts one deserialized resource
+type InsertResource = Box<dyn FnOnce(&mut World)>;
+
+/// Serialized state of a whole world
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    /// Format version
+    pub version: u32,
+    /// Entities in index order
+    pub entities: Vec<EntitySnapshot>,
+    /// Registered resources, by name
+    #[serde(default)]
+    pub resources: BTreeMap<String, serde_json::Value>,
+}
+
+/// Serialized state of one entity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntitySnapshot {
+    /// Entity ID when the snapshot was taken
+    pub id: Entity,
+    /// Children, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Entity>,
+    /// Registered components, by name
+    #[serde(default)]
+    pub components: BTreeMap<String, serde_json::Value>,
+}
+
+/// Old-to-new entity IDs produced by [`World::restore`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntityMap {
+    map: HashMap<Entity, Entity>,
+}
+
+impl EntityMap {
+    /// Get the new ID of an entity from the snapshot
+    #[must_use]
+    pub fn get(&self, old: Entity) -> Option<Entity> {
+        self.map.get(&old).copied()
+    }
+
+    /// Get the number of mapped entities
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Check if no entity is mapped
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// Components that refer to other entities
+///
+/// Called on restore with the IDs of the new world. References to entities
+/// that were not in the snapshot map to `None`.
+pub trait MapEntities {
+    /// Rewrite stored entity IDs
+    fn map_entities(&mut self, map: &EntityMap);
+}
+
+/// Serialization accessors for one registered component
+struct ComponentSerializer {
+    name: String,
+    save: fn(&World, Entity) -> Option<serde_json::Result<serde_json::Value>>,
+    load: fn(serde_json::Value) -> serde_json::Result<Insert>,
+}
+
+/// Serialization accessors for one registered resource
+struct ResourceSerializer {
+    name: String,
+    save: fn(&World) -> Option<serde_json::Result<serde_json::Value>>,
+    load: fn(serde_json::Value) -> serde_json::Result<InsertResource>,
+}
+
+/// Component and resource types included in snapshots, by name
+///
+/// Stored as a world resource so [`World::snapshot`] can find it.
+#[derive(Default)]
+pub struct SnapshotRegistry {
+    components: Vec<ComponentSerializer>,
+    resources: Vec<ResourceSerializer>,
+}
+
+impl SnapshotRegistry {
+    /// Create an empty registry
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a component type
+    pub fn register_component<T>(&mut self, name: impl Into<String>)
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        self.add_component(ComponentSerializer {
+            name: name.into(),
+            save: save_component::<T>,
+            load: |value| {
+                let component: T = serde_json::from_value(value)?;
+                Ok(Box::new(move |world: &mut World, entity, _: &EntityMap| {
+                    world.insert(entity, component);
+                }))
+            },
+        });
+    }
+
+    /// Register a component type holding entity IDs
+    pub fn register_component_mapped<T>(&mut self, name: impl Into<String>)
+    where
+        T: Component + MapEntities + Serialize + DeserializeOwned,
+    {
+        self.add_component(ComponentSerializer {
+            name: name.into(),
+            save: save_component::<T>,
+            load: |value| {
+                let mut component: T = serde_json::from_value(value)?;
+                Ok(Box::new(
+                    move |world: &mut World, entity, map: &EntityMap| {
+                        component.map_entities(map);
+                        world.insert(entity, component);
+                    },
+                ))
+            },
+        });
+    }
+
+    /// Register a resource type
+    pub fn register_resource<R>(&mut self, name: impl Into<String>)
+    where
+        R: Resource + Serialize + DeserializeOwned,
+    {
+        let serializer = ResourceSerializer {
+            name: name.into(),
+            save: |world| world.get_resource::<R>().map(serde_json::to_value),
+            load: |value| {
+                let resource: R = serde_json::from_value(value)?;
+                Ok(Box::new(move |world: &mut World| {
+                    world.insert_resource(resource)
+                }))
+            },
+        };
+        match self
+            .resources
+            .iter_mut()
+            .find(|r| r.name == serializer.name)
+        {
+            Some(existing) => *existing = serializer,
+            None => self.resources.push(serializer),
+        }
+    }
+
+    fn add_component(&mut self, serializer: ComponentSerializer) {
+        match self
+            .components
+            .iter_mut()
+            .find(|c| c.name == serializer.name)
+        {
+            Some(existing) => *existing = serializer,
+            None => self.components.push(serializer),
+        }
+    }
+
+    /// Check if a component name is registered
+    #[must_use]
+    pub fn has_component(&self, name: &str) -> bool {
+        self.components.iter().any(|c| c.name == name)
+    }
+
+    /// Check if a resource name is registered
+    #[must_use]
+    pub fn has_resource(&self, name: &str) -> bool {
+        self.resources.iter().any(|r| r.name == name)
+    }
+
+    /// Capture a world
+    pub fn snapshot(&self, world: &World) -> Result<WorldSnapshot, SnapshotError> {
+        let mut entities = Vec::with_capacity(world.entity_count());
+        for entity in world.entities() {
+            let mut components = BTreeMap::new();
+            for serializer in &self.components {
+                if let Some(value) = (serializer.save)(world, entity) {
+                    let value = value.map_err(|e| SnapshotError::Serialize {
+                        name: serializer.name.clone(),
+                        error: e.to_string(),
+                    })?;
+                    components.insert(serializer.name.clone(), value);
+                }
+            }
+
+            entities.push(EntitySnapshot {
+                id: entity,
+                children: world.children(entity).to_vec(),
+                components,
+            });
+        }
+
+        let mut resources = BTreeMap::new();
+        for serializer in &self.resources {
+            if let Some(value) = (serializer.save)(world) {
+                let value = value.map_err(|e| SnapshotError::Serialize {
+                    name: serializer.name.clone(),
+                    error: e.to_string(),
+                })?;
+                resources.insert(serializer.name.clone(), value);
+            }
+        }
+
+        Ok(WorldSnapshot {
+            version: SNAPSHOT_VERSION,
+            entities,
+            resources,
+        })
+    }
+
+    /// Replace every entity of a world with the snapshot's
+    ///
+    /// Registered resources present in the snapshot are overwritten; other
+    /// resources are left alone. Everything is deserialized before the
+    /// world is touched, so an error leaves it unchanged.
+    pub fn restore(
+        &self,
+        world: &mut World,
+        snapshot: &WorldSnapshot,
+    ) -> Result<EntityMap, SnapshotError> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::Version(snapshot.version));
+        }
+
+        let mut entity_inserts = Vec::with_capacity(snapshot.entities.len());
+        for entity in &snapshot.entities {
+            let mut inserts = Vec::with_capacity(entity.components.len());
+            for (name, value) in &entity.components {
+                let serializer = self
+                    .components
+                    .iter()
+                    .find(|c| &c.name == name)
+                    .ok_or_else(|| SnapshotError::UnknownComponent(name.clone()))?;
+                let insert =
+                    (serializer.load)(value.clone()).map_err(|e| SnapshotError::Deserialize {
+                        name: name.clone(),
+                        error: e.to_string(),
+                    })?;
+                inserts.push(insert);
+            }
+            entity_inserts.push(inserts);
+        }
+
+        let mut resource_inserts = Vec::with_capacity(snapshot.resources.len());
+        for (name, value) in &snapshot.resources {
+            let serializer = self
+                .resources
+                .iter()
+                .find(|r| &r.name == name)
+                .ok_or_else(|| SnapshotError::UnknownResource(name.clone()))?;
+            let insert =
+                (serializer.load)(value.clone()).map_err(|e| SnapshotError::Deserialize {
+                    name: name.clone(),
+                    error: e.to_string(),
+                })?;
+            resource_inserts.push(insert);
+        }
+
+        // Clear the world, then spawn everything so references can be mapped
+        let existing: Vec<Entity> = world.entities().collect();
+        for entity in existing {
+            world.despawn(entity);
+        }
+
+        let mut map = EntityMap::default();
+        for entity in &snapshot.entities {
+            map.map.insert(entity.id, world.spawn());
+        }
+
+        for (entity, inserts) in snapshot.entities.iter().zip(entity_inserts) {
+            let new = map.map[&entity.id];
+            for insert in inserts {
+                insert(world, new, &map);
+            }
+            for child in entity.children.iter().filter_map(|&child| map.get(child)) {
+                world.set_parent(child, new);
+            }
+        }
+
+        for insert in resource_inserts {
+            insert(world);
+        }
+        Ok(map)
+    }
+}
+
+fn save_component<T>(world: &World, entity: Entity) -> Option<serde_json::Result<serde_json::Value>>
+where
+    T: Component + Serialize,
+{
+    world.get::<T>(entity).map(serde_json::to_value)
+}
+
+impl World {
+    /// Capture the world using the [`SnapshotRegistry`] resource
+    pub fn snapshot(&self) -> Result<WorldSnapshot, SnapshotError> {
+        self.get_resource::<SnapshotRegistry>()
+            .ok_or(SnapshotError::NoRegistry)?
+            .snapshot(self)
+    }
+
+    /// Restore a snapshot using the [`SnapshotRegistry`] resource
+    ///
+    /// See [`SnapshotRegistry::restore`].
+    pub fn restore(&mut self, snapshot: &WorldSnapshot) -> Result<EntityMap, SnapshotError> {
+        let registry = self
+            .remove_resource::<SnapshotRegistry>()
+            .ok_or(SnapshotError::NoRegistry)?;
+        let result = registry.restore(self, snapshot);
+        self.insert_resource(registry);
+        result
+    }
+}
+
+/// Snapshot capture and restore errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    Serialize { name: String, error: String },
+    Deserialize { name: String, error: String },
+    UnknownComponent(String),
+    UnknownResource(String),
+    Version(u32),
+    NoRegistry,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serialize { name, error } => {
+                write!(f, "Failed to serialize '{}': {}", name, error)
+            }
+            Self::Deserialize { name, error } => {
+                write!(f, "Failed to deserialize '{}': {}", name, error)
+            }
+            Self::UnknownComponent(name) => {
+                write!(f, "Snapshot uses unregistered component '{}'", name)
+            }
+            Self::UnknownResource(name) => {
+                write!(f, "Snapshot uses unregistered resource '{}'", name)
+            }
+            Self::Version(version) => write!(
+                f,
+                "Snapshot version {} is not supported (expected {})",
+                version, SNAPSHOT_VERSION
+            ),
+            Self::NoRegistry => write!(f, "No SnapshotRegistry resource in the world"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{Parent, Transform};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Health(u32);
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Follow {
+        target: Option<Entity>,
+    }
+
+    impl MapEntities for Follow {
+        fn map_entities(&mut self, map: &EntityMap) {
+            self.target = self.target.and_then(|target| map.get(target));
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Day(u32);
+
+    fn registry() -> SnapshotRegistry {
+        let mut registry = SnapshotRegistry::new();
+        registry.register_component::<Health>("Health");
+        registry.register_component::<Transform>("Transform");
+        registry.register_component_mapped::<Follow>("Follow");
+        registry.register_resource::<Day>("Day");
+        registry
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip_remaps_entities() {
+        let mut world = World::new();
+        world.insert_resource(registry());
+        world.insert_resource(Day(3));
+
+        // Leave a hole so restored IDs differ from the saved ones
+        let gone = world.spawn();
+        let farmer = world.spawn();
+        world.insert(farmer, Health(5));
+        world.insert(farmer, Transform::from_xy(10.0, 20.0));
+        let hat = world.spawn();
+        world.insert(hat, Transform::from_xy(0.0, -8.0));
+        world.set_parent(hat, farmer);
+        let dog = world.spawn();
+        world.insert(
+            dog,
+            Follow {
+                target: Some(farmer),
+            },
+        );
+        world.despawn(gone);
+
+        let snapshot = world.snapshot().unwrap();
+        let json = serde_json::to_string(&snapshot).unwrap();
+
+        let mut restored = World::new();
+        restored.insert_resource(registry());
+        restored.spawn();
+        let map = restored
+            .restore(&serde_json::from_str(&json).unwrap())
+            .unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(restored.entity_count(), 3);
+
+        let farmer = map.get(farmer).unwrap();
+        let hat = map.get(hat).unwrap();
+        let dog = map.get(dog).unwrap();
+        assert_eq!(restored.get::<Health>(farmer), Some(&Health(5)));
+        assert_eq!(restored.get::<Parent>(hat).map(Parent::get), Some(farmer));
+        assert_eq!(restored.children(farmer), [hat]);
+        assert_eq!(
+            restored.get::<Follow>(dog),
+            Some(&Follow {
+                target: Some(farmer)
+            })
+        );
+        assert_eq!(restored.get_resource::<Day>(), Some(&Day(3)));
+
+        // Capturing the restored world gives the same content
+        let again = restored.snapshot().unwrap();
+        assert_eq!(again.resources, snapshot.resources);
+        assert_eq!(again.entities.len(), snapshot.entities.len());
+    }
+
+    #[test]
+    fn test_restore_errors_leave_world_untouched() {
+        let mut world = World::new();
+        world.insert_resource(registry());
+        let farmer = world.spawn();
+        world.insert(farmer, Health(1));
+
+        let mut snapshot = world.snapshot().unwrap();
+        snapshot.entities[0]
+            .components
+            .insert("Mana".into(), serde_json::json!(3));
+        assert_eq!(
+            world.restore(&snapshot),
+            Err(SnapshotError::UnknownComponent("Mana".into()))
+        );
+
+        snapshot.entities[0].components.remove("Mana");
+        snapshot.entities[0]
+            .components
+            .insert("Health".into(), serde_json::json!("full"));
+        assert!(matches!(
+            world.restore(&snapshot),
+            Err(SnapshotError::Deserialize { ref name, .. }) if name == "Health"
+        ));
+        assert!(world.is_alive(farmer));
+        assert_eq!(world.get::<Health>(farmer), Some(&Health(1)));
+
+        assert_eq!(World::new().snapshot(), Err(SnapshotError::NoRegistry));
+    }
+}
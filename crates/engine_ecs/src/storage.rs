@@ -0,0 +1,363 @@
+//! Component storage
+//!
+//! Each component type lives either in its own [`SparseSet`] (the default,
+//! cheap to add and remove) or in the archetype tables of
+//! [`crate::table`], picked per type with [`World::set_storage_type`].
+//! Queries see both through [`ComponentView`] and [`ComponentViewMut`].
+//!
+//! [`World::set_storage_type`]: crate::World::set_storage_type
+
+use std::any::Any;
+use std::cell::UnsafeCell;
+
+use crate::table::{TableView, TableViewMut};
+use crate::Component;
+
+/// Where the components of one type are kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum StorageType {
+    /// One sparse set per type: O(1) insert and remove, joins by entity index
+    #[default]
+    SparseSet,
+    /// Archetype tables: rows of entities with the same table components,
+    /// packed together for fast iteration, at the cost of moving a row on
+    /// every insert or remove
+    Table,
+}
+
+/// Boxed world data that parallel systems may borrow mutably through `&World`
+///
+/// Outside of [`SystemWorld`](crate::SystemWorld) everything goes through
+/// the checked accessors. The schedule only runs systems side by side when
+/// their declared accesses don't conflict, which is what makes the
+/// unchecked path sound.
+pub(crate) struct DataCell<T: ?Sized>(UnsafeCell<Box<T>>);
+
+// Safety: shared access only hands out `&T`, except through the unchecked
+// pointer whose callers guarantee exclusivity
+unsafe impl<T: ?Sized + Send + Sync> Sync for DataCell<T> {}
+
+impl<T: ?Sized> DataCell<T> {
+    pub(crate) fn new(value: Box<T>) -> Self {
+        Self(UnsafeCell::new(value))
+    }
+
+    pub(crate) fn get(&self) -> &T {
+        // Safety: no mutable borrow coexists with shared ones; see the type docs
+        unsafe { &*self.0.get() }
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut()
+    }
+
+    /// Pointer for mutable access through a shared world
+    pub(crate) fn as_ptr(&self) -> *mut T {
+        // Safety: only the box is dereferenced, to reach its heap value
+        unsafe { &mut **self.0.get() }
+    }
+
+    pub(crate) fn into_inner(self) -> Box<T> {
+        self.0.into_inner()
+    }
+}
+
+/// World ticks at which a component was added and last mutably accessed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ComponentTicks {
+    pub(crate) added: u32,
+    pub(crate) changed: u32,
+}
+
+impl ComponentTicks {
+    pub(crate) const fn new(tick: u32) -> Self {
+        Self {
+            added: tick,
+            changed: tick,
+        }
+    }
+
+    /// Check if the component was added after `last_run`
+    pub(crate) const fn is_added(self, last_run: u32) -> bool {
+        self.added > last_run
+    }
+
+    /// Check if the component was added or changed after `last_run`
+    pub(crate) const fn is_changed(self, last_run: u32) -> bool {
+        self.changed > last_run
+    }
+}
+
+/// Sparse set storage for a single component type
+pub(crate) struct SparseSet<T> {
+    /// Sparse array: entity index -> dense index
+    sparse: Vec<Option<usize>>,
+    /// Dense array of components
+    dense: Vec<T>,
+    /// Entity indices for each dense element
+    entities: Vec<u32>,
+    /// Change ticks for each dense element
+    ticks: Vec<ComponentTicks>,
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SparseSet<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            sparse: Vec::new(),
+            dense: Vec::new(),
+            entities: Vec::new(),
+            ticks: Vec::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, entity_index: u32, component: T, tick: u32) {
+        let idx = entity_index as usize;
+
+        // Grow sparse array if needed
+        if idx >= self.sparse.len() {
+            self.sparse.resize(idx + 1, None);
+        }
+
+        if let Some(dense_idx) = self.sparse[idx] {
+            // Update existing component
+            self.dense[dense_idx] = component;
+            self.ticks[dense_idx].changed = tick;
+        } else {
+            // Add new component
+            let dense_idx = self.dense.len();
+            self.sparse[idx] = Some(dense_idx);
+            self.dense.push(component);
+            self.entities.push(entity_index);
+            self.ticks.push(ComponentTicks::new(tick));
+        }
+    }
+
+    pub(crate) fn remove(&mut self, entity_index: u32) -> Option<T> {
+        let idx = entity_index as usize;
+
+        if idx >= self.sparse.len() {
+            return None;
+        }
+
+        if let Some(dense_idx) = self.sparse[idx].take() {
+            // Swap remove from dense arrays
+            let removed = self.dense.swap_remove(dense_idx);
+            self.entities.swap_remove(dense_idx);
+            self.ticks.swap_remove(dense_idx);
+
+            // Update sparse array for swapped element
+            if dense_idx < self.dense.len() {
+                let swapped_entity = self.entities[dense_idx] as usize;
+                self.sparse[swapped_entity] = Some(dense_idx);
+            }
+
+            Some(removed)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn get(&self, entity_index: u32) -> Option<&T> {
+        self.dense_index(entity_index)
+            .map(|dense_idx| &self.dense[dense_idx])
+    }
+
+    /// Get a mutable reference, marking the component changed at `tick`
+    pub(crate) fn get_mut(&mut self, entity_index: u32, tick: u32) -> Option<&mut T> {
+        self.dense_index(entity_index).map(|dense_idx| {
+            self.ticks[dense_idx].changed = tick;
+            &mut self.dense[dense_idx]
+        })
+    }
+
+    /// Get the change ticks of an entity's component
+    pub(crate) fn ticks(&self, entity_index: u32) -> Option<ComponentTicks> {
+        self.dense_index(entity_index)
+            .map(|dense_idx| self.ticks[dense_idx])
+    }
+
+    pub(crate) fn contains(&self, entity_index: u32) -> bool {
+        self.dense_index(entity_index).is_some()
+    }
+
+    /// Get the dense index of an entity's component
+    pub(crate) fn dense_index(&self, entity_index: u32) -> Option<usize> {
+        self.sparse.get(entity_index as usize).copied().flatten()
+    }
+
+    /// Entity indices in dense order
+    pub(crate) fn entities(&self) -> &[u32] {
+        &self.entities
+    }
+
+    /// Split into the parts a mutable query needs: the sparse lookup and
+    /// entity list stay shared while components and their ticks are handed
+    /// out through raw pointers, one distinct element at a time.
+    pub(crate) fn split_mut(&mut self) -> (&[Option<usize>], &[u32], *mut T, *mut ComponentTicks) {
+        (
+            &self.sparse,
+            &self.entities,
+            self.dense.as_mut_ptr(),
+            self.ticks.as_mut_ptr(),
+        )
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.dense.len()
+    }
+}
+
+/// Type-erased component storage
+pub(crate) trait ComponentStorage: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// Remove an entity's component, returning whether it had one
+    fn remove_entity(&mut self, entity_index: u32) -> bool;
+}
+
+impl<T: Component> ComponentStorage for SparseSet<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn remove_entity(&mut self, entity_index: u32) -> bool {
+        self.remove(entity_index).is_some()
+    }
+}
+
+/// Entity indices in storage order
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct EntityList<'w> {
+    pub(crate) entities: &'w [u32],
+    /// Archetype whose rows these are, for table storage
+    pub(crate) archetype: Option<usize>,
+}
+
+/// Shared access to every component of one type, wherever it is stored
+///
+/// Table components are read from the archetype set with
+/// [`ComponentView::set_archetype`], by row; sparse-set components by
+/// entity index.
+pub(crate) enum ComponentView<'w, T> {
+    Sparse(&'w SparseSet<T>),
+    Table(TableView<'w, T>),
+}
+
+impl<'w, T: Component> ComponentView<'w, T> {
+    /// Switch to the archetype of the next entities, returning `false` if
+    /// none of them can have the component
+    pub(crate) fn set_archetype(&mut self, archetype: Option<usize>) -> bool {
+        match self {
+            Self::Sparse(_) => true,
+            Self::Table(view) => view.set_archetype(archetype),
+        }
+    }
+
+    pub(crate) fn get(&self, entity_index: u32, row: usize) -> Option<&'w T> {
+        match self {
+            Self::Sparse(set) => set.get(entity_index),
+            Self::Table(view) => view.get(row),
+        }
+    }
+
+    pub(crate) fn ticks(&self, entity_index: u32, row: usize) -> Option<ComponentTicks> {
+        match self {
+            Self::Sparse(set) => set.ticks(entity_index),
+            Self::Table(view) => view.ticks(row),
+        }
+    }
+
+    pub(crate) fn contains(&self, entity_index: u32) -> bool {
+        match self {
+            Self::Sparse(set) => set.contains(entity_index),
+            Self::Table(view) => view.contains(),
+        }
+    }
+
+    /// Lists of the entities that have the component
+    pub(crate) fn entities(&self) -> Vec<EntityList<'w>> {
+        match self {
+            Self::Sparse(set) => vec![EntityList {
+                entities: set.entities(),
+                archetype: None,
+            }],
+            Self::Table(view) => view.entities(),
+        }
+    }
+}
+
+/// Mutable access to every component of one type, handed out one distinct
+/// entity at a time
+pub(crate) enum ComponentViewMut<'w, T> {
+    Sparse {
+        sparse: &'w [Option<usize>],
+        entities: &'w [u32],
+        dense: *mut T,
+        ticks: *mut ComponentTicks,
+    },
+    Table(TableViewMut<'w, T>),
+}
+
+impl<'w, T: Component> ComponentViewMut<'w, T> {
+    /// Switch to the archetype of the next entities, returning `false` if
+    /// none of them can have the component
+    pub(crate) fn set_archetype(&mut self, archetype: Option<usize>) -> bool {
+        match self {
+            Self::Sparse { .. } => true,
+            Self::Table(view) => view.set_archetype(archetype),
+        }
+    }
+
+    pub(crate) fn contains(&self, entity_index: u32) -> bool {
+        match self {
+            Self::Sparse { sparse, .. } => sparse
+                .get(entity_index as usize)
+                .is_some_and(Option::is_some),
+            Self::Table(view) => view.contains(),
+        }
+    }
+
+    /// Lists of the entities that have the component
+    pub(crate) fn entities(&self) -> Vec<EntityList<'w>> {
+        match self {
+            Self::Sparse { entities, .. } => vec![EntityList {
+                entities,
+                archetype: None,
+            }],
+            Self::Table(view) => view.entities(),
+        }
+    }
+
+    /// Get a mutable reference, marking the component changed at `tick`
+    ///
+    /// # Safety
+    /// The entity must have the component, `row` must be its row in the
+    /// current archetype for table storage, and no other reference to the
+    /// component may be alive for `'w`.
+    pub(crate) unsafe fn get_mut(&self, entity_index: u32, row: usize, tick: u32) -> &'w mut T {
+        let (value, ticks) = match self {
+            Self::Sparse {
+                sparse,
+                dense,
+                ticks,
+                ..
+            } => {
+                let dense_idx = sparse[entity_index as usize].expect("entity has the component");
+                (dense.add(dense_idx), ticks.add(dense_idx))
+            }
+            Self::Table(view) => view.row_ptrs(row),
+        };
+        (*ticks).changed = tick;
+        &mut *value
+    }
+}
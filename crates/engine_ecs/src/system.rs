@@ -0,0 +1,345 @@
+//! Systems with declared data access
+//!
+//! A system registered with [`Schedule::add_parallel_system`] states up
+//! front which components and resources it reads and writes through a
+//! [`SystemAccess`]. It then runs against a [`SystemWorld`], a view of the
+//! world limited to that access, so the schedule can run systems whose
+//! accesses don't overlap side by side. Structural changes (spawn, insert,
+//! despawn) go through the view's [`Commands`], applied once the batch is
+//! done.
+//!
+//! [`Schedule::add_parallel_system`]: crate::Schedule::add_parallel_system
+
+use std::any::type_name;
+use std::fmt;
+
+use crate::query::Access;
+use crate::{
+    Commands, Component, Entity, QueryData, QueryFilter, QueryIter, ReadOnlyQueryData, Resource,
+    World,
+};
+
+/// Components and resources a system reads and writes
+#[derive(Debug, Clone, Default)]
+pub struct SystemAccess {
+    components: Access,
+    resources: Access,
+    /// First declared query borrowing a component mutably twice, and that
+    /// component
+    aliasing: Option<(&'static str, &'static str)>,
+}
+
+impl SystemAccess {
+    /// Create an empty access set
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a shared borrow of component `T`
+    #[must_use]
+    pub fn read<T: Component>(mut self) -> Self {
+        self.components.add_read::<T>();
+        self
+    }
+
+    /// Declare a mutable borrow of component `T`
+    #[must_use]
+    pub fn write<T: Component>(mut self) -> Self {
+        self.components.add_write::<T>();
+        self
+    }
+
+    /// Declare every component a query and its filter borrow
+    ///
+    /// A query that could never run, because it borrows a component
+    /// mutably along with another borrow of it, is reported by
+    /// [`Schedule::build`](crate::Schedule::build).
+    #[must_use]
+    pub fn query<Q: QueryData, F: QueryFilter>(mut self) -> Self {
+        let mut access = Access::new();
+        Q::access(&mut access);
+        F::access(&mut access);
+        if let Some(component) = access.self_conflict() {
+            self.aliasing
+                .get_or_insert((type_name::<(Q, F)>(), component));
+        }
+        self.components.extend(&access);
+        self
+    }
+
+    /// Declare a shared borrow of resource `R`
+    #[must_use]
+    pub fn read_resource<R: Resource>(mut self) -> Self {
+        self.resources.add_read::<R>();
+        self
+    }
+
+    /// Declare a mutable borrow of resource `R`
+    #[must_use]
+    pub fn write_resource<R: Resource>(mut self) -> Self {
+        self.resources.add_write::<R>();
+        self
+    }
+
+    /// First declared query that borrows a component mutably along with
+    /// another borrow of it, with that component
+    pub(crate) fn aliasing_query(&self) -> Option<(&'static str, &'static str)> {
+        self.aliasing
+    }
+
+    /// Reasons two access sets can't run at the same time
+    #[must_use]
+    pub fn conflicts_with(&self, other: &Self) -> Vec<ConflictReason> {
+        let components = self.components.conflicts_with(&other.components);
+        let resources = self.resources.conflicts_with(&other.resources);
+        components
+            .into_iter()
+            .map(ConflictReason::Component)
+            .chain(resources.into_iter().map(ConflictReason::Resource))
+            .collect()
+    }
+}
+
+/// Why two systems of a stage are not run in parallel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictReason {
+    /// The named system takes `&mut World`
+    Exclusive(String),
+    /// One system writes a component the other reads or writes
+    Component(&'static str),
+    /// One system writes a resource the other reads or writes
+    Resource(&'static str),
+}
+
+impl fmt::Display for ConflictReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exclusive(system) => write!(f, "`{}` needs exclusive world access", system),
+            Self::Component(name) => write!(f, "component `{}` is written", name),
+            Self::Resource(name) => write!(f, "resource `{}` is written", name),
+        }
+    }
+}
+
+/// Two systems of a stage that are serialized, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemConflict {
+    /// System that runs first
+    pub first: String,
+    /// System that runs second
+    pub second: String,
+    /// Every overlapping access between the two
+    pub reasons: Vec<ConflictReason>,
+}
+
+impl fmt::Display for SystemConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` and `{}` are serialized: ", self.first, self.second)?;
+        for (i, reason) in self.reasons.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// A world view limited to a system's declared access
+///
+/// Any component or resource access outside the declaration panics, since
+/// another system may be using it on another thread.
+pub struct SystemWorld<'w> {
+    world: &'w World,
+    access: &'w SystemAccess,
+    name: &'w str,
+    last_run: u32,
+    commands: Commands,
+}
+
+impl<'w> SystemWorld<'w> {
+    /// Create a view for one system run
+    ///
+    /// # Safety
+    /// No other borrow of the components and resources `access` writes may
+    /// be alive for `'w`, and nothing may write what it reads.
+    pub(crate) unsafe fn new(
+        world: &'w World,
+        access: &'w SystemAccess,
+        name: &'w str,
+        last_run: u32,
+    ) -> Self {
+        Self {
+            world,
+            access,
+            name,
+            last_run,
+            commands: world.commands(),
+        }
+    }
+
+    /// Take the commands queued by the system
+    pub(crate) fn into_commands(self) -> Commands {
+        self.commands
+    }
+
+    /// Panic unless `access` is covered by the components declared
+    fn check_components(&self, access: &Access) {
+        if let Some(name) = access.first_undeclared(&self.access.components) {
+            panic!(
+                "system `{}` accesses component `{}` without declaring it",
+                self.name, name
+            );
+        }
+    }
+
+    /// Panic unless `access` is covered by the resources declared
+    fn check_resources(&self, access: &Access) {
+        if let Some(name) = access.first_undeclared(&self.access.resources) {
+            panic!(
+                "system `{}` accesses resource `{}` without declaring it",
+                self.name, name
+            );
+        }
+    }
+
+    /// Check if an entity is alive
+    #[must_use]
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.world.is_alive(entity)
+    }
+
+    /// Iterate over all alive entities
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.world.entities()
+    }
+
+    /// Get the number of alive entities
+    #[must_use]
+    pub fn entity_count(&self) -> usize {
+        self.world.entity_count()
+    }
+
+    /// Queue structural changes, applied after the system's batch
+    pub fn commands(&mut self) -> &mut Commands {
+        &mut self.commands
+    }
+
+    /// Query entities with a set of components
+    ///
+    /// # Panics
+    /// Panics if `Q` uses a component the system did not declare.
+    pub fn query<Q: ReadOnlyQueryData>(&self) -> QueryIter<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    /// Query entities with a set of components, restricted by a filter
+    ///
+    /// # Panics
+    /// Panics if `Q` or `F` use a component the system did not declare.
+    pub fn query_filtered<Q: ReadOnlyQueryData, F: QueryFilter>(&self) -> QueryIter<'_, Q, F> {
+        let mut access = Access::new();
+        Q::access(&mut access);
+        F::access(&mut access);
+        self.check_components(&access);
+        // Safety: read-only queries declare no writes
+        unsafe { QueryIter::new(self.world, self.last_run) }
+    }
+
+    /// Query entities with a set of components (mutable)
+    ///
+    /// # Panics
+    /// Panics if `Q` writes a component the system did not declare as
+    /// written, or under the conditions of [`World::query_mut`].
+    pub fn query_mut<Q: QueryData>(&mut self) -> QueryIter<'_, Q> {
+        self.query_filtered_mut::<Q, ()>()
+    }
+
+    /// Query entities with a set of components (mutable), restricted by a filter
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`SystemWorld::query_mut`], or
+    /// if `F` looks at a component `Q` borrows mutably.
+    pub fn query_filtered_mut<Q: QueryData, F: QueryFilter>(&mut self) -> QueryIter<'_, Q, F> {
+        let mut access = Access::new();
+        Q::access(&mut access);
+        F::access(&mut access);
+        self.check_components(&access);
+        // Safety: the writes were declared, so no other system borrows those
+        // storages, and `&mut self` keeps this system from aliasing them
+        unsafe { QueryIter::new(self.world, self.last_run) }
+    }
+
+    /// Get a component reference
+    ///
+    /// # Panics
+    /// Panics if `T` was not declared.
+    #[must_use]
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        let mut access = Access::new();
+        access.add_read::<T>();
+        self.check_components(&access);
+        self.world.get(entity)
+    }
+
+    /// Get a mutable component reference
+    ///
+    /// # Panics
+    /// Panics if `T` was not declared as written.
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        let mut access = Access::new();
+        access.add_write::<T>();
+        self.check_components(&access);
+        // Safety: the write was declared and `&mut self` prevents aliasing
+        unsafe { self.world.get_unchecked_mut(entity) }
+    }
+
+    /// Check if an entity has a component
+    ///
+    /// # Panics
+    /// Panics if `T` was not declared.
+    #[must_use]
+    pub fn has<T: Component>(&self, entity: Entity) -> bool {
+        self.get::<T>(entity).is_some()
+    }
+
+    /// Entities that lost component `T` since this system's last run
+    ///
+    /// # Panics
+    /// Panics if `T` was not declared.
+    pub fn removed<T: Component>(&self) -> impl Iterator<Item = Entity> + '_ {
+        let mut access = Access::new();
+        access.add_read::<T>();
+        self.check_components(&access);
+        self.world.removed_since::<T>(self.last_run)
+    }
+
+    /// Get a resource reference
+    ///
+    /// # Panics
+    /// Panics if `R` was not declared.
+    #[must_use]
+    pub fn resource<R: Resource>(&self) -> Option<&R> {
+        let mut access = Access::new();
+        access.add_read::<R>();
+        self.check_resources(&access);
+        self.world.get_resource()
+    }
+
+    /// Get a mutable resource reference
+    ///
+    /// # Panics
+    /// Panics if `R` was not declared as written.
+    pub fn resource_mut<R: Resource>(&mut self) -> Option<&mut R> {
+        let mut access = Access::new();
+        access.add_write::<R>();
+        self.check_resources(&access);
+        // Safety: the write was declared and `&mut self` prevents aliasing
+        unsafe {
+            self.world
+                .resource_ptr::<R>()
+                .map(|resource| &mut *resource)
+        }
+    }
+}
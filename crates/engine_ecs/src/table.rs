@@ -0,0 +1,676 @@
+//! Archetype tables for components registered with [`StorageType::Table`]
+//!
+//! Entities with the same set of table components share an archetype, whose
+//! table keeps one densely packed column per component type with rows lined
+//! up by entity. Iterating several table components then walks each column
+//! in order instead of jumping between sparse sets. Adding or removing a
+//! table component moves the entity's row to the matching archetype;
+//! sparse-set components are stored elsewhere and never move.
+//!
+//! [`StorageType::Table`]: crate::StorageType::Table
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use crate::storage::{ComponentTicks, DataCell, EntityList};
+use crate::Component;
+
+/// Row of an entity in its archetype's table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct EntityLocation {
+    pub(crate) archetype: usize,
+    pub(crate) row: usize,
+}
+
+/// Type-erased table column
+pub(crate) trait Column: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// Create an empty column of the same type
+    fn new_empty(&self) -> Box<dyn Column>;
+    /// Swap-remove a row, pushing it onto `dst` (same type) or dropping it
+    fn move_row(&mut self, row: usize, dst: Option<&mut dyn Column>);
+}
+
+/// Components of one type in an archetype, one per row
+pub(crate) struct TypedColumn<T> {
+    data: Vec<T>,
+    ticks: Vec<ComponentTicks>,
+}
+
+impl<T: Component> TypedColumn<T> {
+    fn boxed() -> Box<dyn Column> {
+        Box::new(Self {
+            data: Vec::new(),
+            ticks: Vec::new(),
+        })
+    }
+
+    fn push(&mut self, component: T, ticks: ComponentTicks) {
+        self.data.push(component);
+        self.ticks.push(ticks);
+    }
+
+    fn swap_remove(&mut self, row: usize) -> T {
+        self.ticks.swap_remove(row);
+        self.data.swap_remove(row)
+    }
+
+    pub(crate) fn get(&self, row: usize) -> &T {
+        &self.data[row]
+    }
+
+    /// Get a mutable reference, marking the component changed at `tick`
+    pub(crate) fn get_mut(&mut self, row: usize, tick: u32) -> &mut T {
+        self.ticks[row].changed = tick;
+        &mut self.data[row]
+    }
+
+    pub(crate) fn ticks(&self, row: usize) -> ComponentTicks {
+        self.ticks[row]
+    }
+
+    /// Pointers to the first component and its ticks, for handing out
+    /// distinct rows mutably through a shared view
+    fn as_mut_ptrs(&mut self) -> (*mut T, *mut ComponentTicks) {
+        (self.data.as_mut_ptr(), self.ticks.as_mut_ptr())
+    }
+}
+
+impl<T: Component> Column for TypedColumn<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn new_empty(&self) -> Box<dyn Column> {
+        Self::boxed()
+    }
+
+    fn move_row(&mut self, row: usize, dst: Option<&mut dyn Column>) {
+        let ticks = self.ticks[row];
+        let component = self.swap_remove(row);
+        if let Some(dst) = dst {
+            dst.as_any_mut()
+                .downcast_mut::<Self>()
+                .expect("column types match")
+                .push(component, ticks);
+        }
+    }
+}
+
+/// The table of every entity with one exact set of table components
+pub(crate) struct Archetype {
+    /// Component types, sorted
+    types: Vec<TypeId>,
+    columns: HashMap<TypeId, DataCell<dyn Column>>,
+    /// Entity index of each row
+    entities: Vec<u32>,
+    /// Archetype reached by adding each component type
+    add_edges: HashMap<TypeId, usize>,
+    /// Archetype reached by removing each component type, `None` when
+    /// no table component is left
+    remove_edges: HashMap<TypeId, Option<usize>>,
+}
+
+impl Archetype {
+    fn column<T: Component>(&self) -> Option<&TypedColumn<T>> {
+        self.columns
+            .get(&TypeId::of::<T>())
+            .map(|column| column.get().as_any().downcast_ref().unwrap())
+    }
+
+    fn column_mut<T: Component>(&mut self) -> Option<&mut TypedColumn<T>> {
+        self.columns
+            .get_mut(&TypeId::of::<T>())
+            .map(|column| column.get_mut().as_any_mut().downcast_mut().unwrap())
+    }
+
+    /// Pointer to a column, for mutation through a shared world
+    ///
+    /// # Safety
+    /// No other borrow of this column may be alive while the pointer is
+    /// dereferenced mutably.
+    unsafe fn column_ptr<T: Component>(&self) -> Option<*mut TypedColumn<T>> {
+        self.columns.get(&TypeId::of::<T>()).map(|column| {
+            let column = &mut *column.as_ptr();
+            let typed: *mut TypedColumn<T> = column.as_any_mut().downcast_mut().unwrap();
+            typed
+        })
+    }
+
+    /// Swap-remove a row, moving its components into `dst` where `dst` has
+    /// a column for them and dropping the rest
+    ///
+    /// `taken` names a column whose row was already removed by the caller.
+    /// Returns the entity moved into the freed row, if any.
+    fn move_row(
+        &mut self,
+        row: usize,
+        mut dst: Option<&mut Self>,
+        taken: Option<TypeId>,
+    ) -> Option<u32> {
+        for (type_id, column) in &mut self.columns {
+            if Some(*type_id) == taken {
+                continue;
+            }
+            match dst
+                .as_deref_mut()
+                .and_then(|dst| dst.columns.get_mut(type_id))
+            {
+                Some(target) => column.get_mut().move_row(row, Some(target.get_mut())),
+                None => column.get_mut().move_row(row, None),
+            }
+        }
+
+        self.entities.swap_remove(row);
+        self.entities.get(row).copied()
+    }
+}
+
+/// Every archetype table, and which row each entity occupies
+#[derive(Default)]
+pub(crate) struct Tables {
+    archetypes: Vec<Archetype>,
+    /// Archetype of each sorted type set
+    ids: HashMap<Vec<TypeId>, usize>,
+    /// Location of each entity index, `None` without table components
+    locations: Vec<Option<EntityLocation>>,
+    /// Archetype reached by adding a first table component
+    root_edges: HashMap<TypeId, usize>,
+}
+
+impl Tables {
+    pub(crate) fn location(&self, entity_index: u32) -> Option<EntityLocation> {
+        self.locations.get(entity_index as usize).copied().flatten()
+    }
+
+    fn set_location(&mut self, entity_index: u32, location: Option<EntityLocation>) {
+        let idx = entity_index as usize;
+        if idx >= self.locations.len() {
+            self.locations.resize(idx + 1, None);
+        }
+        self.locations[idx] = location;
+    }
+
+    /// Check if any archetype stores component `T`
+    pub(crate) fn has_column<T: Component>(&self) -> bool {
+        let type_id = TypeId::of::<T>();
+        self.archetypes
+            .iter()
+            .any(|archetype| archetype.columns.contains_key(&type_id))
+    }
+
+    /// Find or create the archetype for a sorted type set
+    ///
+    /// New columns are created empty, like those of `src` where it has the
+    /// type and with `new_column` otherwise.
+    fn archetype_for(
+        &mut self,
+        types: Vec<TypeId>,
+        src: Option<usize>,
+        new_column: fn() -> Box<dyn Column>,
+    ) -> usize {
+        if let Some(&id) = self.ids.get(&types) {
+            return id;
+        }
+
+        let columns = types
+            .iter()
+            .map(|type_id| {
+                let column = src
+                    .and_then(|src| self.archetypes[src].columns.get(type_id))
+                    .map_or_else(new_column, |column| column.get().new_empty());
+                (*type_id, DataCell::new(column))
+            })
+            .collect();
+        let id = self.archetypes.len();
+        self.archetypes.push(Archetype {
+            types: types.clone(),
+            columns,
+            entities: Vec::new(),
+            add_edges: HashMap::new(),
+            remove_edges: HashMap::new(),
+        });
+        self.ids.insert(types, id);
+        id
+    }
+
+    /// Archetype reached by adding `T` to the components of `src`
+    fn archetype_with<T: Component>(&mut self, src: Option<usize>) -> usize {
+        let type_id = TypeId::of::<T>();
+        let edges = match src {
+            Some(src) => &mut self.archetypes[src].add_edges,
+            None => &mut self.root_edges,
+        };
+        if let Some(&dst) = edges.get(&type_id) {
+            return dst;
+        }
+
+        let mut types = src.map_or_else(Vec::new, |src| self.archetypes[src].types.clone());
+        let pos = types.binary_search(&type_id).unwrap_or_else(|pos| pos);
+        types.insert(pos, type_id);
+        let dst = self.archetype_for(types, src, TypedColumn::<T>::boxed);
+        match src {
+            Some(src) => self.archetypes[src].add_edges.insert(type_id, dst),
+            None => self.root_edges.insert(type_id, dst),
+        };
+        dst
+    }
+
+    /// Archetype reached by removing `T` from the components of `src`
+    fn archetype_without<T: Component>(&mut self, src: usize) -> Option<usize> {
+        let type_id = TypeId::of::<T>();
+        if let Some(&dst) = self.archetypes[src].remove_edges.get(&type_id) {
+            return dst;
+        }
+
+        let types: Vec<TypeId> = self.archetypes[src]
+            .types
+            .iter()
+            .copied()
+            .filter(|&other| other != type_id)
+            .collect();
+        let dst = (!types.is_empty())
+            .then(|| self.archetype_for(types, Some(src), TypedColumn::<T>::boxed));
+        self.archetypes[src].remove_edges.insert(type_id, dst);
+        dst
+    }
+
+    /// Add an entity at the end of an archetype's table
+    ///
+    /// The caller pushes its components onto every column.
+    fn push_entity(&mut self, entity_index: u32, archetype: usize) -> EntityLocation {
+        let entities = &mut self.archetypes[archetype].entities;
+        entities.push(entity_index);
+        let location = EntityLocation {
+            archetype,
+            row: entities.len() - 1,
+        };
+        self.set_location(entity_index, Some(location));
+        location
+    }
+
+    /// Move an entity's row out of `src`, into archetype `dst` if given
+    ///
+    /// Components `dst` has no column for are dropped, except `taken`,
+    /// which the caller already removed.
+    fn move_entity(&mut self, src: EntityLocation, dst: Option<usize>, taken: Option<TypeId>) {
+        let (from, to) = match dst {
+            Some(dst) => {
+                let (from, to) = pair_mut(&mut self.archetypes, src.archetype, dst);
+                (from, Some(to))
+            }
+            None => (&mut self.archetypes[src.archetype], None),
+        };
+        let entity_index = from.entities[src.row];
+        if let Some(swapped) = from.move_row(src.row, to, taken) {
+            self.set_location(swapped, Some(src));
+        }
+
+        match dst {
+            Some(dst) => {
+                self.push_entity(entity_index, dst);
+            }
+            None => self.set_location(entity_index, None),
+        }
+    }
+
+    pub(crate) fn insert<T: Component>(&mut self, entity_index: u32, component: T, tick: u32) {
+        let location = self.location(entity_index);
+        if let Some(location) = location {
+            if let Some(column) = self.archetypes[location.archetype].column_mut::<T>() {
+                // Update existing component
+                *column.get_mut(location.row, tick) = component;
+                return;
+            }
+        }
+
+        // Move to the archetype with `T` added
+        let dst = self.archetype_with::<T>(location.map(|location| location.archetype));
+
+        match location {
+            Some(location) => self.move_entity(location, Some(dst), None),
+            None => {
+                self.push_entity(entity_index, dst);
+            }
+        }
+        self.archetypes[dst]
+            .column_mut::<T>()
+            .expect("archetype has a column for the new component")
+            .push(component, ComponentTicks::new(tick));
+    }
+
+    pub(crate) fn remove<T: Component>(&mut self, entity_index: u32) -> Option<T> {
+        let location = self.location(entity_index)?;
+        let type_id = TypeId::of::<T>();
+        let component = self.archetypes[location.archetype]
+            .column_mut::<T>()?
+            .swap_remove(location.row);
+
+        let dst = self.archetype_without::<T>(location.archetype);
+        self.move_entity(location, dst, Some(type_id));
+        Some(component)
+    }
+
+    /// Drop every table component of an entity, returning their types
+    pub(crate) fn remove_entity(&mut self, entity_index: u32) -> Vec<TypeId> {
+        let Some(location) = self.location(entity_index) else {
+            return Vec::new();
+        };
+        let types = self.archetypes[location.archetype].types.clone();
+        self.move_entity(location, None, None);
+        types
+    }
+
+    pub(crate) fn get<T: Component>(&self, entity_index: u32) -> Option<&T> {
+        let location = self.location(entity_index)?;
+        self.archetypes[location.archetype]
+            .column::<T>()
+            .map(|column| column.get(location.row))
+    }
+
+    /// Get a mutable reference, marking the component changed at `tick`
+    pub(crate) fn get_mut<T: Component>(&mut self, entity_index: u32, tick: u32) -> Option<&mut T> {
+        let location = self.location(entity_index)?;
+        self.archetypes[location.archetype]
+            .column_mut::<T>()
+            .map(|column| column.get_mut(location.row, tick))
+    }
+
+    /// Get a mutable reference through a shared world
+    ///
+    /// # Safety
+    /// No other borrow of `T`'s columns may be alive for the returned
+    /// lifetime.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn get_unchecked_mut<T: Component>(
+        &self,
+        entity_index: u32,
+        tick: u32,
+    ) -> Option<&mut T> {
+        let location = self.location(entity_index)?;
+        self.archetypes[location.archetype]
+            .column_ptr::<T>()
+            .map(|column| (*column).get_mut(location.row, tick))
+    }
+
+    pub(crate) fn contains<T: Component>(&self, entity_index: u32) -> bool {
+        self.location(entity_index).is_some_and(|location| {
+            self.archetypes[location.archetype]
+                .columns
+                .contains_key(&TypeId::of::<T>())
+        })
+    }
+
+    /// Shared view of component `T` across archetypes, if any stores it
+    pub(crate) fn view<T: Component>(&self) -> Option<TableView<'_, T>> {
+        let columns: Vec<_> = self.archetypes.iter().map(Archetype::column::<T>).collect();
+        columns.iter().any(Option::is_some).then(|| TableView {
+            current: None,
+            entities: self.entities_with(&columns),
+            columns,
+        })
+    }
+
+    /// Mutable view of component `T` across archetypes, if any stores it
+    ///
+    /// # Safety
+    /// No other borrow of `T`'s columns may be alive for `'w`.
+    pub(crate) unsafe fn view_mut<T: Component>(&self) -> Option<TableViewMut<'_, T>> {
+        let columns: Vec<_> = self
+            .archetypes
+            .iter()
+            .map(|archetype| {
+                archetype
+                    .column_ptr::<T>()
+                    .map(|column| (*column).as_mut_ptrs())
+            })
+            .collect();
+        columns.iter().any(Option::is_some).then(|| TableViewMut {
+            current: None,
+            entities: self.entities_with(&columns),
+            columns,
+        })
+    }
+
+    /// Entity lists of the archetypes with a column present
+    fn entities_with<C>(&self, columns: &[Option<C>]) -> Vec<EntityList<'_>> {
+        self.archetypes
+            .iter()
+            .zip(columns)
+            .enumerate()
+            .filter(|(_, (_, column))| column.is_some())
+            .map(|(id, (archetype, _))| EntityList {
+                entities: &archetype.entities,
+                archetype: Some(id),
+            })
+            .collect()
+    }
+}
+
+/// Borrow two distinct archetypes mutably
+fn pair_mut(archetypes: &mut [Archetype], a: usize, b: usize) -> (&mut Archetype, &mut Archetype) {
+    debug_assert_ne!(a, b);
+    if a < b {
+        let (left, right) = archetypes.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = archetypes.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    }
+}
+
+/// Shared access to one table component across every archetype storing it
+///
+/// Rows are read from the archetype picked with [`TableView::set_archetype`].
+pub(crate) struct TableView<'w, T> {
+    /// Column of each archetype, `None` where the archetype lacks `T`
+    columns: Vec<Option<&'w TypedColumn<T>>>,
+    current: Option<&'w TypedColumn<T>>,
+    entities: Vec<EntityList<'w>>,
+}
+
+impl<'w, T: Component> TableView<'w, T> {
+    /// Switch to another archetype's column, returning whether it has one
+    pub(crate) fn set_archetype(&mut self, archetype: Option<usize>) -> bool {
+        self.current = archetype.and_then(|archetype| self.columns[archetype]);
+        self.current.is_some()
+    }
+
+    pub(crate) fn get(&self, row: usize) -> Option<&'w T> {
+        self.current.map(|column| column.get(row))
+    }
+
+    pub(crate) fn ticks(&self, row: usize) -> Option<ComponentTicks> {
+        self.current.map(|column| column.ticks(row))
+    }
+
+    pub(crate) fn contains(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Entity lists of the archetypes storing `T`
+    pub(crate) fn entities(&self) -> Vec<EntityList<'w>> {
+        self.entities.clone()
+    }
+}
+
+/// Mutable access to one table component across every archetype storing it
+///
+/// Rows are handed out from the archetype picked with
+/// [`TableViewMut::set_archetype`].
+pub(crate) struct TableViewMut<'w, T> {
+    /// Component and tick pointers of each archetype's column
+    columns: Vec<Option<(*mut T, *mut ComponentTicks)>>,
+    current: Option<(*mut T, *mut ComponentTicks)>,
+    entities: Vec<EntityList<'w>>,
+}
+
+impl<'w, T: Component> TableViewMut<'w, T> {
+    /// Switch to another archetype's column, returning whether it has one
+    pub(crate) fn set_archetype(&mut self, archetype: Option<usize>) -> bool {
+        self.current = archetype.and_then(|archetype| self.columns[archetype]);
+        self.current.is_some()
+    }
+
+    pub(crate) fn contains(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Entity lists of the archetypes storing `T`
+    pub(crate) fn entities(&self) -> Vec<EntityList<'w>> {
+        self.entities.clone()
+    }
+
+    /// Pointers to a row's component and its ticks
+    ///
+    /// # Safety
+    /// The current archetype must have the column, and `row` must be in
+    /// bounds.
+    pub(crate) unsafe fn row_ptrs(&self, row: usize) -> (*mut T, *mut ComponentTicks) {
+        let (data, ticks) = self.current.expect("archetype has the component");
+        (data.add(row), ticks.add(row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Added, Changed, Entity, StorageType, With, Without, World};
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Position(f32);
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Velocity(f32);
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Watered;
+
+    fn table_world() -> World {
+        let mut world = World::new();
+        world.set_storage_type::<Position>(StorageType::Table);
+        world.set_storage_type::<Velocity>(StorageType::Table);
+        world
+    }
+
+    /// Apply the same inserts, removals and despawns to a world
+    fn populate(world: &mut World) -> Vec<Entity> {
+        let entities: Vec<_> = (0..6u8)
+            .map(|i| {
+                let entity = world.spawn();
+                world.insert(entity, Position(f32::from(i)));
+                if i % 2 == 0 {
+                    world.insert(entity, Velocity(1.0));
+                }
+                if i % 3 == 0 {
+                    world.insert(entity, Watered);
+                }
+                entity
+            })
+            .collect();
+
+        world.remove::<Velocity>(entities[2]);
+        world.insert(entities[3], Velocity(2.0));
+        world.despawn(entities[4]);
+        entities
+    }
+
+    fn sorted<T>(mut items: Vec<(Entity, T)>) -> Vec<(Entity, T)> {
+        items.sort_by_key(|(entity, _)| entity.index);
+        items
+    }
+
+    #[test]
+    fn test_table_rows_follow_inserts_and_removals() {
+        let mut world = table_world();
+        let entities = populate(&mut world);
+
+        assert_eq!(world.get::<Position>(entities[5]), Some(&Position(5.0)));
+        assert_eq!(world.get::<Velocity>(entities[0]), Some(&Velocity(1.0)));
+        assert_eq!(world.get::<Velocity>(entities[3]), Some(&Velocity(2.0)));
+        assert!(!world.has::<Velocity>(entities[2]));
+        assert!(world.has::<Position>(entities[2]));
+        assert_eq!(world.get::<Position>(entities[4]), None);
+
+        world.get_mut::<Position>(entities[1]).unwrap().0 = 10.0;
+        assert_eq!(world.remove::<Position>(entities[1]), Some(Position(10.0)));
+        assert_eq!(world.remove::<Position>(entities[1]), None);
+        assert_eq!(world.get::<Position>(entities[3]), Some(&Position(3.0)));
+
+        let removed: Vec<_> = world.removed::<Position>().collect();
+        assert_eq!(removed, vec![entities[4], entities[1]]);
+    }
+
+    #[test]
+    fn test_queries_match_across_storage_types() {
+        let mut sparse = World::new();
+        let mut table = table_world();
+        populate(&mut sparse);
+        populate(&mut table);
+
+        for world in [&mut sparse, &mut table] {
+            for (_, (pos, vel)) in world.query_mut::<(&mut Position, &Velocity)>() {
+                pos.0 += vel.0;
+            }
+        }
+
+        let pairs = |world: &World| {
+            sorted(
+                world
+                    .query::<(&Position, Option<&Velocity>)>()
+                    .map(|(e, (p, v))| (e, (*p, v.copied())))
+                    .collect(),
+            )
+        };
+        assert_eq!(pairs(&sparse), pairs(&table));
+
+        let watered = |world: &World| {
+            sorted(
+                world
+                    .query_filtered::<&Position, (With<Watered>, Without<Velocity>)>()
+                    .map(|(e, p)| (e, *p))
+                    .collect(),
+            )
+        };
+        assert_eq!(watered(&sparse), watered(&table));
+        assert_eq!(watered(&table).len(), 0);
+        assert_eq!(table.query::<&Velocity>().count(), 2);
+    }
+
+    #[test]
+    fn test_table_change_detection() {
+        let mut world = table_world();
+        let a = world.spawn();
+        let b = world.spawn();
+        world.insert(a, Position(0.0));
+        world.insert(b, Position(0.0));
+        world.clear_trackers();
+
+        world.get_mut::<Position>(a).unwrap().0 = 1.0;
+        // Moving `b` to another archetype keeps its ticks
+        world.insert(b, Velocity(0.0));
+        let changed: Vec<_> = world
+            .query_filtered::<&Position, Changed<Position>>()
+            .map(|(e, _)| e)
+            .collect();
+        assert_eq!(changed, vec![a]);
+        let added: Vec<_> = world
+            .query_filtered::<&Position, Added<Velocity>>()
+            .map(|(e, _)| e)
+            .collect();
+        assert_eq!(added, vec![b]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot change the storage")]
+    fn test_storage_type_fixed_once_used() {
+        let mut world = World::new();
+        let e = world.spawn();
+        world.insert(e, Position(0.0));
+        world.set_storage_type::<Position>(StorageType::Table);
+    }
+}
@@ -0,0 +1,593 @@
+//! The World: entity allocation, component storages and resources
+
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::commands::Commands;
+use crate::entity::EntityAllocator;
+use crate::hierarchy::Children;
+use crate::observer::{Hooks, Observers};
+use crate::query::{QueryData, QueryFilter, QueryIter, ReadOnlyQueryData};
+use crate::storage::{
+    ComponentStorage, ComponentView, ComponentViewMut, DataCell, SparseSet, StorageType,
+};
+use crate::table::Tables;
+use crate::{Component, Entity, Resource};
+
+/// The World stores all entities and components
+pub struct World {
+    /// Entity generations (index -> generation)
+    pub(crate) generations: Vec<u32>,
+    /// Whether each entity index is currently alive
+    pub(crate) alive: Vec<bool>,
+    /// Entity ID allocator, shared with command buffers
+    allocator: Arc<Mutex<EntityAllocator>>,
+    /// Sparse-set component storages by type
+    storages: HashMap<TypeId, DataCell<dyn ComponentStorage>>,
+    /// Archetype tables holding the components stored as tables
+    tables: Tables,
+    /// Storage chosen for each component type, sparse set if absent
+    storage_types: HashMap<TypeId, StorageType>,
+    /// Resources (singleton data) by type
+    resources: HashMap<TypeId, DataCell<dyn Any + Send + Sync>>,
+    /// Count of alive entities
+    entity_count: usize,
+    /// Current change tick, stamped on added and mutated components
+    change_tick: u32,
+    /// Tick the running system last ran at; changes after it are visible
+    last_change_tick: u32,
+    /// Change tick at the previous `clear_trackers` call
+    last_clear_tick: u32,
+    /// Entities that lost a component, with the tick it was removed at
+    removed: HashMap<TypeId, Vec<(Entity, u32)>>,
+    /// Buffer swaps for each registered event type
+    pub(crate) event_updaters: Vec<fn(&mut World)>,
+    /// Component insert and remove hooks
+    pub(crate) hooks: Hooks,
+    /// Observers of entity-targeted events
+    pub(crate) observers: Observers,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl World {
+    /// Create a new empty world
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            generations: Vec::new(),
+            alive: Vec::new(),
+            allocator: Arc::default(),
+            storages: HashMap::new(),
+            tables: Tables::default(),
+            storage_types: HashMap::new(),
+            resources: HashMap::new(),
+            entity_count: 0,
+            change_tick: 1,
+            last_change_tick: 0,
+            last_clear_tick: 0,
+            removed: HashMap::new(),
+            event_updaters: Vec::new(),
+            hooks: Hooks::default(),
+            observers: Observers::default(),
+        }
+    }
+
+    /// Spawn a new entity
+    pub fn spawn(&mut self) -> Entity {
+        let entity = self.allocator().alloc();
+        self.spawn_reserved(entity);
+        entity
+    }
+
+    /// Bring an ID reserved by the allocator to life
+    pub(crate) fn spawn_reserved(&mut self, entity: Entity) {
+        let index = entity.index as usize;
+        if index >= self.generations.len() {
+            // Indices reserved by command buffers may not be spawned in order
+            self.generations.resize(index + 1, 0);
+            self.alive.resize(index + 1, false);
+        }
+
+        debug_assert!(!self.alive[index], "entity index {} spawned twice", index);
+        self.generations[index] = entity.generation;
+        self.alive[index] = true;
+        self.entity_count += 1;
+    }
+
+    /// Despawn an entity and remove all its components
+    ///
+    /// Children are despawned with it, and it is detached from its parent.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        // Despawn the hierarchy below this entity first
+        if let Some(children) = self.remove::<Children>(entity) {
+            for child in children.0 {
+                self.despawn(child);
+            }
+        }
+        self.run_despawn_hooks(entity);
+        if !self.is_alive(entity) {
+            // A hook despawned it already
+            return true;
+        }
+        self.detach_from_parent(entity);
+        self.remove_observers(entity);
+
+        // Remove all components
+        for (type_id, storage) in &mut self.storages {
+            if storage.get_mut().remove_entity(entity.index) {
+                self.removed
+                    .entry(*type_id)
+                    .or_default()
+                    .push((entity, self.change_tick));
+            }
+        }
+        for type_id in self.tables.remove_entity(entity.index) {
+            self.removed
+                .entry(type_id)
+                .or_default()
+                .push((entity, self.change_tick));
+        }
+
+        // Increment generation and mark as free
+        let index = entity.index as usize;
+        self.generations[index] += 1;
+        self.alive[index] = false;
+        self.entity_count -= 1;
+        let freed = Entity::new(entity.index, self.generations[index]);
+        self.allocator().free(freed);
+
+        true
+    }
+
+    /// Check if an entity is alive
+    #[must_use]
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let idx = entity.index as usize;
+        idx < self.generations.len()
+            && self.alive[idx]
+            && self.generations[idx] == entity.generation
+    }
+
+    /// Create a command buffer for deferred spawns, despawns and inserts
+    ///
+    /// The buffer does not borrow the world, so it can be filled while a
+    /// query is being iterated and applied afterwards with
+    /// [`Commands::apply`].
+    #[must_use]
+    pub fn commands(&self) -> Commands {
+        Commands::new(Arc::clone(&self.allocator))
+    }
+
+    /// Check if a command buffer's allocator belongs to this world
+    pub(crate) fn owns_allocator(&self, allocator: &Arc<Mutex<EntityAllocator>>) -> bool {
+        Arc::ptr_eq(&self.allocator, allocator)
+    }
+
+    /// Lock the entity allocator
+    fn allocator(&self) -> MutexGuard<'_, EntityAllocator> {
+        // The allocator has no invariant a panic could break halfway
+        self.allocator
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get the number of alive entities
+    #[must_use]
+    pub fn entity_count(&self) -> usize {
+        self.entity_count
+    }
+
+    /// Iterate over all alive entities
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.generations
+            .iter()
+            .zip(&self.alive)
+            .enumerate()
+            .filter(|(_, (_, &alive))| alive)
+            .map(|(index, (&generation, _))| Entity::new(index as u32, generation))
+    }
+
+    /// Choose where components of type `T` are stored
+    ///
+    /// Table storage suits components iterated together in bulk; sparse
+    /// sets (the default) suit components added and removed often, such as
+    /// markers.
+    ///
+    /// # Panics
+    /// Panics if components of type `T` already exist in another storage.
+    pub fn set_storage_type<T: Component>(&mut self, storage_type: StorageType) {
+        if self.storage_type::<T>() == storage_type {
+            return;
+        }
+        let in_use = match self.storage_type::<T>() {
+            StorageType::SparseSet => self.storage::<T>().is_some_and(|set| set.len() > 0),
+            StorageType::Table => self.tables.has_column::<T>(),
+        };
+        assert!(
+            !in_use,
+            "cannot change the storage of `{}` once components are stored",
+            type_name::<T>()
+        );
+        self.storage_types.insert(TypeId::of::<T>(), storage_type);
+    }
+
+    /// Get where components of type `T` are stored
+    #[must_use]
+    pub fn storage_type<T: Component>(&self) -> StorageType {
+        self.storage_types
+            .get(&TypeId::of::<T>())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Add a component to an entity
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) {
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        let tick = self.change_tick;
+        if self.storage_type::<T>() == StorageType::Table {
+            self.tables.insert(entity.index, component, tick);
+        } else {
+            let storage = self
+                .storages
+                .entry(TypeId::of::<T>())
+                .or_insert_with(|| DataCell::new(Box::new(SparseSet::<T>::new())));
+
+            storage
+                .get_mut()
+                .as_any_mut()
+                .downcast_mut::<SparseSet<T>>()
+                .unwrap()
+                .insert(entity.index, component, tick);
+        }
+        self.run_insert_hooks(TypeId::of::<T>(), entity);
+    }
+
+    /// Remove a component from an entity
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        self.run_remove_hooks(TypeId::of::<T>(), entity);
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        let removed = match self.storage_type::<T>() {
+            StorageType::SparseSet => self
+                .storage_mut::<T>()
+                .and_then(|storage| storage.remove(entity.index)),
+            StorageType::Table => self.tables.remove(entity.index),
+        };
+        if removed.is_some() {
+            self.removed
+                .entry(TypeId::of::<T>())
+                .or_default()
+                .push((entity, self.change_tick));
+        }
+        removed
+    }
+
+    /// Get a component reference
+    #[must_use]
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        match self.storage_type::<T>() {
+            StorageType::SparseSet => self
+                .storage::<T>()
+                .and_then(|storage| storage.get(entity.index)),
+            StorageType::Table => self.tables.get(entity.index),
+        }
+    }
+
+    /// Get a mutable component reference
+    ///
+    /// The component counts as changed, whether or not it is written to.
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        let tick = self.change_tick;
+        match self.storage_type::<T>() {
+            StorageType::SparseSet => self
+                .storage_mut::<T>()
+                .and_then(|storage| storage.get_mut(entity.index, tick)),
+            StorageType::Table => self.tables.get_mut(entity.index, tick),
+        }
+    }
+
+    /// Get a mutable component reference through a shared world
+    ///
+    /// # Safety
+    /// No other borrow of `T` components may be alive for the returned
+    /// lifetime.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn get_unchecked_mut<T: Component>(&self, entity: Entity) -> Option<&mut T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        let tick = self.change_tick;
+        match self.storage_type::<T>() {
+            StorageType::SparseSet => {
+                let set = self.storage_ptr::<T>()?;
+                (*set).get_mut(entity.index, tick)
+            }
+            StorageType::Table => self.tables.get_unchecked_mut(entity.index, tick),
+        }
+    }
+
+    /// Check if an entity has a component
+    #[must_use]
+    pub fn has<T: Component>(&self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        match self.storage_type::<T>() {
+            StorageType::SparseSet => self
+                .storage::<T>()
+                .is_some_and(|storage| storage.contains(entity.index)),
+            StorageType::Table => self.tables.contains::<T>(entity.index),
+        }
+    }
+
+    /// Query entities with a set of components
+    ///
+    /// `Q` is a component reference or a tuple of them, e.g.
+    /// `world.query::<(&Position, &Collider)>()`. Only shared references are
+    /// accepted here; use [`World::query_mut`] to borrow components mutably.
+    pub fn query<Q: ReadOnlyQueryData>(&self) -> QueryIter<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    /// Query entities with a set of components, restricted by a filter
+    ///
+    /// `F` is a filter such as `With<T>`, `Without<T>` or a tuple of them,
+    /// e.g. `world.query_filtered::<&Position, Without<Npc>>()`.
+    pub fn query_filtered<Q: ReadOnlyQueryData, F: QueryFilter>(&self) -> QueryIter<'_, Q, F> {
+        // Safety: read-only queries declare no writes
+        unsafe { QueryIter::new(self, self.last_change_tick) }
+    }
+
+    /// Query entities with a set of components (mutable)
+    ///
+    /// `Q` may mix shared and mutable references, e.g.
+    /// `world.query_mut::<(&Position, &mut Velocity)>()`.
+    ///
+    /// # Panics
+    /// Panics if `Q` borrows the same component type mutably more than once,
+    /// or both mutably and immutably.
+    pub fn query_mut<Q: QueryData>(&mut self) -> QueryIter<'_, Q> {
+        self.query_filtered_mut::<Q, ()>()
+    }
+
+    /// Query entities with a set of components (mutable), restricted by a filter
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`World::query_mut`], or if `F`
+    /// looks at a component `Q` borrows mutably.
+    pub fn query_filtered_mut<Q: QueryData, F: QueryFilter>(&mut self) -> QueryIter<'_, Q, F> {
+        // Safety: we hold the unique borrow of the world for the iterator's lifetime
+        unsafe { QueryIter::new(self, self.last_change_tick) }
+    }
+
+    /// Entities that lost component `T` since the last run of the current system
+    ///
+    /// Covers both `remove` and `despawn`. Outside a schedule, "last run" is
+    /// the previous [`World::clear_trackers`] call.
+    pub fn removed<T: Component>(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.removed_since::<T>(self.last_change_tick)
+    }
+
+    /// Entities that lost component `T` after a given tick
+    pub(crate) fn removed_since<T: Component>(
+        &self,
+        last_run: u32,
+    ) -> impl Iterator<Item = Entity> + '_ {
+        self.removed
+            .get(&TypeId::of::<T>())
+            .into_iter()
+            .flatten()
+            .filter(move |&&(_, tick)| tick > last_run)
+            .map(|&(entity, _)| entity)
+    }
+
+    /// Get the current change tick
+    #[must_use]
+    pub fn change_tick(&self) -> u32 {
+        self.change_tick
+    }
+
+    /// Get the tick changes are compared against by `Added`, `Changed`
+    /// and [`World::removed`]
+    #[must_use]
+    pub fn last_change_tick(&self) -> u32 {
+        self.last_change_tick
+    }
+
+    /// Advance the change tick, returning the new value
+    pub(crate) fn increment_change_tick(&mut self) -> u32 {
+        self.change_tick += 1;
+        self.change_tick
+    }
+
+    /// Set the tick changes are compared against
+    pub(crate) fn set_last_change_tick(&mut self, tick: u32) {
+        self.last_change_tick = tick;
+    }
+
+    /// Mark everything so far as seen, for code running outside a schedule
+    ///
+    /// Call once per frame. Removal records are kept for one more frame so
+    /// systems that ran before the removal still get to see it.
+    pub fn clear_trackers(&mut self) {
+        let last_clear = self.last_clear_tick;
+        for removed in self.removed.values_mut() {
+            removed.retain(|&(_, tick)| tick > last_clear);
+        }
+
+        self.last_clear_tick = self.change_tick;
+        self.last_change_tick = self.change_tick;
+        self.change_tick += 1;
+    }
+
+    /// Get the sparse set storing a component type
+    fn storage<T: Component>(&self) -> Option<&SparseSet<T>> {
+        self.storages.get(&TypeId::of::<T>()).map(|storage| {
+            storage
+                .get()
+                .as_any()
+                .downcast_ref::<SparseSet<T>>()
+                .unwrap()
+        })
+    }
+
+    /// Get the mutable sparse set storing a component type
+    fn storage_mut<T: Component>(&mut self) -> Option<&mut SparseSet<T>> {
+        self.storages.get_mut(&TypeId::of::<T>()).map(|storage| {
+            storage
+                .get_mut()
+                .as_any_mut()
+                .downcast_mut::<SparseSet<T>>()
+                .unwrap()
+        })
+    }
+
+    /// Get a pointer to the sparse set storing a component type, for
+    /// mutation through a shared world
+    ///
+    /// # Safety
+    /// No other borrow of this storage may be alive while the pointer is
+    /// dereferenced mutably.
+    unsafe fn storage_ptr<T: Component>(&self) -> Option<*mut SparseSet<T>> {
+        self.storages.get(&TypeId::of::<T>()).map(|storage| {
+            let storage = &mut *storage.as_ptr();
+            let set: *mut SparseSet<T> = storage.as_any_mut().downcast_mut().unwrap();
+            set
+        })
+    }
+
+    /// Get the archetype tables
+    pub(crate) fn tables(&self) -> &Tables {
+        &self.tables
+    }
+
+    /// Shared view of every component of a type, if any storage exists
+    pub(crate) fn view<T: Component>(&self) -> Option<ComponentView<'_, T>> {
+        match self.storage_type::<T>() {
+            StorageType::SparseSet => self.storage::<T>().map(ComponentView::Sparse),
+            StorageType::Table => self.tables.view::<T>().map(ComponentView::Table),
+        }
+    }
+
+    /// Mutable view of every component of a type, if any storage exists
+    ///
+    /// # Safety
+    /// No other borrow of `T` components may be alive for the view's
+    /// lifetime.
+    pub(crate) unsafe fn view_mut<T: Component>(&self) -> Option<ComponentViewMut<'_, T>> {
+        match self.storage_type::<T>() {
+            StorageType::SparseSet => self.storage_ptr::<T>().map(|set| {
+                let (sparse, entities, dense, ticks) = (*set).split_mut();
+                ComponentViewMut::Sparse {
+                    sparse,
+                    entities,
+                    dense,
+                    ticks,
+                }
+            }),
+            StorageType::Table => self.tables.view_mut::<T>().map(ComponentViewMut::Table),
+        }
+    }
+
+    /// Insert a resource (singleton data)
+    pub fn insert_resource<T: Resource>(&mut self, resource: T) {
+        let type_id = TypeId::of::<T>();
+        self.resources
+            .insert(type_id, DataCell::new(Box::new(resource)));
+    }
+
+    /// Get a resource reference
+    #[must_use]
+    pub fn get_resource<T: Resource>(&self) -> Option<&T> {
+        let type_id = TypeId::of::<T>();
+        self.resources
+            .get(&type_id)
+            .and_then(|r| r.get().downcast_ref::<T>())
+    }
+
+    /// Get a mutable resource reference
+    pub fn get_resource_mut<T: Resource>(&mut self) -> Option<&mut T> {
+        let type_id = TypeId::of::<T>();
+        self.resources
+            .get_mut(&type_id)
+            .and_then(|r| r.get_mut().downcast_mut::<T>())
+    }
+
+    /// Borrow resource `T` mutably alongside the rest of the world
+    ///
+    /// The resource is taken out of the world for the duration of `f`, so
+    /// components and other resources stay freely accessible. It is put back
+    /// afterwards, replacing any `T` inserted by `f`. Returns `None` if the
+    /// resource doesn't exist.
+    pub fn resource_scope<T: Resource, U>(
+        &mut self,
+        f: impl FnOnce(&mut World, &mut T) -> U,
+    ) -> Option<U> {
+        let type_id = TypeId::of::<T>();
+        let mut resource = self
+            .resources
+            .remove(&type_id)?
+            .into_inner()
+            .downcast::<T>()
+            .ok()?;
+        let result = f(self, &mut resource);
+        self.resources.insert(
+            type_id,
+            DataCell::new(resource as Box<dyn Any + Send + Sync>),
+        );
+        Some(result)
+    }
+
+    /// Get a pointer to a resource, for mutation through a shared world
+    ///
+    /// # Safety
+    /// No other borrow of this resource may be alive while the pointer is
+    /// dereferenced mutably.
+    pub(crate) unsafe fn resource_ptr<T: Resource>(&self) -> Option<*mut T> {
+        self.resources.get(&TypeId::of::<T>()).and_then(|r| {
+            let resource = &mut *r.as_ptr();
+            resource.downcast_mut::<T>().map(|r| r as *mut T)
+        })
+    }
+
+    /// Remove a resource
+    pub fn remove_resource<T: Resource>(&mut self) -> Option<T> {
+        let type_id = TypeId::of::<T>();
+        self.resources
+            .remove(&type_id)
+            .and_then(|r| r.into_inner().downcast::<T>().ok())
+            .map(|b| *b)
+    }
+
+    /// Check if a resource exists
+    #[must_use]
+    pub fn has_resource<T: Resource>(&self) -> bool {
+        let type_id = TypeId::of::<T>();
+        self.resources.contains_key(&type_id)
+    }
+}
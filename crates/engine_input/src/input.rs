@@ -29,6 +29,16 @@ impl Input {
         }
     }
 
+    /// Press a key without a window event (scripted input, replays)
+    pub fn press_key(&mut self, key: KeyCode) {
+        self.keyboard.on_key_pressed(key);
+    }
+
+    /// Release a key without a window event (scripted input, replays)
+    pub fn release_key(&mut self, key: KeyCode) {
+        self.keyboard.on_key_released(key);
+    }
+
     /// Update at the end of each frame
     pub fn end_frame(&mut self) {
         self.keyboard.end_frame();
@@ -0,0 +1,227 @@
+//! Rigid bodies and overlap resolution between entities
+
+use engine_ecs::{impl_reflect, FieldType, FieldValue, ReflectValue};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::AABB;
+
+/// How a body responds to overlaps with other bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BodyType {
+    /// Pushed out of the bodies it overlaps
+    #[default]
+    Dynamic,
+    /// Never moved by collisions (walls, props, crops)
+    Static,
+}
+
+/// Rigid body component
+///
+/// Colliders without a rigid body behave as static bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RigidBody {
+    #[serde(default)]
+    pub body_type: BodyType,
+    /// Mass; heavier bodies are pushed and knocked back less
+    #[serde(default = "default_mass")]
+    pub mass: f32,
+    /// How fast momentum fades, per second
+    #[serde(default)]
+    pub drag: f32,
+    /// Share of momentum kept when bouncing off a surface, in `0.0..=1.0`
+    #[serde(default)]
+    pub restitution: f32,
+}
+
+fn default_mass() -> f32 {
+    1.0
+}
+
+impl Default for RigidBody {
+    fn default() -> Self {
+        Self::dynamic()
+    }
+}
+
+/// Reflected by variant name
+impl ReflectValue for BodyType {
+    const TYPE: FieldType = FieldType::String;
+
+    fn to_field(&self) -> FieldValue {
+        FieldValue::String(format!("{:?}", self))
+    }
+
+    fn from_field(value: FieldValue) -> Option<Self> {
+        match value {
+            FieldValue::String(name) if name.eq_ignore_ascii_case("dynamic") => Some(Self::Dynamic),
+            FieldValue::String(name) if name.eq_ignore_ascii_case("static") => Some(Self::Static),
+            _ => None,
+        }
+    }
+}
+
+impl_reflect!(RigidBody {
+    body_type: BodyType,
+    mass: f32,
+    drag: f32,
+    restitution: f32,
+});
+
+impl RigidBody {
+    /// Create a dynamic body
+    #[must_use]
+    pub const fn dynamic() -> Self {
+        Self::new(BodyType::Dynamic)
+    }
+
+    /// Create a static body
+    #[must_use]
+    pub const fn fixed() -> Self {
+        Self::new(BodyType::Static)
+    }
+
+    /// Create a body of unit mass, without drag or bounce
+    #[must_use]
+    pub const fn new(body_type: BodyType) -> Self {
+        Self {
+            body_type,
+            mass: 1.0,
+            drag: 0.0,
+            restitution: 0.0,
+        }
+    }
+
+    /// Set the mass
+    #[must_use]
+    pub const fn with_mass(mut self, mass: f32) -> Self {
+        self.mass = mass;
+        self
+    }
+
+    /// Set the drag
+    #[must_use]
+    pub const fn with_drag(mut self, drag: f32) -> Self {
+        self.drag = drag;
+        self
+    }
+
+    /// Set the restitution
+    #[must_use]
+    pub const fn with_restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    /// Check if collisions move this body
+    #[must_use]
+    pub fn is_dynamic(&self) -> bool {
+        self.body_type == BodyType::Dynamic
+    }
+
+    /// Get how much an impulse changes this body's velocity
+    ///
+    /// Zero for static bodies and bodies without a positive mass.
+    #[must_use]
+    pub fn inverse_mass(&self) -> f32 {
+        if self.is_dynamic() && self.mass > 0.0 {
+            1.0 / self.mass
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Compute the offsets that separate two overlapping bodies
+///
+/// Returns the offsets to apply to `a` and `b`, or None if they don't
+/// overlap or neither can move. A dynamic body overlapping a static one
+/// takes the whole minimum translation; two dynamic bodies share it.
+#[must_use]
+pub fn separate(a: &AABB, a_type: BodyType, b: &AABB, b_type: BodyType) -> Option<(Vec2, Vec2)> {
+    separate_bodies(a, &RigidBody::new(a_type), b, &RigidBody::new(b_type))
+}
+
+/// Compute the offsets that separate two overlapping bodies, by mass
+///
+/// Like [`separate`], but two dynamic bodies share the minimum translation
+/// in inverse proportion to their masses: pushing a heavy barrel mostly
+/// moves the one pushing.
+#[must_use]
+pub fn separate_bodies(
+    a: &AABB,
+    a_body: &RigidBody,
+    b: &AABB,
+    b_body: &RigidBody,
+) -> Option<(Vec2, Vec2)> {
+    let collision = a.get_collision(b)?;
+    let (a_inverse, b_inverse) = (a_body.inverse_mass(), b_body.inverse_mass());
+    let total = a_inverse + b_inverse;
+    if total == 0.0 {
+        return None;
+    }
+    Some((
+        collision.mtv * (a_inverse / total),
+        -collision.mtv * (b_inverse / total),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dynamic_pushed_out_of_static() {
+        let player = AABB::new(0.0, 0.0, 10.0, 10.0);
+        let rock = AABB::new(8.0, 0.0, 10.0, 10.0);
+
+        let (push_player, push_rock) =
+            separate(&player, BodyType::Dynamic, &rock, BodyType::Static).unwrap();
+        assert_eq!(push_player, Vec2::new(-2.0, 0.0));
+        assert_eq!(push_rock, Vec2::ZERO);
+        assert!(!player.translated(push_player).intersects(&rock));
+
+        let (push_rock, push_player) =
+            separate(&rock, BodyType::Static, &player, BodyType::Dynamic).unwrap();
+        assert_eq!(push_rock, Vec2::ZERO);
+        assert_eq!(push_player, Vec2::new(-2.0, 0.0));
+    }
+
+    #[test]
+    fn test_dynamic_bodies_share_separation() {
+        let a = AABB::new(0.0, 0.0, 10.0, 10.0);
+        let b = AABB::new(0.0, 6.0, 10.0, 10.0);
+
+        let (push_a, push_b) = separate(&a, BodyType::Dynamic, &b, BodyType::Dynamic).unwrap();
+        assert_eq!(push_a, Vec2::new(0.0, -2.0));
+        assert_eq!(push_b, Vec2::new(0.0, 2.0));
+        assert!(!a.translated(push_a).intersects(&b.translated(push_b)));
+    }
+
+    #[test]
+    fn test_static_and_separate_bodies_untouched() {
+        let a = AABB::new(0.0, 0.0, 10.0, 10.0);
+        let b = AABB::new(5.0, 5.0, 10.0, 10.0);
+        let far = AABB::new(50.0, 0.0, 10.0, 10.0);
+
+        assert!(separate(&a, BodyType::Static, &b, BodyType::Static).is_none());
+        assert!(separate(&a, BodyType::Dynamic, &far, BodyType::Static).is_none());
+    }
+
+    #[test]
+    fn test_heavier_bodies_pushed_less() {
+        let player = AABB::new(0.0, 0.0, 10.0, 10.0);
+        let barrel = AABB::new(5.0, 0.0, 10.0, 10.0);
+        let heavy = RigidBody::dynamic().with_mass(4.0);
+
+        let (push_player, push_barrel) =
+            separate_bodies(&player, &RigidBody::dynamic(), &barrel, &heavy).unwrap();
+        assert_eq!(push_player, Vec2::new(-4.0, 0.0));
+        assert_eq!(push_barrel, Vec2::new(1.0, 0.0));
+
+        // Bodies without a positive mass are immovable, like static ones
+        assert_eq!(heavy.inverse_mass(), 0.25);
+        assert_eq!(RigidBody::dynamic().with_mass(0.0).inverse_mass(), 0.0);
+        assert_eq!(RigidBody::fixed().inverse_mass(), 0.0);
+    }
+}
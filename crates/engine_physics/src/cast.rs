@@ -0,0 +1,581 @@
+//! Raycasts and shape casts
+//!
+//! Rays march cell by cell (DDA) through a [`TileGrid`] or the
+//! [`SpatialGrid`], so they stop at the first hit without testing anything
+//! beyond it. Shape casts move an [`AABB`] along a straight line and report
+//! its first contact, using the same sweep as movement.
+
+use engine_ecs::Entity;
+use glam::Vec2;
+
+use crate::{LayerMask, SpatialGrid, AABB};
+
+/// Tile collision grid that casts can march through
+pub trait TileGrid {
+    /// Size of a tile in pixels
+    fn tile_size(&self) -> Vec2;
+
+    /// Size of the grid in tiles
+    ///
+    /// Tiles outside it may still collide, but rays don't march further
+    /// than one tile beyond it.
+    fn grid_size(&self) -> (i32, i32);
+
+    /// Collision layers of the tile at `(x, y)`, [`LayerMask::NONE`] if walkable
+    fn tile_layers(&self, x: i32, y: i32) -> LayerMask;
+
+    /// Collision boxes of the tile at `(x, y)`, in world space
+    ///
+    /// Defaults to the whole tile; boxes must stay within the tile.
+    fn tile_boxes(&self, x: i32, y: i32) -> Vec<AABB> {
+        let size = self.tile_size();
+        vec![AABB::new(
+            x as f32 * size.x,
+            y as f32 * size.y,
+            size.x,
+            size.y,
+        )]
+    }
+}
+
+/// Half-line or segment cast from a point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec2,
+    /// Unit direction, zero for a degenerate ray
+    pub direction: Vec2,
+    /// Distance the ray stops at
+    pub max_distance: f32,
+}
+
+impl Ray {
+    /// Create a ray; `direction` doesn't need to be normalized
+    #[must_use]
+    pub fn new(origin: Vec2, direction: Vec2, max_distance: f32) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize_or_zero(),
+            max_distance,
+        }
+    }
+
+    /// Create a ray covering the segment from `start` to `end`
+    #[must_use]
+    pub fn segment(start: Vec2, end: Vec2) -> Self {
+        Self::new(start, end - start, start.distance(end))
+    }
+
+    /// Get the point at a distance along the ray
+    #[must_use]
+    pub fn at(&self, distance: f32) -> Vec2 {
+        self.origin + self.direction * distance
+    }
+}
+
+/// What a cast hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitTarget {
+    /// Tile coordinates
+    Tile {
+        x: i32,
+        y: i32,
+    },
+    Entity(Entity),
+}
+
+/// First hit of a cast
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CastHit {
+    pub target: HitTarget,
+    /// Distance travelled before the hit
+    pub distance: f32,
+    /// Hit point for rays; center of the cast box at contact for shape casts
+    pub point: Vec2,
+    /// Surface normal at the hit, zero if the cast started inside the target
+    pub normal: Vec2,
+}
+
+impl CastHit {
+    /// Keep the nearest of two optional hits
+    #[must_use]
+    pub fn nearest(a: Option<Self>, b: Option<Self>) -> Option<Self> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(if b.distance < a.distance { b } else { a }),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+impl AABB {
+    /// Find where a ray enters this AABB, as a distance and surface normal
+    ///
+    /// A ray starting inside hits at distance 0 with a zero normal.
+    #[must_use]
+    pub fn raycast(&self, ray: &Ray) -> Option<(f32, Vec2)> {
+        self.ray_span(ray).map(|(entry, _, normal)| (entry, normal))
+    }
+
+    /// Find the distances a ray enters and leaves this AABB at, and the
+    /// surface normal it enters through
+    fn ray_span(&self, ray: &Ray) -> Option<(f32, f32, Vec2)> {
+        let mut entry = 0.0_f32;
+        let mut exit = ray.max_distance;
+        let mut normal = Vec2::ZERO;
+
+        for axis in 0..2 {
+            let (origin, dir) = (ray.origin[axis], ray.direction[axis]);
+            let (min, max) = (self.min[axis], self.max[axis]);
+            if dir == 0.0 {
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+
+            let (near, far) = if dir > 0.0 { (min, max) } else { (max, min) };
+            let t_near = (near - origin) / dir;
+            let t_far = (far - origin) / dir;
+            if t_near > entry {
+                entry = t_near;
+                normal = Vec2::ZERO;
+                normal[axis] = -dir.signum();
+            }
+            exit = exit.min(t_far);
+        }
+
+        (entry <= exit).then_some((entry, exit, normal))
+    }
+}
+
+/// Walk the grid cells a ray crosses, in order, until `visit` returns true
+///
+/// `visit` gets the cell and the distance the ray entered it at. Only the
+/// part of the ray within the cells from `min` to `max` (inclusive) is
+/// walked, so an unbounded ray still stops past the last occupied cell.
+fn walk_cells(
+    ray: &Ray,
+    cell_size: Vec2,
+    (min, max): ((i32, i32), (i32, i32)),
+    mut visit: impl FnMut((i32, i32), f32) -> bool,
+) {
+    let bounds = AABB {
+        min: Vec2::new(min.0 as f32, min.1 as f32) * cell_size,
+        max: Vec2::new((max.0 + 1) as f32, (max.1 + 1) as f32) * cell_size,
+    };
+    let Some((entry, exit, _)) = bounds.ray_span(ray) else {
+        return;
+    };
+
+    // Start where the ray enters the bounds rather than at its origin
+    let origin = ray.at(entry);
+    let start = origin / cell_size;
+    let mut cell = (start.x.floor() as i32, start.y.floor() as i32);
+    let step = (
+        ray.direction.x.signum() as i32,
+        ray.direction.y.signum() as i32,
+    );
+
+    // Distance to the next cell boundary on each axis, and between boundaries
+    let boundary = |pos: f32, dir: f32, cell: i32, size: f32| {
+        if dir == 0.0 {
+            return (f32::INFINITY, f32::INFINITY);
+        }
+        let next = if dir > 0.0 { cell + 1 } else { cell } as f32 * size;
+        (entry + (next - pos) / dir, size / dir.abs())
+    };
+    let (mut next_x, delta_x) = boundary(origin.x, ray.direction.x, cell.0, cell_size.x);
+    let (mut next_y, delta_y) = boundary(origin.y, ray.direction.y, cell.1, cell_size.y);
+
+    if visit(cell, entry) {
+        return;
+    }
+    loop {
+        let distance = if next_x < next_y {
+            cell.0 += step.0;
+            next_x += delta_x;
+            next_x - delta_x
+        } else {
+            cell.1 += step.1;
+            next_y += delta_y;
+            next_y - delta_y
+        };
+        if !distance.is_finite() || distance > exit {
+            return;
+        }
+        if visit(cell, distance) {
+            return;
+        }
+    }
+}
+
+/// Cast a ray through a tile grid, hitting the first tile on any `mask` layer
+#[must_use]
+pub fn raycast_tiles(tiles: &impl TileGrid, ray: &Ray, mask: LayerMask) -> Option<CastHit> {
+    // Outside the grid, only its one-tile border is marched through
+    let (width, height) = tiles.grid_size();
+    let bounds = ((-1, -1), (width, height));
+    let mut hit = None;
+    walk_cells(ray, tiles.tile_size(), bounds, |(x, y), _| {
+        if !tiles.tile_layers(x, y).intersects(mask) {
+            return false;
+        }
+        // Boxes stay within their tile, so the first tile hit is the nearest
+        hit = tiles
+            .tile_boxes(x, y)
+            .iter()
+            .filter_map(|aabb| aabb.raycast(ray))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(distance, normal)| CastHit {
+                target: HitTarget::Tile { x, y },
+                distance,
+                point: ray.at(distance),
+                normal,
+            });
+        hit.is_some()
+    });
+    hit
+}
+
+/// Cast an AABB by `delta` through a tile grid, hitting the first tile on
+/// any `mask` layer
+///
+/// Tiles the box already overlaps are ignored, as when sliding.
+#[must_use]
+pub fn shape_cast_tiles(
+    tiles: &impl TileGrid,
+    aabb: &AABB,
+    delta: Vec2,
+    mask: LayerMask,
+) -> Option<CastHit> {
+    let size = tiles.tile_size();
+    let bounds = aabb.swept(delta);
+    let (min_x, min_y) = (
+        (bounds.min.x / size.x).floor() as i32,
+        (bounds.min.y / size.y).floor() as i32,
+    );
+    let (max_x, max_y) = (
+        (bounds.max.x / size.x).ceil() as i32,
+        (bounds.max.y / size.y).ceil() as i32,
+    );
+
+    let mut nearest = None;
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            if !tiles.tile_layers(x, y).intersects(mask) {
+                continue;
+            }
+            for tile in tiles.tile_boxes(x, y) {
+                let hit = aabb
+                    .sweep(delta, &tile)
+                    .map(|hit| shape_hit(aabb, delta, hit, HitTarget::Tile { x, y }));
+                nearest = CastHit::nearest(nearest, hit);
+            }
+        }
+    }
+    nearest
+}
+
+fn shape_hit(aabb: &AABB, delta: Vec2, hit: crate::SweepHit, target: HitTarget) -> CastHit {
+    CastHit {
+        target,
+        distance: delta.length() * hit.time,
+        point: aabb.center() + delta * hit.time,
+        normal: hit.normal,
+    }
+}
+
+impl SpatialGrid {
+    /// Cast a ray through the grid, hitting the nearest entity
+    ///
+    /// The grid doesn't store shapes: `shape` gives each candidate's AABB,
+    /// or None to ignore it (e.g. the caster itself, or filtered layers).
+    pub fn raycast(
+        &self,
+        ray: &Ray,
+        mut shape: impl FnMut(Entity) -> Option<AABB>,
+    ) -> Option<CastHit> {
+        let cell_size = Vec2::splat(self.cell_size);
+        let bounds = self.occupied_bounds()?;
+        let mut nearest: Option<CastHit> = None;
+        let mut tested = std::collections::HashSet::new();
+
+        walk_cells(ray, cell_size, bounds, |cell, distance| {
+            // Nothing in this cell or later can be nearer than a hit already found
+            if nearest.is_some_and(|hit| hit.distance <= distance) {
+                return true;
+            }
+            for &entity in self.cells.get(&cell).into_iter().flatten() {
+                if !tested.insert(entity) {
+                    continue;
+                }
+                let hit =
+                    shape(entity)
+                        .and_then(|aabb| aabb.raycast(ray))
+                        .map(|(distance, normal)| CastHit {
+                            target: HitTarget::Entity(entity),
+                            distance,
+                            point: ray.at(distance),
+                            normal,
+                        });
+                nearest = CastHit::nearest(nearest, hit);
+            }
+            false
+        });
+        nearest
+    }
+
+    /// Get the lowest and highest occupied cells, None if the grid is empty
+    fn occupied_bounds(&self) -> Option<((i32, i32), (i32, i32))> {
+        self.cells
+            .iter()
+            .filter(|(_, entities)| !entities.is_empty())
+            .map(|(&cell, _)| (cell, cell))
+            .reduce(|(min, max), (cell, _)| {
+                (
+                    (min.0.min(cell.0), min.1.min(cell.1)),
+                    (max.0.max(cell.0), max.1.max(cell.1)),
+                )
+            })
+    }
+
+    /// Cast an AABB by `delta` through the grid, hitting the nearest entity
+    ///
+    /// `shape` works as in [`SpatialGrid::raycast`]. Entities the box
+    /// already overlaps are ignored.
+    pub fn shape_cast(
+        &self,
+        aabb: &AABB,
+        delta: Vec2,
+        mut shape: impl FnMut(Entity) -> Option<AABB>,
+    ) -> Option<CastHit> {
+        self.query(&aabb.swept(delta))
+            .into_iter()
+            .filter_map(|entity| {
+                let hit = aabb.sweep(delta, &shape(entity)?)?;
+                Some(shape_hit(aabb, delta, hit, HitTarget::Entity(entity)))
+            })
+            .fold(None, |nearest, hit| CastHit::nearest(nearest, Some(hit)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rows of `#` (solid), `h` (solid bottom half) and `~` (water) tiles,
+    /// 16px each
+    struct Grid(Vec<&'static str>);
+
+    impl Grid {
+        fn tile(&self, x: i32, y: i32) -> Option<u8> {
+            let row = self.0.get(usize::try_from(y).ok()?)?;
+            row.as_bytes().get(usize::try_from(x).ok()?).copied()
+        }
+    }
+
+    impl TileGrid for Grid {
+        fn tile_size(&self) -> Vec2 {
+            Vec2::splat(16.0)
+        }
+
+        fn grid_size(&self) -> (i32, i32) {
+            (self.0[0].len() as i32, self.0.len() as i32)
+        }
+
+        fn tile_layers(&self, x: i32, y: i32) -> LayerMask {
+            match self.tile(x, y) {
+                Some(b'#' | b'h') | None => LayerMask::SOLID,
+                Some(b'~') => LayerMask::WATER,
+                _ => LayerMask::NONE,
+            }
+        }
+
+        fn tile_boxes(&self, x: i32, y: i32) -> Vec<AABB> {
+            let (left, top) = (x as f32 * 16.0, y as f32 * 16.0);
+            match self.tile(x, y) {
+                Some(b'h') => vec![AABB::new(left, top + 8.0, 16.0, 8.0)],
+                _ => vec![AABB::new(left, top, 16.0, 16.0)],
+            }
+        }
+    }
+
+    fn grid() -> Grid {
+        Grid(vec!["......", "...~.#", "......", "..#...", "h....."])
+    }
+
+    #[test]
+    fn test_raycast_tiles() {
+        let ray = Ray::new(Vec2::new(8.0, 24.0), Vec2::X, 200.0);
+
+        let hit = raycast_tiles(&grid(), &ray, LayerMask::SOLID).unwrap();
+        assert_eq!(hit.target, HitTarget::Tile { x: 5, y: 1 });
+        assert_eq!(hit.distance, 72.0);
+        assert_eq!(hit.point, Vec2::new(80.0, 24.0));
+        assert_eq!(hit.normal, Vec2::new(-1.0, 0.0));
+
+        // Water stops a line of sight masked for it
+        let hit = raycast_tiles(&grid(), &ray, LayerMask::SOLID | LayerMask::WATER).unwrap();
+        assert_eq!(hit.target, HitTarget::Tile { x: 3, y: 1 });
+
+        // Out of reach
+        let short = Ray::new(ray.origin, Vec2::X, 16.0);
+        assert!(raycast_tiles(&grid(), &short, LayerMask::SOLID).is_none());
+    }
+
+    #[test]
+    fn test_raycast_diagonal_and_upwards() {
+        // Diagonal from above the pillar at (2, 3), entering through its top
+        let hit = raycast_tiles(
+            &grid(),
+            &Ray::segment(Vec2::new(4.0, 4.0), Vec2::new(44.0, 64.0)),
+            LayerMask::SOLID,
+        )
+        .unwrap();
+        assert_eq!(hit.target, HitTarget::Tile { x: 2, y: 3 });
+        assert_eq!(hit.normal, Vec2::new(0.0, -1.0));
+        assert!((hit.point.y - 48.0).abs() < 1e-4);
+
+        // Upwards leaves the map, which counts as solid
+        let up = Ray::new(Vec2::new(8.0, 40.0), Vec2::NEG_Y, 100.0);
+        let hit = raycast_tiles(&grid(), &up, LayerMask::SOLID).unwrap();
+        assert_eq!(hit.target, HitTarget::Tile { x: 0, y: -1 });
+        assert_eq!(hit.distance, 40.0);
+        assert_eq!(hit.normal, Vec2::new(0.0, 1.0));
+
+        // Starting inside a solid tile
+        let inside = Ray::new(Vec2::new(40.0, 56.0), Vec2::X, 10.0);
+        let hit = raycast_tiles(&grid(), &inside, LayerMask::SOLID).unwrap();
+        assert_eq!((hit.distance, hit.normal), (0.0, Vec2::ZERO));
+    }
+
+    #[test]
+    fn test_casts_honor_tile_boxes() {
+        // The half-wall at (0, 4) only blocks its bottom half
+        let down = Ray::new(Vec2::new(8.0, 8.0), Vec2::Y, 100.0);
+        let hit = raycast_tiles(&grid(), &down, LayerMask::SOLID).unwrap();
+        assert_eq!(hit.target, HitTarget::Tile { x: 0, y: 4 });
+        assert_eq!(hit.distance, 64.0);
+        assert_eq!(hit.normal, Vec2::new(0.0, -1.0));
+
+        let over = Ray::new(Vec2::new(40.0, 68.0), Vec2::NEG_X, 100.0);
+        let hit = raycast_tiles(&grid(), &over, LayerMask::SOLID).unwrap();
+        assert_eq!(hit.target, HitTarget::Tile { x: -1, y: 4 });
+        assert_eq!(hit.distance, 40.0);
+
+        let top_half = AABB::new(24.0, 66.0, 8.0, 4.0);
+        let hit = shape_cast_tiles(&grid(), &top_half, Vec2::new(-40.0, 0.0), LayerMask::SOLID);
+        assert_eq!(hit.unwrap().target, HitTarget::Tile { x: -1, y: 4 });
+        let bottom_half = top_half.translated(Vec2::new(0.0, 8.0));
+        let hit = shape_cast_tiles(
+            &grid(),
+            &bottom_half,
+            Vec2::new(-40.0, 0.0),
+            LayerMask::SOLID,
+        );
+        assert_eq!(hit.unwrap().target, HitTarget::Tile { x: 0, y: 4 });
+    }
+
+    #[test]
+    fn test_shape_cast_tiles() {
+        // A 12x8 box moving right in row 1 stops at the wall
+        let mover = AABB::from_center(Vec2::new(8.0, 24.0), Vec2::new(6.0, 4.0));
+        let hit =
+            shape_cast_tiles(&grid(), &mover, Vec2::new(100.0, 0.0), LayerMask::SOLID).unwrap();
+        assert_eq!(hit.target, HitTarget::Tile { x: 5, y: 1 });
+        assert!((hit.distance - 66.0).abs() < 1e-4);
+        assert!((hit.point - Vec2::new(74.0, 24.0)).length() < 1e-4);
+        assert_eq!(hit.normal, Vec2::new(-1.0, 0.0));
+
+        // A ray down the same row would miss the pillar a box clips
+        let low = AABB::from_center(Vec2::new(8.0, 44.0), Vec2::new(6.0, 6.0));
+        let hit = shape_cast_tiles(&grid(), &low, Vec2::new(40.0, 0.0), LayerMask::SOLID).unwrap();
+        assert_eq!(hit.target, HitTarget::Tile { x: 2, y: 3 });
+    }
+
+    fn entity(index: u32) -> Entity {
+        Entity::new(index, 0)
+    }
+
+    #[test]
+    fn test_raycast_entities() {
+        let boxes = [
+            (entity(1), AABB::new(100.0, 0.0, 10.0, 10.0)),
+            (entity(2), AABB::new(300.0, 0.0, 10.0, 10.0)),
+            (entity(3), AABB::new(40.0, 40.0, 10.0, 10.0)),
+            (entity(4), AABB::new(0.0, 0.0, 10.0, 10.0)),
+        ];
+        let mut grid = SpatialGrid::new(64.0);
+        for (entity, aabb) in &boxes {
+            grid.insert(*entity, aabb);
+        }
+        let shape = |caster: Entity| {
+            move |e: Entity| {
+                (e != caster)
+                    .then(|| boxes.iter().find(|(b, _)| *b == e).map(|(_, aabb)| *aabb))
+                    .flatten()
+            }
+        };
+
+        // The caster (entity 4) is skipped; entity 1 hides entity 2
+        let ray = Ray::new(Vec2::new(5.0, 5.0), Vec2::X, 1000.0);
+        let hit = grid.raycast(&ray, shape(entity(4))).unwrap();
+        assert_eq!(hit.target, HitTarget::Entity(entity(1)));
+        assert_eq!(hit.distance, 95.0);
+        assert_eq!(hit.normal, Vec2::new(-1.0, 0.0));
+
+        let miss = Ray::new(Vec2::new(5.0, 5.0), Vec2::Y, 1000.0);
+        assert!(grid.raycast(&miss, shape(entity(4))).is_none());
+
+        let diagonal = Ray::segment(Vec2::new(5.0, 5.0), Vec2::new(60.0, 60.0));
+        let hit = grid.raycast(&diagonal, shape(entity(4))).unwrap();
+        assert_eq!(hit.target, HitTarget::Entity(entity(3)));
+        assert!((hit.point - Vec2::new(40.0, 40.0)).length() < 1e-4);
+
+        let cast = AABB::new(0.0, 35.0, 10.0, 10.0);
+        let hit = grid
+            .shape_cast(&cast, Vec2::new(100.0, 0.0), shape(entity(4)))
+            .unwrap();
+        assert_eq!(hit.target, HitTarget::Entity(entity(3)));
+        assert!((hit.distance - 30.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_unbounded_rays_stop_past_the_grid() {
+        // Nothing but water masked out, so even the map border can't stop it
+        let ray = Ray::new(Vec2::new(8.0, 24.0), Vec2::new(1.0, 0.3), f32::INFINITY);
+        assert!(raycast_tiles(&grid(), &ray, LayerMask::WATER).is_none());
+        let far = Ray::new(Vec2::new(-1e9, 24.0), Vec2::X, f32::MAX);
+        let hit = raycast_tiles(&grid(), &far, LayerMask::WATER).unwrap();
+        assert_eq!(hit.target, HitTarget::Tile { x: 3, y: 1 });
+
+        // Into empty space, and through an empty grid
+        let mut grid = SpatialGrid::new(64.0);
+        let away = Ray::new(Vec2::new(5.0, 5.0), Vec2::NEG_X, f32::INFINITY);
+        assert!(grid.raycast(&away, |_| None).is_none());
+        let aabb = AABB::new(0.0, 0.0, 10.0, 10.0);
+        grid.insert(entity(1), &aabb);
+        let away = Ray::new(Vec2::new(20.0, 5.0), Vec2::new(1.0, 0.5), f32::INFINITY);
+        assert!(grid.raycast(&away, |_| Some(aabb)).is_none());
+        let back = Ray::new(Vec2::new(1e9, 5.0), Vec2::NEG_X, f32::INFINITY);
+        let hit = grid.raycast(&back, |_| Some(aabb)).unwrap();
+        assert_eq!(hit.target, HitTarget::Entity(entity(1)));
+    }
+
+    #[test]
+    fn test_aabb_raycast() {
+        let aabb = AABB::new(10.0, 10.0, 10.0, 10.0);
+
+        let from_below = Ray::new(Vec2::new(15.0, 40.0), Vec2::NEG_Y, 100.0);
+        assert_eq!(aabb.raycast(&from_below), Some((20.0, Vec2::new(0.0, 1.0))));
+        assert!(aabb
+            .raycast(&Ray::new(Vec2::new(15.0, 40.0), Vec2::NEG_Y, 10.0))
+            .is_none());
+        assert!(aabb
+            .raycast(&Ray::new(Vec2::new(25.0, 40.0), Vec2::NEG_Y, 100.0))
+            .is_none());
+        assert_eq!(
+            aabb.raycast(&Ray::new(Vec2::new(12.0, 12.0), Vec2::X, 1.0)),
+            Some((0.0, Vec2::ZERO))
+        );
+    }
+}
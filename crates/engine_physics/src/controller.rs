@@ -0,0 +1,271 @@
+//! Kinematic character controller
+//!
+//! [`slide`](crate::slide) stops a box dead when it brushes the corner of a
+//! wall it is walking past. The controller slides the same way, but when
+//! a movement is blocked by no more than `corner_correction` pixels of a
+//! corner, it nudges the box sideways around it and keeps going. It also
+//! remembers the surfaces it touched.
+
+use engine_ecs::impl_reflect;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::sweep::first_hit;
+use crate::AABB;
+
+/// Default corner correction, in pixels
+pub const DEFAULT_CORNER_CORRECTION: f32 = 4.0;
+
+/// Blockers hit within this fraction of the earliest hit count as hit at once
+const SAME_HIT_TIME: f32 = 1e-4;
+
+/// A surface the controller ran into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Touch {
+    /// Box that was touched
+    pub solid: AABB,
+    /// Its surface normal, pointing at the mover
+    pub normal: Vec2,
+}
+
+/// Character controller component
+///
+/// Entities with one move with [`CharacterController::move_and_slide`]
+/// instead of a plain slide.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CharacterController {
+    /// Furthest a blocked movement is nudged sideways around a corner
+    #[serde(default = "default_corner_correction")]
+    pub corner_correction: f32,
+    /// Surfaces touched by the last movement
+    #[serde(skip)]
+    touches: Vec<Touch>,
+}
+
+fn default_corner_correction() -> f32 {
+    DEFAULT_CORNER_CORRECTION
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self::new(DEFAULT_CORNER_CORRECTION)
+    }
+}
+
+impl_reflect!(CharacterController { corner_correction: f32 });
+
+impl CharacterController {
+    /// Create a controller nudging around corners up to `corner_correction`
+    /// pixels
+    #[must_use]
+    pub fn new(corner_correction: f32) -> Self {
+        Self {
+            corner_correction,
+            touches: Vec::new(),
+        }
+    }
+
+    /// Get the surfaces touched by the last movement
+    #[must_use]
+    pub fn touches(&self) -> &[Touch] {
+        &self.touches
+    }
+
+    /// Check if the last movement was blocked going towards `direction`
+    #[must_use]
+    pub fn is_blocked(&self, direction: Vec2) -> bool {
+        self.touches
+            .iter()
+            .any(|touch| touch.normal.dot(direction) < 0.0)
+    }
+
+    /// Move an AABB by `delta` without entering any of `solids`
+    ///
+    /// Axes are resolved like [`slide`](crate::slide), horizontal first.
+    /// Returns the movement done, including corner nudges; solids the box
+    /// already overlaps are ignored.
+    pub fn move_and_slide(&mut self, aabb: &AABB, delta: Vec2, solids: &[AABB]) -> Vec2 {
+        self.touches.clear();
+        let movement_x = self.move_axis(aabb, Vec2::new(delta.x, 0.0), delta.y, solids);
+        let aabb = aabb.translated(movement_x);
+        let movement_y = self.move_axis(&aabb, Vec2::new(0.0, delta.y), delta.x, solids);
+        movement_x + movement_y
+    }
+
+    /// Move along one axis, nudging around a corner if blocked
+    ///
+    /// `side` is the movement along the other axis: the nudge never goes
+    /// against it.
+    fn move_axis(&mut self, aabb: &AABB, step: Vec2, side: f32, solids: &[AABB]) -> Vec2 {
+        let Some(hit) = first_hit(aabb, step, solids) else {
+            return step;
+        };
+        let movement = step * hit.time;
+        let blockers: Vec<AABB> = solids
+            .iter()
+            .filter(|solid| {
+                aabb.sweep(step, solid)
+                    .is_some_and(|other| other.time <= hit.time + SAME_HIT_TIME)
+            })
+            .copied()
+            .collect();
+
+        let contact = aabb.translated(movement);
+        let rest = step - movement;
+        if let Some(nudge) = self.corner_nudge(&contact, rest, side, &blockers, solids) {
+            let nudged = contact.translated(nudge);
+            let rest = match first_hit(&nudged, rest, solids) {
+                Some(next) => {
+                    self.touch(&nudged, rest, solids, next.time);
+                    rest * next.time
+                }
+                None => rest,
+            };
+            return movement + nudge + rest;
+        }
+
+        self.touch(aabb, step, solids, hit.time);
+        movement
+    }
+
+    /// Find the smallest sideways nudge that gets `contact` past all of
+    /// `blockers`, if it is within the corner correction and free
+    fn corner_nudge(
+        &self,
+        contact: &AABB,
+        rest: Vec2,
+        side: f32,
+        blockers: &[AABB],
+        solids: &[AABB],
+    ) -> Option<Vec2> {
+        if self.corner_correction <= 0.0 || rest == Vec2::ZERO {
+            return None;
+        }
+        // Sideways is the other axis
+        let across = if rest.x == 0.0 { Vec2::X } else { Vec2::Y };
+        let (low, high) = (contact.min.dot(across), contact.max.dot(across));
+        let forward = blockers
+            .iter()
+            .map(|blocker| blocker.max.dot(across) - low)
+            .fold(0.0, f32::max);
+        let backward = blockers
+            .iter()
+            .map(|blocker| high - blocker.min.dot(across))
+            .fold(0.0, f32::max);
+
+        let mut candidates = [(forward, 1.0), (backward, -1.0)];
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        candidates
+            .into_iter()
+            .filter(|&(distance, sign)| distance <= self.corner_correction && side * sign >= 0.0)
+            .map(|(distance, sign)| across * distance * sign)
+            .find(|&nudge| {
+                // The nudge itself must be free, and must let the box move on
+                first_hit(contact, nudge, solids).is_none()
+                    && first_hit(&contact.translated(nudge), rest, solids)
+                        .is_none_or(|hit| hit.time > 0.0)
+            })
+    }
+
+    /// Record the solids a movement hits at `time`
+    fn touch(&mut self, aabb: &AABB, step: Vec2, solids: &[AABB], time: f32) {
+        for solid in solids {
+            if let Some(hit) = aabb.sweep(step, solid) {
+                if hit.time <= time + SAME_HIT_TIME {
+                    self.touches.push(Touch {
+                        solid: *solid,
+                        normal: hit.normal,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slide;
+
+    const TILE: f32 = 16.0;
+
+    fn tile(x: i32, y: i32) -> AABB {
+        AABB::new(x as f32 * TILE, y as f32 * TILE, TILE, TILE)
+    }
+
+    fn assert_near(actual: Vec2, expected: Vec2) {
+        assert!(
+            (actual - expected).length() < 1e-4,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_slides_like_slide_without_corners() {
+        let solids: Vec<AABB> = (0..4).map(|y| tile(2, y)).collect();
+        let mover = AABB::new(16.0, 4.0, 12.0, 8.0);
+        let mut controller = CharacterController::default();
+
+        let movement = controller.move_and_slide(&mover, Vec2::new(10.0, 20.0), &solids);
+        assert_near(
+            movement,
+            slide(&mover, Vec2::new(10.0, 20.0), &solids).movement,
+        );
+        assert!(controller.is_blocked(Vec2::X));
+        assert!(!controller.is_blocked(Vec2::Y));
+        assert_eq!(controller.touches().len(), 1);
+        assert_eq!(controller.touches()[0].normal, Vec2::new(-1.0, 0.0));
+    }
+
+    #[test]
+    fn test_corner_correction_nudges_past_corners() {
+        // Walking right with the bottom 3px of the box against a block
+        let block = tile(2, 1);
+        let mover = AABB::new(18.0, 5.0, 12.0, 14.0);
+        let delta = Vec2::new(6.0, 0.0);
+
+        assert_eq!(slide(&mover, delta, &[block]).movement.x, 2.0);
+        let mut controller = CharacterController::new(4.0);
+        let movement = controller.move_and_slide(&mover, delta, &[block]);
+        assert_near(movement, Vec2::new(6.0, -3.0));
+        assert!(!mover.translated(movement).intersects(&block));
+        assert!(controller.touches().is_empty());
+
+        // Stopped when the overlap is deeper than the correction
+        let mut controller = CharacterController::new(2.0);
+        let movement = controller.move_and_slide(&mover, delta, &[block]);
+        assert_near(movement, Vec2::new(2.0, 0.0));
+        assert!(controller.is_blocked(Vec2::X));
+    }
+
+    #[test]
+    fn test_no_nudge_against_sideways_movement_or_into_walls() {
+        let block = tile(2, 1);
+        let mover = AABB::new(18.0, 5.0, 12.0, 14.0);
+        let mut controller = CharacterController::default();
+
+        // Moving down-right: nudging up would undo the input
+        let movement = controller.move_and_slide(&mover, Vec2::new(6.0, 1.0), &[block]);
+        assert_near(movement, Vec2::new(2.0, 1.0));
+
+        // A ceiling above leaves no room to nudge into
+        let ceiling = AABB::new(0.0, -10.0, 64.0, 13.0);
+        let movement = controller.move_and_slide(&mover, Vec2::new(6.0, 0.0), &[block, ceiling]);
+        assert_near(movement, Vec2::new(2.0, 0.0));
+        assert!(controller.is_blocked(Vec2::X));
+    }
+
+    #[test]
+    fn test_nudges_into_doorways() {
+        // A doorway exactly as tall as the box, entered 1px too low
+        let solids = [tile(2, 0), tile(2, 2)];
+        let mover = AABB::new(20.0, 17.0, 6.0, 16.0);
+        let mut controller = CharacterController::default();
+
+        let movement = controller.move_and_slide(&mover, Vec2::new(8.0, 0.0), &solids);
+        assert_near(movement, Vec2::new(8.0, -1.0));
+        assert!(controller.touches().is_empty());
+    }
+}
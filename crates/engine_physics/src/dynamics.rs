@@ -0,0 +1,196 @@
+//! Impulses, drag and bounces for dynamic bodies
+//!
+//! Bodies move by their own velocity (walking, AI) plus a [`Momentum`] that
+//! impulses such as knockback add to. Momentum fades with the body's drag,
+//! bounces off surfaces by its restitution and is exchanged between bodies
+//! that collide. It is meant to be integrated on the fixed timestep, with
+//! the movement it causes swept like any other so it still stops at walls.
+
+use engine_ecs::impl_reflect;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::RigidBody;
+
+/// Momentum slower than this, in pixels per second, comes to rest
+pub const REST_SPEED: f32 = 1.0;
+
+/// Velocity from impulses, on top of a body's own movement (component)
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Momentum {
+    /// Current velocity in pixels per second
+    #[serde(default)]
+    pub velocity: Vec2,
+    /// Impulses applied since the last integration
+    #[serde(default)]
+    impulse: Vec2,
+}
+
+impl_reflect!(Momentum { velocity: Vec2 });
+
+impl Momentum {
+    /// Create momentum moving at `velocity`
+    #[must_use]
+    pub fn new(velocity: Vec2) -> Self {
+        Self {
+            velocity,
+            impulse: Vec2::ZERO,
+        }
+    }
+
+    /// Add an impulse, applied at the next integration
+    pub fn apply_impulse(&mut self, impulse: Vec2) {
+        self.impulse += impulse;
+    }
+
+    /// Get the impulses waiting for the next integration
+    #[must_use]
+    pub fn pending_impulse(&self) -> Vec2 {
+        self.impulse
+    }
+
+    /// Check if there is no velocity and no pending impulse
+    #[must_use]
+    pub fn is_resting(&self) -> bool {
+        self.velocity == Vec2::ZERO && self.impulse == Vec2::ZERO
+    }
+
+    /// Apply pending impulses and drag over `dt` seconds
+    ///
+    /// Drag divides the velocity by `1 + drag * dt` rather than decaying it
+    /// with `exp`, which goes through the platform's libm and may round
+    /// differently elsewhere. Static bodies don't keep any momentum.
+    pub fn integrate(&mut self, body: &RigidBody, dt: f32) {
+        if !body.is_dynamic() {
+            *self = Self::default();
+            return;
+        }
+        self.velocity += self.impulse * body.inverse_mass();
+        self.impulse = Vec2::ZERO;
+        self.velocity /= 1.0 + body.drag * dt;
+        if self.velocity.length_squared() < REST_SPEED * REST_SPEED {
+            self.velocity = Vec2::ZERO;
+        }
+    }
+
+    /// Bounce off a surface facing `normal`, keeping `restitution` of the
+    /// speed into it
+    pub fn bounce(&mut self, normal: Vec2, restitution: f32) {
+        let into = self.velocity.dot(normal);
+        if into < 0.0 {
+            self.velocity -= normal * into * (1.0 + restitution);
+        }
+    }
+}
+
+/// Get the impulse knocking a body at `target` away from `source`
+///
+/// Zero if both are at the same place.
+#[must_use]
+pub fn knockback_impulse(source: Vec2, target: Vec2, strength: f32) -> Vec2 {
+    (target - source).normalize_or_zero() * strength
+}
+
+/// Exchange momentum between two bodies that collided
+///
+/// `normal` points from `b` to `a`, the way `a` was pushed out. Nothing
+/// happens if they are already moving apart. The bounce uses the larger
+/// of both restitutions.
+pub fn collide(
+    a: &mut Momentum,
+    a_body: &RigidBody,
+    b: &mut Momentum,
+    b_body: &RigidBody,
+    normal: Vec2,
+) {
+    let (a_inverse, b_inverse) = (a_body.inverse_mass(), b_body.inverse_mass());
+    let total = a_inverse + b_inverse;
+    let approach = (a.velocity - b.velocity).dot(normal);
+    if total == 0.0 || approach >= 0.0 {
+        return;
+    }
+    let restitution = a_body.restitution.max(b_body.restitution);
+    let impulse = normal * (-(1.0 + restitution) * approach / total);
+    a.velocity += impulse * a_inverse;
+    b.velocity -= impulse * b_inverse;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_impulses_scale_with_mass_and_fade_with_drag() {
+        let barrel = RigidBody::dynamic().with_mass(4.0).with_drag(2.0);
+        let mut momentum = Momentum::default();
+        momentum.apply_impulse(Vec2::new(400.0, 0.0));
+        assert!(!momentum.is_resting());
+
+        momentum.integrate(&barrel, 0.0);
+        assert_eq!(momentum.velocity, Vec2::new(100.0, 0.0));
+        assert_eq!(momentum.pending_impulse(), Vec2::ZERO);
+
+        // A third left after a second, then at rest
+        momentum.integrate(&barrel, 1.0);
+        assert_eq!(momentum.velocity, Vec2::new(100.0 / 3.0, 0.0));
+        for _ in 0..5 {
+            momentum.integrate(&barrel, 1.0);
+        }
+        assert!(momentum.is_resting());
+
+        // Static bodies drop everything
+        momentum.apply_impulse(Vec2::Y);
+        momentum.integrate(&RigidBody::fixed(), 1.0);
+        assert!(momentum.is_resting());
+    }
+
+    #[test]
+    fn test_bounce_keeps_restitution() {
+        let mut momentum = Momentum::new(Vec2::new(-10.0, 5.0));
+        momentum.bounce(Vec2::X, 0.5);
+        assert_eq!(momentum.velocity, Vec2::new(5.0, 5.0));
+
+        // Moving away from the surface already
+        momentum.bounce(Vec2::X, 0.5);
+        assert_eq!(momentum.velocity, Vec2::new(5.0, 5.0));
+        momentum.bounce(Vec2::NEG_Y, 0.0);
+        assert_eq!(momentum.velocity, Vec2::new(5.0, 0.0));
+    }
+
+    #[test]
+    fn test_collisions_exchange_momentum() {
+        let body = RigidBody::dynamic();
+        let mut rolling = Momentum::new(Vec2::new(20.0, 0.0));
+        let mut resting = Momentum::default();
+
+        // Equal masses without bounce end up moving together
+        collide(&mut rolling, &body, &mut resting, &body, Vec2::NEG_X);
+        assert_eq!(rolling.velocity, Vec2::new(10.0, 0.0));
+        assert_eq!(resting.velocity, Vec2::new(10.0, 0.0));
+
+        // Elastic: the mover stops and the other takes all of it
+        let bouncy = body.with_restitution(1.0);
+        let mut rolling = Momentum::new(Vec2::new(20.0, 0.0));
+        let mut resting = Momentum::default();
+        collide(&mut rolling, &bouncy, &mut resting, &body, Vec2::NEG_X);
+        assert_eq!(rolling.velocity, Vec2::ZERO);
+        assert_eq!(resting.velocity, Vec2::new(20.0, 0.0));
+
+        // Against a static body, only the dynamic one bounces
+        let mut wall = Momentum::default();
+        collide(
+            &mut resting,
+            &bouncy,
+            &mut wall,
+            &RigidBody::fixed(),
+            Vec2::NEG_X,
+        );
+        assert_eq!(resting.velocity, Vec2::new(-20.0, 0.0));
+        assert_eq!(wall.velocity, Vec2::ZERO);
+
+        assert_eq!(
+            knockback_impulse(Vec2::ZERO, Vec2::new(0.0, 3.0), 50.0),
+            Vec2::new(0.0, 50.0)
+        );
+    }
+}
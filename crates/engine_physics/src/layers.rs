@@ -0,0 +1,340 @@
+//! Collision layers and masks
+//!
+//! Every collider belongs to one or more layers and has a mask of the
+//! layers it collides with. Two colliders interact only if each one's mask
+//! includes a layer of the other; tiles only have layers, so a mover is
+//! blocked by a tile when its mask includes one of the tile's layers.
+//!
+//! Layers serialize as lists of names, e.g. `layers = ["player"]` in a
+//! prefab.
+
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not};
+
+use engine_ecs::{FieldType, FieldValue, ReflectValue};
+use serde::{Deserialize, Serialize};
+
+/// Set of collision layers, stored as bits
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct LayerMask(pub u32);
+
+impl LayerMask {
+    /// No layers
+    pub const NONE: Self = Self(0);
+    /// Walls, solid tiles and props
+    pub const SOLID: Self = Self(1 << 0);
+    /// The player
+    pub const PLAYER: Self = Self(1 << 1);
+    /// Villagers
+    pub const NPC: Self = Self(1 << 2);
+    /// Planted crops
+    pub const CROP: Self = Self(1 << 3);
+    /// Water tiles
+    pub const WATER: Self = Self(1 << 4);
+    /// Pickups, map transitions and other zones that only fire triggers
+    pub const TRIGGER: Self = Self(1 << 5);
+    /// Thrown or cast objects (fishing line, tools)
+    pub const PROJECTILE: Self = Self(1 << 6);
+    /// Every layer
+    pub const ALL: Self = Self(u32::MAX);
+
+    /// Layer names, as used in serialized masks
+    pub const NAMED: [(&'static str, Self); 7] = [
+        ("solid", Self::SOLID),
+        ("player", Self::PLAYER),
+        ("npc", Self::NPC),
+        ("crop", Self::CROP),
+        ("water", Self::WATER),
+        ("trigger", Self::TRIGGER),
+        ("projectile", Self::PROJECTILE),
+    ];
+
+    /// Look up a layer by name
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMED
+            .iter()
+            .find(|(layer_name, _)| layer_name.eq_ignore_ascii_case(name.trim()))
+            .map(|&(_, layer)| layer)
+    }
+
+    /// Parse a comma-separated list of layer names, e.g. `"water, solid"`
+    ///
+    /// # Errors
+    /// Returns the first unknown name.
+    pub fn parse(names: &str) -> Result<Self, UnknownLayer> {
+        names
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+            .try_fold(Self::NONE, |mask, name| {
+                Self::from_name(name)
+                    .map(|layer| mask | layer)
+                    .ok_or_else(|| UnknownLayer(name.trim().to_string()))
+            })
+    }
+
+    /// Check if this set shares a layer with another
+    #[must_use]
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Check if this set includes every layer of another
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Check if no layer is set
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for LayerMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for LayerMask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for LayerMask {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl Not for LayerMask {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(!self.0)
+    }
+}
+
+impl fmt::Debug for LayerMask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LayerMask({})", Vec::<String>::from(*self).join(" | "))
+    }
+}
+
+impl From<LayerMask> for Vec<String> {
+    fn from(mask: LayerMask) -> Self {
+        if mask == LayerMask::ALL {
+            return vec!["all".to_string()];
+        }
+        let mut names: Vec<String> = LayerMask::NAMED
+            .iter()
+            .filter(|(_, layer)| mask.contains(*layer))
+            .map(|(name, _)| (*name).to_string())
+            .collect();
+        // Bits without a name are kept as numbers
+        let unnamed = LayerMask::NAMED
+            .iter()
+            .fold(mask, |rest, &(_, layer)| rest & !layer);
+        if !unnamed.is_empty() {
+            names.push(unnamed.0.to_string());
+        }
+        names
+    }
+}
+
+impl TryFrom<Vec<String>> for LayerMask {
+    type Error = UnknownLayer;
+
+    fn try_from(names: Vec<String>) -> Result<Self, UnknownLayer> {
+        names.iter().try_fold(Self::NONE, |mask, name| {
+            if name.eq_ignore_ascii_case("all") {
+                return Ok(Self::ALL);
+            }
+            Self::from_name(name)
+                .or_else(|| name.parse().ok().map(Self))
+                .map(|layer| mask | layer)
+                .ok_or_else(|| UnknownLayer(name.clone()))
+        })
+    }
+}
+
+/// Reflected as a comma-separated list of names, e.g. `"player, npc"`
+impl ReflectValue for LayerMask {
+    const TYPE: FieldType = FieldType::String;
+
+    fn to_field(&self) -> FieldValue {
+        FieldValue::String(Vec::<String>::from(*self).join(", "))
+    }
+
+    fn from_field(value: FieldValue) -> Option<Self> {
+        match value {
+            FieldValue::String(names) => names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+                .try_into()
+                .ok(),
+            _ => None,
+        }
+    }
+}
+
+/// A layer name that isn't defined
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownLayer(pub String);
+
+impl fmt::Display for UnknownLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown collision layer '{}'", self.0)
+    }
+}
+
+impl std::error::Error for UnknownLayer {}
+
+/// Layers a collider belongs to and the layers it collides with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollisionLayers {
+    /// Layers this collider belongs to
+    #[serde(default = "default_layers")]
+    pub layers: LayerMask,
+    /// Layers this collider collides with
+    #[serde(default = "default_mask")]
+    pub mask: LayerMask,
+}
+
+fn default_layers() -> LayerMask {
+    LayerMask::SOLID
+}
+
+fn default_mask() -> LayerMask {
+    LayerMask::ALL
+}
+
+impl Default for CollisionLayers {
+    /// Solid, colliding with everything
+    fn default() -> Self {
+        Self::new(default_layers(), default_mask())
+    }
+}
+
+impl CollisionLayers {
+    /// Create collision layers
+    #[must_use]
+    pub const fn new(layers: LayerMask, mask: LayerMask) -> Self {
+        Self { layers, mask }
+    }
+
+    /// Check if two colliders collide with each other
+    #[must_use]
+    pub const fn interacts(&self, other: &Self) -> bool {
+        self.mask.intersects(other.layers) && other.mask.intersects(self.layers)
+    }
+
+    /// Check if this collider is blocked by a tile on the given layers
+    #[must_use]
+    pub const fn collides_with(&self, layers: LayerMask) -> bool {
+        self.mask.intersects(layers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player() -> CollisionLayers {
+        CollisionLayers::new(
+            LayerMask::PLAYER,
+            LayerMask::SOLID | LayerMask::WATER | LayerMask::NPC,
+        )
+    }
+
+    fn npc() -> CollisionLayers {
+        CollisionLayers::new(
+            LayerMask::NPC,
+            LayerMask::SOLID | LayerMask::WATER | LayerMask::PLAYER | LayerMask::NPC,
+        )
+    }
+
+    #[test]
+    fn test_reflected_mask() {
+        let mask = LayerMask::SOLID | LayerMask::WATER;
+        assert_eq!(mask.to_field(), FieldValue::String("solid, water".into()));
+        assert_eq!(LayerMask::from_field(mask.to_field()), Some(mask));
+        assert_eq!(
+            LayerMask::from_field(FieldValue::String("all".into())),
+            Some(LayerMask::ALL)
+        );
+        assert_eq!(
+            LayerMask::from_field(FieldValue::String("lava".into())),
+            None
+        );
+        assert_eq!(LayerMask::from_field(FieldValue::Int(1)), None);
+    }
+
+    #[test]
+    fn test_layer_interactions() {
+        let crop = CollisionLayers::new(LayerMask::CROP, LayerMask::ALL);
+        let pickup = CollisionLayers::new(LayerMask::TRIGGER, LayerMask::PLAYER);
+        let fishing_cast = CollisionLayers::new(LayerMask::PROJECTILE, LayerMask::SOLID);
+
+        assert!(player().interacts(&npc()));
+        assert!(npc().interacts(&npc()));
+        assert!(!npc().interacts(&crop));
+        assert!(!player().interacts(&pickup));
+
+        // Water blocks walking but not casting over it
+        assert!(player().collides_with(LayerMask::WATER));
+        assert!(!fishing_cast.collides_with(LayerMask::WATER));
+        assert!(fishing_cast.collides_with(LayerMask::SOLID));
+    }
+
+    #[test]
+    fn test_default_layers_collide_with_everything() {
+        let prop = CollisionLayers::default();
+        assert!(prop.interacts(&player()));
+        assert!(prop.interacts(&npc()));
+        assert!(prop.collides_with(LayerMask::WATER));
+    }
+
+    #[test]
+    fn test_layer_names() {
+        assert_eq!(
+            LayerMask::parse("water, Solid"),
+            Ok(LayerMask::WATER | LayerMask::SOLID)
+        );
+        assert_eq!(LayerMask::parse(""), Ok(LayerMask::NONE));
+        assert_eq!(
+            LayerMask::parse("lava"),
+            Err(UnknownLayer("lava".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_layers_serialize_as_names() {
+        let layers = player();
+        let json = serde_json::to_string(&layers).unwrap();
+        assert_eq!(
+            json,
+            r#"{"layers":["player"],"mask":["solid","npc","water"]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<CollisionLayers>(&json).unwrap(),
+            layers
+        );
+
+        let all: CollisionLayers = serde_json::from_str(r#"{"layers":["npc", "256"]}"#).unwrap();
+        assert_eq!(all.layers, LayerMask::NPC | LayerMask(256));
+        assert_eq!(all.mask, LayerMask::ALL);
+        assert!(serde_json::from_str::<CollisionLayers>(r#"{"layers":["lava"]}"#).is_err());
+    }
+}
@@ -1,13 +1,36 @@
 //! Engine Physics - 2D Collision Detection
 //!
 //! This crate provides AABB collision detection with spatial
-//! partitioning for efficient broad-phase collision detection.
-
+//! partitioning for efficient broad-phase collision detection, swept
+//! AABB tests against tunneling, static/dynamic bodies for resolving
+//! entity overlaps, impulses and momentum for dynamic bodies, collision
+//! layers to choose what collides, sensor colliders sending trigger events,
+//! ray and shape casts, and a kinematic character controller.
+
+mod body;
+mod cast;
+mod controller;
+mod dynamics;
+mod layers;
+mod sensor;
+mod sweep;
+
+pub use body::{separate, separate_bodies, BodyType, RigidBody};
+pub use cast::{raycast_tiles, shape_cast_tiles, CastHit, HitTarget, Ray, TileGrid};
+pub use controller::{CharacterController, Touch, DEFAULT_CORNER_CORRECTION};
+pub use dynamics::{collide, knockback_impulse, Momentum, REST_SPEED};
+pub use layers::{CollisionLayers, LayerMask, UnknownLayer};
+pub use sensor::{
+    add_trigger_events, Sensor, SensorTracker, TriggerEnter, TriggerExit, TriggerStay,
+};
+pub use sweep::{slide, SlideResult, SweepHit, CONTACT_EPSILON};
+
+use engine_ecs::Entity;
 use glam::Vec2;
 use std::collections::HashMap;
 
-/// Entity ID type (will be replaced by ECS Entity later)
-pub type EntityId = u32;
+/// Entity ID type stored in the spatial grid
+pub type EntityId = Entity;
 
 /// Axis-Aligned Bounding Box for collision detection
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -212,6 +235,9 @@ impl SpatialGrid {
 
     /// Query for potential collision candidates with an AABB
     /// Returns entity IDs that might collide (broad phase)
+    ///
+    /// Candidates are sorted by entity, so callers resolving them in order
+    /// don't depend on the order entities were added or moved in.
     #[must_use]
     pub fn query(&self, aabb: &AABB) -> Vec<EntityId> {
         let cells = self.get_cells_for_aabb(aabb);
@@ -228,6 +254,7 @@ impl SpatialGrid {
             }
         }
 
+        candidates.sort_unstable_by_key(|entity| (entity.index, entity.generation));
         candidates
     }
 
@@ -254,6 +281,10 @@ impl SpatialGrid {
 mod tests {
     use super::*;
 
+    fn entity(index: u32) -> Entity {
+        Entity::new(index, 0)
+    }
+
     #[test]
     fn test_aabb_intersects() {
         let a = AABB::new(0.0, 0.0, 10.0, 10.0);
@@ -289,36 +320,49 @@ mod tests {
     fn test_spatial_grid_query() {
         let mut grid = SpatialGrid::new(64.0);
 
-        grid.insert(1, &AABB::new(0.0, 0.0, 32.0, 32.0));
-        grid.insert(2, &AABB::new(100.0, 100.0, 32.0, 32.0));
-        grid.insert(3, &AABB::new(16.0, 16.0, 32.0, 32.0));
+        grid.insert(entity(1), &AABB::new(0.0, 0.0, 32.0, 32.0));
+        grid.insert(entity(2), &AABB::new(100.0, 100.0, 32.0, 32.0));
+        grid.insert(entity(3), &AABB::new(16.0, 16.0, 32.0, 32.0));
 
         // Query near entity 1 and 3
         let candidates = grid.query(&AABB::new(0.0, 0.0, 50.0, 50.0));
-        assert!(candidates.contains(&1));
-        assert!(candidates.contains(&3));
-        assert!(!candidates.contains(&2));
+        assert!(candidates.contains(&entity(1)));
+        assert!(candidates.contains(&entity(3)));
+        assert!(!candidates.contains(&entity(2)));
     }
 
     #[test]
     fn test_spatial_grid_update() {
         let mut grid = SpatialGrid::new(64.0);
 
-        grid.insert(1, &AABB::new(0.0, 0.0, 32.0, 32.0));
+        grid.insert(entity(1), &AABB::new(0.0, 0.0, 32.0, 32.0));
 
         // Entity 1 should be found near origin
         let candidates = grid.query(&AABB::new(0.0, 0.0, 10.0, 10.0));
-        assert!(candidates.contains(&1));
+        assert!(candidates.contains(&entity(1)));
 
         // Move entity far away
-        grid.update(1, &AABB::new(500.0, 500.0, 32.0, 32.0));
+        grid.update(entity(1), &AABB::new(500.0, 500.0, 32.0, 32.0));
 
         // Entity 1 should no longer be found near origin
         let candidates = grid.query(&AABB::new(0.0, 0.0, 10.0, 10.0));
-        assert!(!candidates.contains(&1));
+        assert!(!candidates.contains(&entity(1)));
 
         // But should be found at new location
         let candidates = grid.query(&AABB::new(500.0, 500.0, 10.0, 10.0));
-        assert!(candidates.contains(&1));
+        assert!(candidates.contains(&entity(1)));
+    }
+
+    #[test]
+    fn test_spatial_grid_query_order() {
+        let mut grid = SpatialGrid::new(64.0);
+        for index in [3, 1, 2] {
+            grid.insert(entity(index), &AABB::new(0.0, 0.0, 100.0, 10.0));
+        }
+        grid.update(entity(1), &AABB::new(70.0, 0.0, 10.0, 10.0));
+
+        // Same order whatever the insertion and update history
+        let candidates = grid.query(&AABB::new(0.0, 0.0, 100.0, 10.0));
+        assert_eq!(candidates, vec![entity(1), entity(2), entity(3)]);
     }
 }
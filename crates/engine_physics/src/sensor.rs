@@ -0,0 +1,186 @@
+//! Sensor colliders and trigger events
+//!
+//! A collider marked [`Sensor`] neither blocks nor pushes anything; it only
+//! reports the colliders overlapping it. Each update, [`SensorTracker`]
+//! compares the overlapping pairs with the previous ones and sends a
+//! [`TriggerEnter`], [`TriggerStay`] or [`TriggerExit`] event per pair.
+
+use std::collections::HashSet;
+
+use engine_ecs::{impl_reflect, Entity, World};
+use serde::{Deserialize, Serialize};
+
+/// Marks a collider as a sensor (trigger zone)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sensor;
+
+impl_reflect!(Sensor {});
+
+/// A collider started overlapping a sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerEnter {
+    pub sensor: Entity,
+    pub other: Entity,
+}
+
+/// A collider is still overlapping a sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerStay {
+    pub sensor: Entity,
+    pub other: Entity,
+}
+
+/// A collider stopped overlapping a sensor
+///
+/// Also sent when either entity was despawned, in which case it may no
+/// longer be alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerExit {
+    pub sensor: Entity,
+    pub other: Entity,
+}
+
+/// Register the trigger event types on a world
+pub fn add_trigger_events(world: &mut World) {
+    world.add_event::<TriggerEnter>();
+    world.add_event::<TriggerStay>();
+    world.add_event::<TriggerExit>();
+}
+
+/// Remembers which colliders overlap each sensor between updates (resource)
+#[derive(Debug, Default)]
+pub struct SensorTracker {
+    /// (sensor, other) pairs overlapping at the last update, in order
+    overlapping: Vec<(Entity, Entity)>,
+}
+
+impl SensorTracker {
+    /// Create an empty tracker
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record this update's overlapping `(sensor, other)` pairs and send
+    /// the trigger events
+    ///
+    /// Enter and stay events follow the order of `overlaps`; exit events
+    /// follow the order pairs were first seen in.
+    ///
+    /// # Panics
+    /// Panics if the trigger events were not registered with
+    /// [`add_trigger_events`].
+    pub fn update(&mut self, world: &mut World, overlaps: Vec<(Entity, Entity)>) {
+        let previous: HashSet<(Entity, Entity)> = self.overlapping.iter().copied().collect();
+        let current: HashSet<(Entity, Entity)> = overlaps.iter().copied().collect();
+
+        for &(sensor, other) in &self.overlapping {
+            if !current.contains(&(sensor, other)) {
+                world.send_event(TriggerExit { sensor, other });
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for &(sensor, other) in &overlaps {
+            if !seen.insert((sensor, other)) {
+                continue;
+            }
+            if previous.contains(&(sensor, other)) {
+                world.send_event(TriggerStay { sensor, other });
+            } else {
+                world.send_event(TriggerEnter { sensor, other });
+            }
+        }
+
+        // Keep first-seen order for the pairs still overlapping
+        self.overlapping.retain(|pair| current.contains(pair));
+        for pair in overlaps {
+            if !previous.contains(&pair) && !self.overlapping.contains(&pair) {
+                self.overlapping.push(pair);
+            }
+        }
+    }
+
+    /// Check if a collider overlapped a sensor at the last update
+    #[must_use]
+    pub fn is_inside(&self, sensor: Entity, other: Entity) -> bool {
+        self.overlapping.contains(&(sensor, other))
+    }
+
+    /// Get the colliders overlapping a sensor at the last update
+    pub fn overlapping(&self, sensor: Entity) -> impl Iterator<Item = Entity> + '_ {
+        self.overlapping
+            .iter()
+            .filter(move |(s, _)| *s == sensor)
+            .map(|&(_, other)| other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine_ecs::{EventReader, Events};
+
+    /// Read new events as (sensor, other) pairs
+    fn read<T: Send + Sync + 'static>(
+        world: &World,
+        reader: &mut EventReader<T>,
+        pair: fn(&T) -> (Entity, Entity),
+    ) -> Vec<(Entity, Entity)> {
+        reader
+            .read(world.get_resource::<Events<T>>().unwrap())
+            .map(pair)
+            .collect()
+    }
+
+    #[test]
+    fn test_enter_stay_exit() {
+        let mut world = World::new();
+        add_trigger_events(&mut world);
+        let door = world.spawn();
+        let player = world.spawn();
+        let npc = world.spawn();
+        let mut tracker = SensorTracker::new();
+        let mut enter = EventReader::<TriggerEnter>::new();
+        let mut stay = EventReader::<TriggerStay>::new();
+        let mut exit = EventReader::<TriggerExit>::new();
+        let entered = |e: &TriggerEnter| (e.sensor, e.other);
+        let stayed = |e: &TriggerStay| (e.sensor, e.other);
+        let exited = |e: &TriggerExit| (e.sensor, e.other);
+
+        tracker.update(&mut world, vec![(door, player)]);
+        assert_eq!(read(&world, &mut enter, entered), vec![(door, player)]);
+        assert!(read(&world, &mut stay, stayed).is_empty());
+
+        tracker.update(&mut world, vec![(door, npc), (door, player)]);
+        assert_eq!(read(&world, &mut enter, entered), vec![(door, npc)]);
+        assert_eq!(read(&world, &mut stay, stayed), vec![(door, player)]);
+        assert_eq!(
+            tracker.overlapping(door).collect::<Vec<_>>(),
+            vec![player, npc]
+        );
+
+        tracker.update(&mut world, vec![(door, npc)]);
+        assert_eq!(read(&world, &mut exit, exited), vec![(door, player)]);
+        assert!(!tracker.is_inside(door, player));
+        assert!(tracker.is_inside(door, npc));
+
+        tracker.update(&mut world, Vec::new());
+        assert_eq!(read(&world, &mut exit, exited), vec![(door, npc)]);
+        assert!(read(&world, &mut enter, entered).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_pairs_send_one_event() {
+        let mut world = World::new();
+        add_trigger_events(&mut world);
+        let zone = world.spawn();
+        let crop = world.spawn();
+        let mut tracker = SensorTracker::new();
+        let mut enter = EventReader::<TriggerEnter>::new();
+
+        tracker.update(&mut world, vec![(zone, crop), (zone, crop)]);
+        assert_eq!(read(&world, &mut enter, |e| (e.sensor, e.other)).len(), 1);
+        assert_eq!(tracker.overlapping(zone).count(), 1);
+    }
+}
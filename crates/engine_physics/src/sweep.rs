@@ -0,0 +1,247 @@
+//! Swept AABB collision (continuous collision detection)
+//!
+//! Moving a box to `position + velocity * dt` and pushing it out afterwards
+//! lets fast movers tunnel through thin walls. Sweeping finds the time of
+//! impact along the movement instead, and [`slide`] resolves each axis
+//! separately so blocked movement slides along walls.
+
+use glam::Vec2;
+
+use crate::AABB;
+
+/// Overlap below this distance counts as touching rather than overlapping
+pub const CONTACT_EPSILON: f32 = 1e-3;
+
+/// First contact of a moving AABB with another
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepHit {
+    /// Fraction of the movement done before contact, in `0.0..1.0`
+    pub time: f32,
+    /// Surface normal of the box that was hit
+    pub normal: Vec2,
+}
+
+/// Result of sliding an AABB through a set of solids
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlideResult {
+    /// Movement actually done
+    pub movement: Vec2,
+    /// Whether horizontal movement was blocked
+    pub hit_x: bool,
+    /// Whether vertical movement was blocked
+    pub hit_y: bool,
+}
+
+/// Entry and exit times of a moving interval against a fixed one
+///
+/// Returns None if the intervals never overlap along this axis.
+fn axis_times(a_min: f32, a_max: f32, b_min: f32, b_max: f32, delta: f32) -> Option<(f32, f32)> {
+    if delta == 0.0 {
+        let overlapping = a_max - b_min > CONTACT_EPSILON && b_max - a_min > CONTACT_EPSILON;
+        return overlapping.then_some((f32::NEG_INFINITY, f32::INFINITY));
+    }
+
+    let (gap, far) = if delta > 0.0 {
+        (b_min - a_max, b_max - a_min)
+    } else {
+        (a_min - b_max, a_max - b_min)
+    };
+    // Boxes left barely overlapping by rounding still count as touching
+    let gap = if gap > -CONTACT_EPSILON {
+        gap.max(0.0)
+    } else {
+        gap
+    };
+    let speed = delta.abs();
+    Some((gap / speed, far / speed))
+}
+
+impl AABB {
+    /// Find when this AABB, moved by `delta`, first touches `other`
+    ///
+    /// Returns None if it doesn't within the movement, moves away, or
+    /// already overlaps `other` at the start.
+    #[must_use]
+    pub fn sweep(&self, delta: Vec2, other: &Self) -> Option<SweepHit> {
+        let (entry_x, exit_x) =
+            axis_times(self.min.x, self.max.x, other.min.x, other.max.x, delta.x)?;
+        let (entry_y, exit_y) =
+            axis_times(self.min.y, self.max.y, other.min.y, other.max.y, delta.y)?;
+
+        let entry = entry_x.max(entry_y);
+        let exit = exit_x.min(exit_y);
+        if !(0.0..1.0).contains(&entry) || entry >= exit {
+            return None;
+        }
+
+        let normal = if entry_x >= entry_y {
+            Vec2::new(-delta.x.signum(), 0.0)
+        } else {
+            Vec2::new(0.0, -delta.y.signum())
+        };
+        Some(SweepHit {
+            time: entry,
+            normal,
+        })
+    }
+
+    /// Get the box covering this AABB along a whole movement
+    #[must_use]
+    pub fn swept(&self, delta: Vec2) -> Self {
+        let moved = self.translated(delta);
+        Self {
+            min: self.min.min(moved.min),
+            max: self.max.max(moved.max),
+        }
+    }
+}
+
+/// Earliest hit of a movement against any solid
+pub(crate) fn first_hit(aabb: &AABB, delta: Vec2, solids: &[AABB]) -> Option<SweepHit> {
+    solids
+        .iter()
+        .filter_map(|solid| aabb.sweep(delta, solid))
+        .min_by(|a, b| a.time.total_cmp(&b.time))
+}
+
+/// Move an AABB by `delta` without entering any of `solids`
+///
+/// Horizontal movement is resolved first, then vertical movement from where
+/// it stopped, so a blocked axis doesn't stop the other: the box slides
+/// along walls and settles into inside corners. Solids the box already
+/// overlaps are ignored.
+#[must_use]
+pub fn slide(aabb: &AABB, delta: Vec2, solids: &[AABB]) -> SlideResult {
+    let step_x = Vec2::new(delta.x, 0.0);
+    let hit_x = first_hit(aabb, step_x, solids);
+    let movement_x = step_x * hit_x.map_or(1.0, |hit| hit.time);
+    let aabb = aabb.translated(movement_x);
+
+    let step_y = Vec2::new(0.0, delta.y);
+    let hit_y = first_hit(&aabb, step_y, solids);
+    let movement_y = step_y * hit_y.map_or(1.0, |hit| hit.time);
+
+    SlideResult {
+        movement: movement_x + movement_y,
+        hit_x: hit_x.is_some(),
+        hit_y: hit_y.is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TILE: f32 = 16.0;
+
+    fn tile(x: i32, y: i32) -> AABB {
+        AABB::new(x as f32 * TILE, y as f32 * TILE, TILE, TILE)
+    }
+
+    fn assert_near(actual: Vec2, expected: Vec2) {
+        assert!(
+            (actual - expected).length() < 1e-4,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_sweep_time_of_impact() {
+        let mover = AABB::new(0.0, 0.0, 10.0, 10.0);
+        let wall = AABB::new(20.0, 0.0, 10.0, 10.0);
+
+        let hit = mover.sweep(Vec2::new(20.0, 0.0), &wall).unwrap();
+        assert_eq!(hit.time, 0.5);
+        assert_eq!(hit.normal, Vec2::new(-1.0, 0.0));
+
+        // Too short, moving away, or passing beside
+        assert!(mover.sweep(Vec2::new(9.0, 0.0), &wall).is_none());
+        assert!(mover.sweep(Vec2::new(-20.0, 0.0), &wall).is_none());
+        assert!(mover
+            .sweep(Vec2::new(20.0, 0.0), &wall.translated(Vec2::new(0.0, 10.0)))
+            .is_none());
+    }
+
+    #[test]
+    fn test_fast_movement_does_not_tunnel() {
+        // A 2px wall and a movement far longer than the mover
+        let mover = AABB::new(0.0, 0.0, 8.0, 8.0);
+        let thin_wall = AABB::new(100.0, -50.0, 2.0, 100.0);
+
+        let result = slide(&mover, Vec2::new(5000.0, 0.0), &[thin_wall]);
+        assert!(result.hit_x);
+        assert_near(result.movement, Vec2::new(92.0, 0.0));
+        assert!(!mover.translated(result.movement).intersects(&thin_wall));
+    }
+
+    #[test]
+    fn test_slide_along_wall() {
+        // Wall column to the right, moving diagonally into it
+        let solids: Vec<AABB> = (0..4).map(|y| tile(2, y)).collect();
+        let mover = AABB::new(16.0, 4.0, 12.0, 8.0);
+
+        let result = slide(&mover, Vec2::new(10.0, 20.0), &solids);
+        assert!(result.hit_x);
+        assert!(!result.hit_y);
+        assert_near(result.movement, Vec2::new(4.0, 20.0));
+    }
+
+    #[test]
+    fn test_diagonal_into_inside_corner() {
+        // Walls to the right and below meet in an inside corner
+        let solids = [tile(2, 0), tile(2, 1), tile(2, 2), tile(0, 2), tile(1, 2)];
+        let mover = AABB::new(10.0, 10.0, 8.0, 8.0);
+
+        let result = slide(&mover, Vec2::new(30.0, 30.0), &solids);
+        assert!(result.hit_x && result.hit_y);
+        let settled = mover.translated(result.movement);
+        assert_near(settled.max, Vec2::new(32.0, 32.0));
+
+        // Pushing further into the corner stays put instead of jittering
+        let again = slide(&settled, Vec2::new(5.0, 5.0), &solids);
+        assert_near(again.movement, Vec2::ZERO);
+    }
+
+    #[test]
+    fn test_diagonal_past_outside_corner() {
+        // A single block; the mover clips its corner moving diagonally
+        let block = tile(1, 1);
+        let mover = AABB::new(6.0, 6.0, 8.0, 8.0);
+
+        // Horizontal movement passes above it, then the fall is stopped
+        let result = slide(&mover, Vec2::new(6.0, 6.0), &[block]);
+        assert!(!result.hit_x);
+        assert!(result.hit_y);
+        assert_near(result.movement, Vec2::new(6.0, 2.0));
+        assert!(!mover.translated(result.movement).intersects(&block));
+    }
+
+    #[test]
+    fn test_slide_along_floor_seams() {
+        // Flush floor tiles must not catch the mover at their seams
+        let floor: Vec<AABB> = (0..8).map(|x| tile(x, 1)).collect();
+        let mut mover = AABB::new(0.0, 8.0, 12.0, 8.0);
+
+        for _ in 0..20 {
+            let result = slide(&mover, Vec2::new(5.0, 3.0), &floor);
+            assert!(result.hit_y);
+            assert_near(Vec2::new(result.movement.x, 0.0), Vec2::new(5.0, 0.0));
+            mover = mover.translated(result.movement);
+        }
+        assert_near(mover.min, Vec2::new(100.0, 8.0));
+    }
+
+    #[test]
+    fn test_already_overlapping_is_ignored() {
+        let mover = AABB::new(0.0, 0.0, 10.0, 10.0);
+        let solid = AABB::new(5.0, 5.0, 10.0, 10.0);
+
+        assert!(mover.sweep(Vec2::new(3.0, 0.0), &solid).is_none());
+        assert_near(
+            slide(&mover, Vec2::new(3.0, 0.0), &[solid]).movement,
+            Vec2::new(3.0, 0.0),
+        );
+    }
+}
@@ -25,7 +25,9 @@ pub use renderer::{Frame, Renderer};
 pub use sprite::{Sprite, SpriteBatch, SpriteRegion, SpriteVertex};
 pub use stats::RenderStats;
 pub use texture::Texture;
-pub use tilemap::{LayerType, SpawnPoint, TileLayer, Tilemap, TilemapError, Tileset, Trigger};
+pub use tilemap::{
+    LayerType, SpawnPoint, TileLayer, TileShape, Tilemap, TilemapError, Tileset, Trigger,
+};
 pub use wgpu;
 
 /// Default clear color (dark blue)
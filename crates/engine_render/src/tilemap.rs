@@ -3,6 +3,7 @@
 //! Supports JSON-based tilemaps with multiple layers and tilesets.
 //! Compatible with both custom format and Tiled editor exports.
 
+use engine_physics::{LayerMask, TileGrid, AABB};
 use glam::Vec2;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
@@ -81,6 +82,31 @@ struct TiledObject {
     obj_type: String,
     #[serde(default)]
     properties: Vec<TiledProperty>,
+    /// Polygon points, relative to the object position
+    #[serde(default)]
+    polygon: Option<Vec<TiledPoint>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledPoint {
+    x: f32,
+    y: f32,
+}
+
+/// Per-tile data of a tileset (collision objects and properties)
+#[derive(Debug, Deserialize)]
+struct TiledTile {
+    id: u32,
+    #[serde(default)]
+    objectgroup: Option<TiledTileObjects>,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledTileObjects {
+    #[serde(default)]
+    objects: Vec<TiledObject>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -113,6 +139,8 @@ struct TiledTilesetRef {
     imagewidth: Option<u32>,
     #[serde(default)]
     imageheight: Option<u32>,
+    #[serde(default)]
+    tiles: Vec<TiledTile>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -127,6 +155,8 @@ struct TiledExternalTileset {
     imagewidth: Option<u32>,
     #[serde(default)]
     imageheight: Option<u32>,
+    #[serde(default)]
+    tiles: Vec<TiledTile>,
 }
 
 // ============================================================================
@@ -299,6 +329,112 @@ impl TileLayer {
     }
 }
 
+/// Height in pixels of the strips polygon collision shapes are split into
+pub const POLYGON_STRIP_HEIGHT: f32 = 2.0;
+
+/// Collision shape covering part of a tile, in pixels from the tile's
+/// top-left corner
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum TileShape {
+    /// Rectangle (Tiled ellipses use their bounds)
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    /// Polygon outline
+    Polygon { points: Vec<Vec2> },
+}
+
+impl TileShape {
+    /// Get the bounding box (min, max), relative to the tile
+    #[must_use]
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        match self {
+            Self::Rect { x, y, width, height } => {
+                (Vec2::new(*x, *y), Vec2::new(x + width, y + height))
+            }
+            Self::Polygon { points } => points.iter().fold(
+                (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+                |(min, max), &point| (min.min(point), max.max(point)),
+            ),
+        }
+    }
+
+    /// Get the outline, relative to the tile
+    #[must_use]
+    pub fn outline(&self) -> Vec<Vec2> {
+        match self {
+            Self::Rect { .. } => {
+                let (min, max) = self.bounds();
+                vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+            }
+            Self::Polygon { points } => points.clone(),
+        }
+    }
+
+    /// Get the boxes (min, max) this shape collides as, relative to the tile
+    ///
+    /// Polygons are split into horizontal strips of [`POLYGON_STRIP_HEIGHT`],
+    /// each covering the polygon's extent within it, so a diagonal edge
+    /// blocks a staircase close to it rather than the whole bounding box.
+    /// Strips of concave polygons also cover their notches.
+    #[must_use]
+    pub fn collision_boxes(&self) -> Vec<(Vec2, Vec2)> {
+        let Self::Polygon { points } = self else {
+            return vec![self.bounds()];
+        };
+        let (min, max) = self.bounds();
+        let strips = ((max.y - min.y) / POLYGON_STRIP_HEIGHT).ceil() as u32;
+
+        let mut boxes: Vec<(Vec2, Vec2)> = Vec::new();
+        for strip in 0..strips {
+            let top = min.y + strip as f32 * POLYGON_STRIP_HEIGHT;
+            let bottom = (top + POLYGON_STRIP_HEIGHT).min(max.y);
+
+            // The extent within the strip is reached at a corner inside it
+            // or where an edge crosses its top or bottom
+            let (mut left, mut right) = (f32::INFINITY, f32::NEG_INFINITY);
+            for (i, &a) in points.iter().enumerate() {
+                let b = points[(i + 1) % points.len()];
+                if (top..=bottom).contains(&a.y) {
+                    (left, right) = (left.min(a.x), right.max(a.x));
+                }
+                for y in [top, bottom] {
+                    if a.y != b.y && (a.y - y) * (b.y - y) <= 0.0 {
+                        let x = a.x + (b.x - a.x) * (y - a.y) / (b.y - a.y);
+                        (left, right) = (left.min(x), right.max(x));
+                    }
+                }
+            }
+            if left >= right {
+                continue;
+            }
+
+            // Merge with the strip above when it spans the same columns
+            match boxes.last_mut() {
+                Some(last) if last.0.x == left && last.1.x == right && last.1.y == top => {
+                    last.1.y = bottom;
+                }
+                _ => boxes.push((Vec2::new(left, top), Vec2::new(right, bottom))),
+            }
+        }
+        boxes
+    }
+}
+
+/// Collision of a tileset tile, from its objectgroup
+#[derive(Debug, Clone, PartialEq)]
+struct TileCollision {
+    /// Tile ID, local to the tileset
+    id: u32,
+    layers: LayerMask,
+    /// Sub-tile shapes; empty if the tile is solid over its whole area
+    shapes: Vec<TileShape>,
+}
+
 /// A complete tilemap with multiple layers
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tilemap {
@@ -319,6 +455,14 @@ pub struct Tilemap {
     /// Collision data (true = solid tile, row-major order)
     #[serde(default)]
     pub collision: Vec<bool>,
+    /// Collision layers of each tile (row-major order); solid tiles are on
+    /// `LayerMask::SOLID` when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collision_layers: Vec<LayerMask>,
+    /// Collision shapes of each tile (row-major order); solid tiles without
+    /// shapes block their whole area
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collision_shapes: Vec<Vec<TileShape>>,
     /// Spawn points for player positioning
     #[serde(default)]
     pub spawns: Vec<SpawnPoint>,
@@ -384,7 +528,7 @@ impl Tilemap {
 
         // Convert tilesets and collect collision tile IDs
         let mut tilesets = Vec::new();
-        let mut collision_tile_ids: std::collections::HashSet<u32> = std::collections::HashSet::new();
+        let mut collision_tiles: std::collections::HashMap<u32, TileCollision> = std::collections::HashMap::new();
 
         for ts_ref in &tiled.tilesets {
             let (tileset, tile_collisions) = if let Some(source) = &ts_ref.source {
@@ -406,12 +550,12 @@ impl Tilemap {
                     rows,
                     first_gid: ts_ref.firstgid,
                 };
-                (ts, Vec::new())
+                (ts, Self::json_tile_collisions(&ts_ref.tiles))
             };
 
-            // Add collision tile IDs (local_id + first_gid)
-            for local_id in tile_collisions {
-                collision_tile_ids.insert(local_id + ts_ref.firstgid);
+            // Key collision tiles by global ID (local_id + first_gid)
+            for tile in tile_collisions {
+                collision_tiles.insert(tile.id + ts_ref.firstgid, tile);
             }
 
             tilesets.push(tileset);
@@ -433,18 +577,35 @@ impl Tilemap {
             front_group,
         );
 
-        // Build collision array from tile data and collision tile IDs
+        // Build collision array from tile data and collision tiles
         let map_size = (tiled.width * tiled.height) as usize;
         let mut collision = vec![false; map_size];
-
-        if !collision_tile_ids.is_empty() {
+        let mut collision_layers = Vec::new();
+        let mut collision_shapes = Vec::new();
+
+        if !collision_tiles.is_empty() {
+            collision_layers = vec![LayerMask::NONE; map_size];
+            collision_shapes = vec![Vec::new(); map_size];
+            // Stacked tiles add up; a whole-tile collision covers the others
+            let mut whole = vec![false; map_size];
             for layer in &layers {
                 for (i, &tile_id) in layer.data.iter().enumerate() {
-                    if collision_tile_ids.contains(&tile_id) {
+                    if let Some(tile) = collision_tiles.get(&tile_id) {
                         collision[i] = true;
+                        collision_layers[i] |= tile.layers;
+                        whole[i] |= tile.shapes.is_empty();
+                        collision_shapes[i].extend(tile.shapes.iter().cloned());
                     }
                 }
             }
+            for (shapes, whole) in collision_shapes.iter_mut().zip(whole) {
+                if whole {
+                    shapes.clear();
+                }
+            }
+            if collision_shapes.iter().all(Vec::is_empty) {
+                collision_shapes.clear();
+            }
         }
 
         // Extract map name from filename
@@ -463,6 +624,8 @@ impl Tilemap {
             tilesets,
             layers,
             collision,
+            collision_layers,
+            collision_shapes,
             spawns,
             triggers,
         })
@@ -604,8 +767,8 @@ impl Tilemap {
     }
 
     /// Load an external .tsx tileset file with collision data
-    /// Returns (Tileset, Vec<local_tile_ids_with_collision>)
-    fn load_external_tileset_with_collisions(tsx_path: &Path, first_gid: u32, map_dir: &Path) -> Result<(Tileset, Vec<u32>), TilemapError> {
+    /// Returns the tileset and the collision of its tiles that have one
+    fn load_external_tileset_with_collisions(tsx_path: &Path, first_gid: u32, map_dir: &Path) -> Result<(Tileset, Vec<TileCollision>), TilemapError> {
         let contents = std::fs::read_to_string(tsx_path)
             .map_err(|e| TilemapError::IoError(format!("Failed to load tileset {}: {}", tsx_path.display(), e)))?;
 
@@ -626,8 +789,7 @@ impl Tilemap {
                 rows,
                 first_gid,
             };
-            // JSON format doesn't have inline collision - would need separate parsing
-            return Ok((tileset, Vec::new()));
+            return Ok((tileset, Self::json_tile_collisions(&ts.tiles)));
         }
 
         // Try XML format (.tsx files are usually XML)
@@ -635,7 +797,7 @@ impl Tilemap {
     }
 
     /// Parse XML .tsx tileset file with collision data
-    fn parse_tsx_xml_with_collisions(contents: &str, first_gid: u32, map_dir: &Path) -> Result<(Tileset, Vec<u32>), TilemapError> {
+    fn parse_tsx_xml_with_collisions(contents: &str, first_gid: u32, map_dir: &Path) -> Result<(Tileset, Vec<TileCollision>), TilemapError> {
         // Simple XML parsing for <tileset> and <image> tags
         let name = Self::extract_xml_attr(contents, "tileset", "name")
             .unwrap_or_else(|| "tileset".to_string());
@@ -662,7 +824,7 @@ impl Tilemap {
         let rows = if columns > 0 { tilecount / columns } else { 1 };
 
         // Extract tiles with collision (tiles that have <objectgroup> children)
-        let collision_tiles = Self::extract_collision_tile_ids(contents);
+        let collision_tiles = Self::extract_collision_tiles(contents);
 
         let tileset = Tileset {
             name,
@@ -677,9 +839,12 @@ impl Tilemap {
         Ok((tileset, collision_tiles))
     }
 
-    /// Extract tile IDs that have collision defined (contain <objectgroup>)
-    fn extract_collision_tile_ids(xml: &str) -> Vec<u32> {
-        let mut collision_ids = Vec::new();
+    /// Extract the tiles that have collision defined (contain <objectgroup>)
+    ///
+    /// A `collision_layer` tile property (e.g. `"water"`) sets the layers of
+    /// the tile; tiles without one are `LayerMask::SOLID`.
+    fn extract_collision_tiles(xml: &str) -> Vec<TileCollision> {
+        let mut collision_tiles = Vec::new();
 
         // Find all <tile id="X"> tags that contain <objectgroup>
         let mut search_pos = 0;
@@ -702,7 +867,15 @@ impl Tilemap {
                 // Extract the tile id
                 if let Some(id) = Self::extract_xml_attr(tile_content, "tile", "id") {
                     if let Ok(tile_id) = id.parse::<u32>() {
-                        collision_ids.push(tile_id);
+                        let layers = Self::collision_layers_property(
+                            tile_id,
+                            Self::extract_tile_property(tile_content, "collision_layer"),
+                        );
+                        collision_tiles.push(TileCollision {
+                            id: tile_id,
+                            layers,
+                            shapes: Self::extract_tile_shapes(tile_content),
+                        });
                     }
                 }
             }
@@ -710,7 +883,122 @@ impl Tilemap {
             search_pos = tile_end;
         }
 
-        collision_ids
+        collision_tiles
+    }
+
+    /// Extract the collision shapes of the `<object>`s in a tile element
+    fn extract_tile_shapes(tile_xml: &str) -> Vec<TileShape> {
+        let mut shapes = Vec::new();
+
+        let mut search_pos = 0;
+        while let Some(object_start) = tile_xml[search_pos..].find("<object ") {
+            let object_start = search_pos + object_start;
+            let Some(tag_end) = tile_xml[object_start..].find('>') else {
+                break;
+            };
+            let tag_end = object_start + tag_end;
+
+            // Self-closing objects are rectangles; others hold a shape element
+            let object_end = if tile_xml[..tag_end].ends_with('/') {
+                tag_end + 1
+            } else {
+                tile_xml[object_start..]
+                    .find("</object>")
+                    .map_or(tile_xml.len(), |end| object_start + end + 9)
+            };
+            let object = &tile_xml[object_start..object_end];
+
+            let attr = |name: &str| {
+                Self::extract_xml_attr(object, "object", name).and_then(|v| v.parse::<f32>().ok())
+            };
+            let (x, y) = (attr("x").unwrap_or(0.0), attr("y").unwrap_or(0.0));
+            let points = Self::extract_xml_attr(object, "polygon", "points").map(|points| {
+                points
+                    .split_whitespace()
+                    .filter_map(|point| {
+                        let (px, py) = point.split_once(',')?;
+                        Some(Vec2::new(px.parse().ok()?, py.parse().ok()?))
+                    })
+                    .collect::<Vec<_>>()
+            });
+            shapes.extend(Self::tile_shape(x, y, attr("width"), attr("height"), points));
+
+            search_pos = object_end;
+        }
+
+        shapes
+    }
+
+    /// Collision of the tiles of a JSON tileset that have an objectgroup
+    fn json_tile_collisions(tiles: &[TiledTile]) -> Vec<TileCollision> {
+        tiles
+            .iter()
+            .filter_map(|tile| {
+                let objects = &tile.objectgroup.as_ref()?.objects;
+                let shapes = objects
+                    .iter()
+                    .filter_map(|obj| {
+                        let points = obj.polygon.as_ref().map(|polygon| {
+                            polygon.iter().map(|p| Vec2::new(p.x, p.y)).collect()
+                        });
+                        Self::tile_shape(obj.x, obj.y, obj.width, obj.height, points)
+                    })
+                    .collect();
+                let layers = Self::collision_layers_property(
+                    tile.id,
+                    Self::get_property_string(&tile.properties, "collision_layer"),
+                );
+                Some(TileCollision {
+                    id: tile.id,
+                    layers,
+                    shapes,
+                })
+            })
+            .collect()
+    }
+
+    /// Build a collision shape from a Tiled object
+    ///
+    /// Polygon points are relative to the object position. Points and
+    /// objects without an area have no shape.
+    fn tile_shape(
+        x: f32,
+        y: f32,
+        width: Option<f32>,
+        height: Option<f32>,
+        polygon: Option<Vec<Vec2>>,
+    ) -> Option<TileShape> {
+        if let Some(points) = polygon {
+            let points: Vec<Vec2> = points.into_iter().map(|p| p + Vec2::new(x, y)).collect();
+            return (points.len() >= 3).then_some(TileShape::Polygon { points });
+        }
+        match (width, height) {
+            (Some(width), Some(height)) if width > 0.0 && height > 0.0 => Some(TileShape::Rect {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Parse a tile's `collision_layer` property, defaulting to solid
+    fn collision_layers_property(tile_id: u32, names: Option<String>) -> LayerMask {
+        names
+            .map_or(Ok(LayerMask::SOLID), |names| LayerMask::parse(&names))
+            .unwrap_or_else(|e| {
+                log::warn!("Tile {}: {}, using solid", tile_id, e);
+                LayerMask::SOLID
+            })
+    }
+
+    /// Extract the value of a named `<property>` inside a tile element
+    fn extract_tile_property(tile_xml: &str, name: &str) -> Option<String> {
+        let marker = format!("name=\"{}\"", name);
+        let property_start = tile_xml.find(&marker)?;
+        let tag_start = tile_xml[..property_start].rfind("<property")?;
+        Self::extract_xml_attr(&tile_xml[tag_start..], "property", "value")
     }
 
     /// Simple XML attribute extraction (no full XML parser needed)
@@ -857,10 +1145,63 @@ impl Tilemap {
         self.collision.get(index).copied().unwrap_or(false)
     }
 
+    /// Get the collision layers of a tile
+    ///
+    /// Walkable tiles have no layer; out of bounds is solid.
+    #[must_use]
+    pub fn tile_layers(&self, x: u32, y: u32) -> LayerMask {
+        if !self.is_tile_solid(x, y) {
+            return LayerMask::NONE;
+        }
+        let index = (y * self.width + x) as usize;
+        match self.collision_layers.get(index) {
+            Some(&layers) if !layers.is_empty() => layers,
+            _ => LayerMask::SOLID,
+        }
+    }
+
+    /// Get the world-space collision boxes (min, max) of a tile
+    ///
+    /// A tile without collision shapes is one box over its whole area.
+    /// Polygons collide as strips, see [`TileShape::collision_boxes`].
+    #[must_use]
+    pub fn tile_collision_boxes(&self, x: u32, y: u32) -> Vec<(Vec2, Vec2)> {
+        let size = Vec2::new(self.tile_width as f32, self.tile_height as f32);
+        let tile_min = Vec2::new(x as f32, y as f32) * size;
+
+        let shapes = if x < self.width && y < self.height {
+            self.collision_shapes.get((y * self.width + x) as usize)
+        } else {
+            None
+        };
+        match shapes {
+            Some(shapes) if !shapes.is_empty() => shapes
+                .iter()
+                .flat_map(TileShape::collision_boxes)
+                .map(|(min, max)| (tile_min + min, tile_min + max))
+                .collect(),
+            _ => vec![(tile_min, tile_min + size)],
+        }
+    }
+
     /// Get solid tiles that overlap with a world-space rectangle
-    /// Returns tile coordinates (x, y) and their world-space bounds (min, max)
+    /// Returns tile coordinates (x, y) and their world-space bounds (min, max),
+    /// once per collision box of tiles with sub-tile shapes
     #[must_use]
     pub fn get_solid_tiles_in_rect(&self, rect_min: Vec2, rect_max: Vec2) -> Vec<(u32, u32, Vec2, Vec2)> {
+        self.get_blocking_tiles_in_rect(rect_min, rect_max, LayerMask::ALL)
+    }
+
+    /// Get solid tiles on any of the `mask` layers that overlap with a world-space rectangle
+    /// Returns tile coordinates (x, y) and their world-space bounds (min, max),
+    /// once per collision box
+    #[must_use]
+    pub fn get_blocking_tiles_in_rect(
+        &self,
+        rect_min: Vec2,
+        rect_max: Vec2,
+        mask: LayerMask,
+    ) -> Vec<(u32, u32, Vec2, Vec2)> {
         if self.collision.is_empty() {
             return Vec::new();
         }
@@ -878,10 +1219,10 @@ impl Tilemap {
 
         for y in start_y..end_y {
             for x in start_x..end_x {
-                if self.is_tile_solid(x, y) {
-                    let tile_min = Vec2::new(x as f32 * tile_w, y as f32 * tile_h);
-                    let tile_max = Vec2::new(tile_min.x + tile_w, tile_min.y + tile_h);
-                    solid_tiles.push((x, y, tile_min, tile_max));
+                if self.tile_layers(x, y).intersects(mask) {
+                    for (min, max) in self.tile_collision_boxes(x, y) {
+                        solid_tiles.push((x, y, min, max));
+                    }
                 }
             }
         }
@@ -921,6 +1262,40 @@ impl Tilemap {
     }
 }
 
+impl TileGrid for Tilemap {
+    fn tile_size(&self) -> Vec2 {
+        Vec2::new(self.tile_width as f32, self.tile_height as f32)
+    }
+
+    fn grid_size(&self) -> (i32, i32) {
+        (self.width as i32, self.height as i32)
+    }
+
+    fn tile_layers(&self, x: i32, y: i32) -> LayerMask {
+        // Outside the map is solid, as for movement
+        match (u32::try_from(x), u32::try_from(y)) {
+            (Ok(x), Ok(y)) => Tilemap::tile_layers(self, x, y),
+            _ => LayerMask::SOLID,
+        }
+    }
+
+    fn tile_boxes(&self, x: i32, y: i32) -> Vec<AABB> {
+        match (u32::try_from(x), u32::try_from(y)) {
+            (Ok(x), Ok(y)) => self
+                .tile_collision_boxes(x, y)
+                .into_iter()
+                .map(|(min, max)| AABB { min, max })
+                .collect(),
+            _ => vec![AABB::new(
+                x as f32 * self.tile_width as f32,
+                y as f32 * self.tile_height as f32,
+                self.tile_width as f32,
+                self.tile_height as f32,
+            )],
+        }
+    }
+}
+
 /// Tilemap loading errors
 #[derive(Debug)]
 pub enum TilemapError {
@@ -1002,6 +1377,8 @@ mod tests {
                 true, false, true,
                 false, true, false,
             ],
+            collision_layers: vec![],
+            collision_shapes: vec![],
             spawns: vec![],
             triggers: vec![],
         };
@@ -1020,4 +1397,218 @@ mod tests {
         let solid = tilemap.get_solid_tiles_in_rect(Vec2::new(0.0, 0.0), Vec2::new(32.0, 32.0));
         assert_eq!(solid.len(), 2); // (1,0) and (0,1) are solid
     }
+
+    #[test]
+    fn test_tile_collision_layers() {
+        let tilemap = Tilemap {
+            name: "pond".to_string(),
+            width: 3,
+            height: 1,
+            tile_width: 16,
+            tile_height: 16,
+            tilesets: vec![],
+            layers: vec![],
+            collision: vec![true, true, false],
+            collision_layers: vec![LayerMask::NONE, LayerMask::WATER, LayerMask::NONE],
+            collision_shapes: vec![],
+            spawns: vec![],
+            triggers: vec![],
+        };
+
+        assert_eq!(tilemap.tile_layers(0, 0), LayerMask::SOLID);
+        assert_eq!(tilemap.tile_layers(1, 0), LayerMask::WATER);
+        assert_eq!(tilemap.tile_layers(2, 0), LayerMask::NONE);
+
+        // Walking is blocked by both; a cast only by the wall
+        let (min, max) = (Vec2::ZERO, Vec2::new(48.0, 16.0));
+        let walking = LayerMask::SOLID | LayerMask::WATER;
+        assert_eq!(tilemap.get_blocking_tiles_in_rect(min, max, walking).len(), 2);
+        let cast = tilemap.get_blocking_tiles_in_rect(min, max, LayerMask::SOLID);
+        assert_eq!(cast.len(), 1);
+        assert_eq!((cast[0].0, cast[0].1), (0, 0));
+    }
+
+    #[test]
+    fn test_tsx_collision_layer_property() {
+        let xml = r#"<tileset name="farm" tilewidth="16" tileheight="16">
+ <tile id="3">
+  <objectgroup draworder="index"><object id="1" x="0" y="0" width="16" height="16"/></objectgroup>
+ </tile>
+ <tile id="7">
+  <properties>
+   <property name="collision_layer" value="water"/>
+  </properties>
+  <objectgroup draworder="index"><object id="1" x="0" y="0" width="16" height="16"/></objectgroup>
+ </tile>
+ <tile id="9">
+  <properties><property name="footstep" value="wood"/></properties>
+ </tile>
+</tileset>"#;
+
+        let ids: Vec<_> = Tilemap::extract_collision_tiles(xml)
+            .into_iter()
+            .map(|tile| (tile.id, tile.layers))
+            .collect();
+        assert_eq!(ids, vec![(3, LayerMask::SOLID), (7, LayerMask::WATER)]);
+    }
+
+    #[test]
+    fn test_tsx_collision_shapes() {
+        let xml = r#"<tileset name="fences" tilewidth="16" tileheight="16">
+ <tile id="2">
+  <objectgroup draworder="index"><object id="1" x="0" y="8" width="16" height="8"/></objectgroup>
+ </tile>
+ <tile id="4">
+  <objectgroup draworder="index">
+   <object id="1" x="4" y="2">
+    <polygon points="0,0 8,0 8,12"/>
+   </object>
+   <object id="2" x="1" y="1"><point/></object>
+  </objectgroup>
+ </tile>
+ <tile id="5">
+  <objectgroup draworder="index">
+   <object id="1" x="2" y="2" width="12" height="12"><ellipse/></object>
+  </objectgroup>
+ </tile>
+</tileset>"#;
+
+        let tiles = Tilemap::extract_collision_tiles(xml);
+        let shapes: Vec<_> = tiles.iter().map(|tile| (tile.id, tile.shapes.clone())).collect();
+        assert_eq!(
+            shapes,
+            vec![
+                (2, vec![TileShape::Rect { x: 0.0, y: 8.0, width: 16.0, height: 8.0 }]),
+                (
+                    4,
+                    vec![TileShape::Polygon {
+                        points: vec![
+                            Vec2::new(4.0, 2.0),
+                            Vec2::new(12.0, 2.0),
+                            Vec2::new(12.0, 14.0),
+                        ],
+                    }],
+                ),
+                (5, vec![TileShape::Rect { x: 2.0, y: 2.0, width: 12.0, height: 12.0 }]),
+            ]
+        );
+        assert_eq!(tiles[1].shapes[0].bounds(), (Vec2::new(4.0, 2.0), Vec2::new(12.0, 14.0)));
+    }
+
+    #[test]
+    fn test_sub_tile_collision_boxes() {
+        // A half-wall next to a full wall
+        let tilemap = Tilemap {
+            name: "garden".to_string(),
+            width: 2,
+            height: 1,
+            tile_width: 16,
+            tile_height: 16,
+            tilesets: vec![],
+            layers: vec![],
+            collision: vec![true, true],
+            collision_layers: vec![],
+            collision_shapes: vec![
+                vec![TileShape::Rect { x: 0.0, y: 8.0, width: 16.0, height: 8.0 }],
+                vec![],
+            ],
+            spawns: vec![],
+            triggers: vec![],
+        };
+
+        let solid = tilemap.get_solid_tiles_in_rect(Vec2::ZERO, Vec2::new(32.0, 16.0));
+        assert_eq!(
+            solid,
+            vec![
+                (0, 0, Vec2::new(0.0, 8.0), Vec2::new(16.0, 16.0)),
+                (1, 0, Vec2::new(16.0, 0.0), Vec2::new(32.0, 16.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_polygon_collision_follows_the_outline() {
+        // A diagonal fence from the top-left to the bottom-right corner,
+        // solid above the diagonal
+        let diagonal = TileShape::Polygon {
+            points: vec![Vec2::ZERO, Vec2::new(16.0, 0.0), Vec2::new(16.0, 16.0)],
+        };
+        let tilemap = Tilemap {
+            name: "fence".to_string(),
+            width: 1,
+            height: 1,
+            tile_width: 16,
+            tile_height: 16,
+            tilesets: vec![],
+            layers: vec![],
+            collision: vec![true],
+            collision_layers: vec![],
+            collision_shapes: vec![vec![diagonal.clone()]],
+            spawns: vec![],
+            triggers: vec![],
+        };
+        let solid_at = |point: Vec2| {
+            tilemap
+                .tile_collision_boxes(0, 0)
+                .iter()
+                .any(|(min, max)| point.cmpgt(*min).all() && point.cmplt(*max).all())
+        };
+
+        // Inside the bounding box but below the diagonal
+        assert!(!solid_at(Vec2::new(2.0, 14.0)));
+        assert!(!solid_at(Vec2::new(7.0, 9.5)));
+        assert!(solid_at(Vec2::new(14.0, 3.0)));
+        assert!(solid_at(Vec2::new(9.5, 7.0)));
+        assert_eq!(tilemap.tile_collision_boxes(0, 0).len(), 8);
+
+        // A box can move through the open half
+        let mover = AABB::new(1.0, 13.0, 2.0, 2.0);
+        let delta = Vec2::new(8.0, 0.0);
+        let hit = engine_physics::shape_cast_tiles(&tilemap, &mover, delta, LayerMask::SOLID);
+        assert!(hit.is_none());
+
+        assert_eq!(diagonal.outline().len(), 3);
+
+        // Axis-aligned polygons stay one box
+        let square = TileShape::Polygon {
+            points: vec![
+                Vec2::new(4.0, 4.0),
+                Vec2::new(12.0, 4.0),
+                Vec2::new(12.0, 12.0),
+                Vec2::new(4.0, 12.0),
+            ],
+        };
+        assert_eq!(
+            square.collision_boxes(),
+            vec![(Vec2::new(4.0, 4.0), Vec2::new(12.0, 12.0))]
+        );
+    }
+
+    #[test]
+    fn test_embedded_tileset_collision_shapes() {
+        // Tile 2 is a fence post down the middle of the tile
+        let json = r#"{
+            "width": 2, "height": 1, "tilewidth": 16, "tileheight": 16,
+            "layers": [{
+                "type": "tilelayer", "name": "ground", "width": 2, "height": 1, "data": [1, 2]
+            }],
+            "tilesets": [{
+                "firstgid": 1, "name": "fences", "tilewidth": 16, "tileheight": 16,
+                "columns": 2, "tilecount": 2,
+                "tiles": [{"id": 1, "objectgroup": {"objects": [
+                    {"id": 1, "name": "", "x": 6, "y": 0, "width": 4, "height": 16}
+                ]}}]
+            }]
+        }"#;
+        let tiled: TiledMap = serde_json::from_str(json).unwrap();
+        let path = Path::new("maps/fence.json");
+        let tilemap = Tilemap::from_tiled_with_groups(tiled, path, "back", "front").unwrap();
+
+        assert_eq!(tilemap.collision, vec![false, true]);
+        assert_eq!(
+            tilemap.tile_collision_boxes(1, 0),
+            vec![(Vec2::new(22.0, 0.0), Vec2::new(26.0, 16.0))]
+        );
+        assert!(tilemap.get_solid_tiles_in_rect(Vec2::ZERO, Vec2::new(16.0, 16.0)).is_empty());
+    }
 }
@@ -2,9 +2,143 @@
 //!
 //! Components are pure data structs that can be attached to entities.
 
+use engine_ecs::{
+    impl_reflect, FieldInfo, FieldType, FieldValue, GlobalTransform, PrefabRegistry, Reflect,
+    ReflectError, ReflectValue, SnapshotRegistry, StorageType, Transform, TypeRegistry, World,
+};
+use engine_physics::{CharacterController, CollisionLayers, Momentum, RigidBody, Sensor, AABB};
 use engine_render::glam::Vec2;
 use serde::{Deserialize, Serialize};
 
+/// Register the components prefab files may use, under their type names
+pub fn register_prefab_components(registry: &mut PrefabRegistry) {
+    registry.register_component_with("Position", |def: PointDef| Position::new(def.x, def.y));
+    registry.register_component::<Velocity>("Velocity");
+    registry.register_component::<PlayerControlled>("PlayerControlled");
+    registry.register_component::<CameraTarget>("CameraTarget");
+    registry.register_component::<SpriteRender>("SpriteRender");
+    registry.register_component_with("Collider", |def: ColliderDef| {
+        Collider::new(def.width, def.height).with_layers(def.layers)
+    });
+    registry.register_component::<RigidBody>("RigidBody");
+    registry.register_component::<Momentum>("Momentum");
+    registry.register_component::<CharacterController>("CharacterController");
+    registry.register_component::<Sensor>("Sensor");
+    registry.register_component::<Transform>("Transform");
+}
+
+/// Register the components the inspector can list and edit
+pub fn register_reflected_components(registry: &mut TypeRegistry) {
+    registry.register::<Position>("Position");
+    registry.register::<Velocity>("Velocity");
+    registry.register::<PlayerControlled>("PlayerControlled");
+    registry.register::<CameraTarget>("CameraTarget");
+    registry.register::<SpriteRender>("SpriteRender");
+    registry.register::<Collider>("Collider");
+    registry.register::<RigidBody>("RigidBody");
+    registry.register::<Momentum>("Momentum");
+    registry.register::<CharacterController>("CharacterController");
+    registry.register::<Sensor>("Sensor");
+    registry.register::<MapTrigger>("MapTrigger");
+    registry.register::<Transform>("Transform");
+    registry.register::<GlobalTransform>("GlobalTransform");
+}
+
+/// Store the components movement iterates together in archetype tables
+pub fn configure_storage(world: &mut World) {
+    world.set_storage_type::<Position>(StorageType::Table);
+    world.set_storage_type::<Velocity>(StorageType::Table);
+    world.set_storage_type::<Collider>(StorageType::Table);
+}
+
+/// Register the components world snapshots capture
+pub fn register_snapshot_components(registry: &mut SnapshotRegistry) {
+    registry.register_component::<Position>("Position");
+    registry.register_component::<Velocity>("Velocity");
+    registry.register_component::<PlayerControlled>("PlayerControlled");
+    registry.register_component::<CameraTarget>("CameraTarget");
+    registry.register_component::<SpriteRender>("SpriteRender");
+    registry.register_component::<Collider>("Collider");
+    registry.register_component::<RigidBody>("RigidBody");
+    registry.register_component::<Momentum>("Momentum");
+    registry.register_component::<CharacterController>("CharacterController");
+    registry.register_component::<Sensor>("Sensor");
+    registry.register_component::<MapTrigger>("MapTrigger");
+    registry.register_component::<Transform>("Transform");
+}
+
+impl_reflect!(Position { current: Vec2, previous: Vec2 });
+impl_reflect!(Velocity { x: f32, y: f32 });
+impl_reflect!(PlayerControlled { speed: f32 });
+impl_reflect!(CameraTarget {});
+impl_reflect!(SpriteRender { width: f32, height: f32 });
+impl_reflect!(MapTrigger { target_map: String, target_spawn: String });
+
+/// Reflected with its collision layers flattened into `layers` and `mask`
+impl Reflect for Collider {
+    fn fields() -> &'static [FieldInfo] {
+        const FIELDS: &[FieldInfo] = &[
+            FieldInfo { name: "half_width", field_type: FieldType::Float },
+            FieldInfo { name: "half_height", field_type: FieldType::Float },
+            FieldInfo { name: "layers", field_type: FieldType::String },
+            FieldInfo { name: "mask", field_type: FieldType::String },
+        ];
+        FIELDS
+    }
+
+    fn field(&self, name: &str) -> Option<FieldValue> {
+        match name {
+            "half_width" => Some(self.half_width.to_field()),
+            "half_height" => Some(self.half_height.to_field()),
+            "layers" => Some(self.layers.layers.to_field()),
+            "mask" => Some(self.layers.mask.to_field()),
+            _ => None,
+        }
+    }
+
+    fn set_field(&mut self, name: &str, value: FieldValue) -> Result<(), ReflectError> {
+        match name {
+            "half_width" => set_reflected(&mut self.half_width, name, value),
+            "half_height" => set_reflected(&mut self.half_height, name, value),
+            "layers" => set_reflected(&mut self.layers.layers, name, value),
+            "mask" => set_reflected(&mut self.layers.mask, name, value),
+            _ => Err(ReflectError::UnknownField(name.to_string())),
+        }
+    }
+}
+
+/// Write a reflected value into a field, as [`impl_reflect!`] does
+fn set_reflected<T: ReflectValue>(
+    field: &mut T,
+    name: &str,
+    value: FieldValue,
+) -> Result<(), ReflectError> {
+    let found = value.field_type();
+    *field = T::from_field(value).ok_or_else(|| ReflectError::TypeMismatch {
+        field: name.to_string(),
+        expected: T::TYPE,
+        found,
+    })?;
+    Ok(())
+}
+
+/// Prefab format for positions
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PointDef {
+    x: f32,
+    y: f32,
+}
+
+/// Prefab format for colliders: full size, plus optional `layers`/`mask`
+#[derive(Debug, Deserialize)]
+struct ColliderDef {
+    width: f32,
+    height: f32,
+    #[serde(flatten)]
+    layers: CollisionLayers,
+}
+
 /// Position component with previous position for interpolation
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Position {
@@ -15,7 +149,6 @@ pub struct Position {
 impl Position {
     /// Create a new position at the given coordinates
     #[must_use]
-    #[allow(dead_code)]
     pub fn new(x: f32, y: f32) -> Self {
         let pos = Vec2::new(x, y);
         Self {
@@ -68,17 +201,33 @@ impl Velocity {
 }
 
 /// Marker component for player-controlled entities
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct PlayerControlled {
     /// Movement speed in pixels per second
     pub speed: f32,
+    /// Unit direction last moved in; down until the player first moves
+    #[serde(skip, default = "default_facing")]
+    pub facing: Vec2,
+}
+
+fn default_facing() -> Vec2 {
+    Vec2::Y
+}
+
+impl Default for PlayerControlled {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
 }
 
 impl PlayerControlled {
-    /// Create with given speed
+    /// Create with given speed, facing down
     #[must_use]
     pub fn new(speed: f32) -> Self {
-        Self { speed }
+        Self {
+            speed,
+            facing: default_facing(),
+        }
     }
 
     /// Set the movement speed
@@ -107,6 +256,7 @@ impl SpriteRender {
 
     /// Get size as Vec2
     #[must_use]
+    #[allow(dead_code)]
     pub fn size(&self) -> Vec2 {
         Vec2::new(self.width, self.height)
     }
@@ -118,6 +268,9 @@ pub struct Collider {
     /// Half-size of the collision box
     pub half_width: f32,
     pub half_height: f32,
+    /// Layers the collider is on and collides with
+    #[serde(default)]
+    pub layers: CollisionLayers,
 }
 
 impl Collider {
@@ -127,12 +280,171 @@ impl Collider {
         Self {
             half_width: width * 0.5,
             half_height: height * 0.5,
+            layers: CollisionLayers::default(),
         }
     }
 
+    /// Set the collision layers
+    #[must_use]
+    pub fn with_layers(mut self, layers: CollisionLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+
     /// Get half-size as Vec2
     #[must_use]
     pub fn half_size(&self) -> Vec2 {
         Vec2::new(self.half_width, self.half_height)
     }
+
+    /// Get the collision box centered on a position
+    #[must_use]
+    pub fn aabb(&self, center: Vec2) -> AABB {
+        AABB::from_center(center, self.half_size())
+    }
+}
+
+/// Map transition zone, spawned as a sensor from a map trigger
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapTrigger {
+    /// Map file to load
+    pub target_map: String,
+    /// Spawn point to place the player at
+    pub target_spawn: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine_physics::LayerMask;
+
+    #[test]
+    fn test_player_prefab() {
+        let mut registry = PrefabRegistry::new();
+        register_prefab_components(&mut registry);
+        registry
+            .load_from_str("player", include_str!("../../assets/data/prefabs/player.toml"))
+            .unwrap();
+
+        let mut world = World::new();
+        configure_storage(&mut world);
+        world.insert_resource(registry);
+        let player = world.spawn_prefab("player").unwrap();
+
+        assert!(world.has::<CameraTarget>(player));
+        assert!(world.has::<Transform>(player));
+        let body = world.get::<RigidBody>(player).unwrap();
+        assert!(body.is_dynamic());
+        assert_eq!((body.mass, body.drag), (1.0, 8.0));
+        assert_eq!(world.get::<CharacterController>(player).unwrap().corner_correction, 4.0);
+        assert_eq!(world.get::<PlayerControlled>(player).unwrap().speed, 120.0);
+        let collider = world.get::<Collider>(player).unwrap();
+        assert_eq!(collider.half_size(), Vec2::new(6.0, 4.0));
+        assert_eq!(collider.layers.layers, LayerMask::PLAYER);
+        assert!(collider.layers.collides_with(LayerMask::WATER));
+        assert!(!collider.layers.collides_with(LayerMask::TRIGGER | LayerMask::CROP));
+        assert_eq!(world.get::<Position>(player).unwrap().current, Vec2::ZERO);
+    }
+
+    #[test]
+    fn test_player_snapshot_roundtrip() {
+        let mut world = World::new();
+        configure_storage(&mut world);
+        let mut registry = SnapshotRegistry::new();
+        register_snapshot_components(&mut registry);
+        world.insert_resource(registry);
+
+        let player = world.spawn();
+        world.insert(player, Position::new(40.0, 24.0));
+        world.insert(player, PlayerControlled::new(120.0));
+        world.insert(player, Collider::new(12.0, 8.0));
+        world.insert(player, CameraTarget);
+
+        let snapshot = world.snapshot().unwrap();
+        world.despawn(player);
+        let map = world.restore(&snapshot).unwrap();
+
+        let player = map.get(player).unwrap();
+        assert_eq!(world.get::<Position>(player).unwrap().current, Vec2::new(40.0, 24.0));
+        assert_eq!(world.get::<PlayerControlled>(player).unwrap().speed, 120.0);
+        assert_eq!(world.get::<Collider>(player).unwrap().half_size(), Vec2::new(6.0, 4.0));
+        assert!(world.has::<CameraTarget>(player));
+    }
+
+    #[test]
+    fn test_reflected_components() {
+        let mut registry = TypeRegistry::new();
+        register_reflected_components(&mut registry);
+
+        let mut world = World::new();
+        let player = world.spawn();
+        world.insert(player, Position::new(1.0, 2.0));
+        world.insert(player, PlayerControlled::new(80.0));
+
+        let names: Vec<_> = registry.components_of(&world, player).map(|r| r.name()).collect();
+        assert_eq!(names, ["Position", "PlayerControlled"]);
+
+        registry
+            .set_field(&mut world, player, "PlayerControlled", "speed", FieldValue::Float(150.0))
+            .unwrap();
+        assert_eq!(world.get::<PlayerControlled>(player).unwrap().speed, 150.0);
+    }
+
+    #[test]
+    fn test_reflected_physics_components() {
+        let mut registry = TypeRegistry::new();
+        register_reflected_components(&mut registry);
+
+        let mut world = World::new();
+        let crate_box = world.spawn();
+        world.insert(crate_box, Collider::new(16.0, 16.0));
+        world.insert(crate_box, RigidBody::dynamic());
+        world.insert(crate_box, Momentum::default());
+        world.insert(crate_box, CharacterController::default());
+        world.insert(crate_box, Sensor);
+        world.insert(crate_box, MapTrigger {
+            target_map: "farm.json".to_string(),
+            target_spawn: "gate".to_string(),
+        });
+
+        let names: Vec<_> = registry.components_of(&world, crate_box).map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            [
+                "Collider",
+                "RigidBody",
+                "Momentum",
+                "CharacterController",
+                "Sensor",
+                "MapTrigger"
+            ]
+        );
+
+        let collider = registry.get("Collider").unwrap().values(&world, crate_box).unwrap();
+        let layers: Vec<_> = collider.iter().map(|(info, value)| (info.name, value)).collect();
+        assert_eq!(layers[2], ("layers", &FieldValue::String("solid".into())));
+        assert_eq!(layers[3], ("mask", &FieldValue::String("all".into())));
+
+        let edits = [
+            ("Collider", "mask", FieldValue::String("player, npc".into())),
+            ("RigidBody", "body_type", FieldValue::String("static".into())),
+            ("Momentum", "velocity", FieldValue::Vec2(Vec2::new(3.0, 0.0))),
+            ("CharacterController", "corner_correction", FieldValue::Float(2.0)),
+            ("MapTrigger", "target_spawn", FieldValue::String("door".into())),
+        ];
+        for (component, field, value) in edits {
+            registry.set_field(&mut world, crate_box, component, field, value).unwrap();
+        }
+        let collider = world.get::<Collider>(crate_box).unwrap();
+        assert_eq!(collider.layers.mask, LayerMask::PLAYER | LayerMask::NPC);
+        assert!(!world.get::<RigidBody>(crate_box).unwrap().is_dynamic());
+        assert_eq!(world.get::<Momentum>(crate_box).unwrap().velocity, Vec2::new(3.0, 0.0));
+        assert_eq!(world.get::<CharacterController>(crate_box).unwrap().corner_correction, 2.0);
+        assert_eq!(world.get::<MapTrigger>(crate_box).unwrap().target_spawn, "door");
+
+        let unknown_layer = FieldValue::String("lava".into());
+        assert!(registry
+            .set_field(&mut world, crate_box, "Collider", "layers", unknown_layer)
+            .is_err());
+    }
 }
@@ -0,0 +1,10 @@
+//! Game events sent through the ECS `Events<T>` channels
+
+/// The player stepped on a map transition trigger
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapTransition {
+    /// Map file to load
+    pub target_map: String,
+    /// Spawn point to place the player at
+    pub target_spawn: String,
+}
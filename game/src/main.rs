@@ -4,20 +4,30 @@
 
 mod components;
 mod dialogue;
+mod events;
 mod farming;
 mod inventory;
 mod items;
 mod menu;
 mod npc;
+mod pathfinding;
 mod player;
+#[cfg(test)]
+mod replay;
 mod save;
 mod systems;
+#[cfg(test)]
+mod test_map;
+mod world_graph;
 
 use std::sync::Arc;
 
 use anyhow::Result;
 use engine_core::{GameSettings, GameTime};
-use engine_ecs::{Entity, World};
+use engine_ecs::{
+    Entity, EventReader, Events, PrefabRegistry, Schedule, SnapshotRegistry, Stage, TypeRegistry,
+    With, World,
+};
 use engine_input::{Input, KeyCode};
 use engine_render::{glam, glam::Vec2, wgpu, Camera2D, Renderer, Sprite, Texture, Tilemap};
 use engine_ui::{Hud, Menu, MenuItem, SettingsMenu};
@@ -28,12 +38,18 @@ use winit::window::Window as WinitWindow;
 #[cfg(feature = "debug-tools")]
 use engine_debug::{ConsoleCommand, DebugOverlay, EguiRenderer};
 
-use components::{CameraTarget, Collider, PlayerControlled, Position, SpriteRender, Velocity};
+use components::{
+    configure_storage, register_prefab_components, register_reflected_components,
+    register_snapshot_components, Collider, PlayerControlled, Position, SpriteRender, Velocity,
+};
+use events::MapTransition;
 use inventory::Inventory;
 use menu::{GameState, PreviousState};
+use npc::{Npc, NpcDatabase};
 use player::{load_player_animator, CharacterAnimator};
 use save::{GameClockData, PlayerData, SaveData, SaveManager};
-use systems::{camera_system, input_system, movement_system};
+use systems::{create_schedule, init_sensors, init_spatial_grid, spawn_map_triggers, DeltaTime};
+use world_graph::{simulate_offscreen, WorldGraph};
 
 /// The main game application
 struct Game {
@@ -52,8 +68,16 @@ struct Game {
     settings: GameSettings,
     /// ECS World containing all entities and components
     world: World,
+    /// Systems run on the world each frame, by stage
+    schedule: Schedule,
+    /// Cursor into the MapTransition events sent by map_trigger_system
+    map_transitions: EventReader<MapTransition>,
     /// Reference to the player entity
     player_entity: Option<Entity>,
+    /// Maps loaded so far and the maps their triggers lead to, for NPC routes
+    world_graph: WorldGraph,
+    /// NPCs spawned from their definitions, on whichever map they are
+    npcs: Vec<Npc>,
     // Renderer (not in ECS as it needs special handling)
     renderer: Option<Renderer>,
     // Tileset textures and bind groups (one per tileset, indexed by tileset order)
@@ -111,10 +135,51 @@ impl Game {
 
     fn new() -> Self {
         let mut world = World::new();
+        configure_storage(&mut world);
+        init_spatial_grid(&mut world);
+        init_sensors(&mut world);
 
         // Insert Input as a resource
         world.insert_resource(Input::new());
 
+        // Register game events
+        world.add_event::<MapTransition>();
+
+        // Load entity prefabs
+        let mut prefabs = PrefabRegistry::new();
+        register_prefab_components(&mut prefabs);
+        match prefabs.load_dir("assets/data/prefabs") {
+            Ok(count) => info!("Loaded {} prefabs", count),
+            Err(e) => error!("Failed to load prefabs: {}", e),
+        }
+        world.insert_resource(prefabs);
+
+        // Register what world snapshots capture
+        let mut snapshots = SnapshotRegistry::new();
+        register_snapshot_components(&mut snapshots);
+        snapshots.register_resource::<Inventory>("Inventory");
+        world.insert_resource(snapshots);
+
+        // Register components for the inspector
+        let mut types = TypeRegistry::new();
+        register_reflected_components(&mut types);
+        world.insert_resource(types);
+
+        // Spawn NPCs from their definitions, in a stable order
+        let mut npc_database = NpcDatabase::new();
+        let npcs: Vec<Npc> = match npc_database.load_from_file("assets/data/npcs.toml") {
+            Ok(count) => {
+                info!("Loaded {} NPCs", count);
+                let mut ids = npc_database.all_ids();
+                ids.sort_unstable();
+                ids.into_iter().filter_map(|id| npc_database.create_npc(id)).collect()
+            }
+            Err(e) => {
+                error!("Failed to load NPCs: {}", e);
+                Vec::new()
+            }
+        };
+
         // Load settings (or use defaults)
         let settings = GameSettings::load();
         let settings_menu = Self::create_settings_menu(&settings);
@@ -128,7 +193,11 @@ impl Game {
             settings_menu,
             settings,
             world,
+            schedule: create_schedule(),
+            map_transitions: EventReader::new(),
             player_entity: None,
+            world_graph: WorldGraph::new(),
+            npcs,
             renderer: None,
             tileset_textures: Vec::new(),
             player_animator: None,
@@ -186,8 +255,10 @@ impl Game {
                     camera.set_position(spawn_pos);
                 }
 
-                // Update tilemap resource
+                // Update tilemap resource and its trigger sensors
+                self.world_graph.add_connected(map_path, &tilemap);
                 self.world.insert_resource(tilemap);
+                spawn_map_triggers(&mut self.world);
 
                 // Store current map path for save system
                 self.current_map = map_path.to_string();
@@ -252,12 +323,16 @@ impl Game {
             .cloned()
             .unwrap_or_default();
 
-        let save_data = SaveData::new(
+        let mut save_data = SaveData::new(
             player_data,
             game_clock_data,
             self.current_map.clone(),
             inventory,
         );
+        match self.world.snapshot() {
+            Ok(snapshot) => save_data = save_data.with_world(snapshot),
+            Err(e) => error!("Failed to snapshot world: {}", e),
+        }
 
         match self.save_manager.save(0, &save_data) {
             Ok(()) => info!("Game saved successfully!"),
@@ -275,6 +350,20 @@ impl Game {
             }
         };
 
+        // Restore every entity when the save has a world snapshot
+        if let Some(snapshot) = &save_data.world {
+            match self.world.restore(snapshot) {
+                Ok(_) => {
+                    self.player_entity = self
+                        .world
+                        .query::<&PlayerControlled>()
+                        .next()
+                        .map(|(entity, _)| entity);
+                }
+                Err(e) => error!("Failed to restore world: {}", e),
+            }
+        }
+
         // Restore player state
         if let Some(entity) = self.player_entity {
             if let Some(pos) = self.world.get_mut::<Position>(entity) {
@@ -287,7 +376,7 @@ impl Game {
                 *sprite = save_data.player.to_sprite_render();
             }
             if let Some(collider) = self.world.get_mut::<Collider>(entity) {
-                *collider = save_data.player.to_collider();
+                *collider = save_data.player.to_collider().with_layers(collider.layers);
             }
 
             // Snap camera to loaded position
@@ -496,6 +585,34 @@ impl Game {
         }
     }
 
+    /// Write back fields edited in the ECS inspector
+    #[cfg(feature = "debug-tools")]
+    fn apply_inspector_edits(&mut self) {
+        let edits = self.debug_overlay.take_pending_edits();
+        if edits.is_empty() {
+            return;
+        }
+
+        let Some(types) = self.world.remove_resource::<TypeRegistry>() else {
+            return;
+        };
+        for edit in edits {
+            if !self.world.is_alive(edit.entity) {
+                continue;
+            }
+            if let Err(e) = types.set_field(
+                &mut self.world,
+                edit.entity,
+                &edit.component,
+                &edit.field,
+                edit.value,
+            ) {
+                log::warn!("Inspector edit of {}.{} failed: {}", edit.component, edit.field, e);
+            }
+        }
+        self.world.insert_resource(types);
+    }
+
     /// Process pending console commands
     #[cfg(feature = "debug-tools")]
     fn process_console_commands(&mut self) {
@@ -549,7 +666,7 @@ impl Game {
                 }
                 ConsoleCommand::ListEntities => {
                     let mut count = 0;
-                    for (entity, pos) in self.world.query::<Position>() {
+                    for (entity, pos) in self.world.query::<&Position>() {
                         let name = if Some(entity) == self.player_entity {
                             "Player"
                         } else {
@@ -582,6 +699,16 @@ impl App for Game {
         // Store window reference for egui
         self.window = Some(Arc::clone(&window));
 
+        // Report system ordering mistakes before the first frame
+        if let Err(e) = self.schedule.build() {
+            error!("Invalid system schedule: {}", e);
+        }
+        for stage in Stage::ALL {
+            for conflict in self.schedule.conflicts(stage).unwrap_or_default() {
+                log::debug!("{:?}: {}", stage, conflict);
+            }
+        }
+
         // Create renderer
         let renderer = pollster::block_on(Renderer::new(Arc::clone(&window)));
         let size = renderer.size();
@@ -625,8 +752,10 @@ impl App for Game {
                 #[cfg(feature = "debug-tools")]
                 self.debug_overlay.log_system(0.0, format!("Map loaded: test.json ({}x{}) - Tiled format", w, h));
 
-                // Store tilemap as resource
+                // Store tilemap as resource and spawn its trigger sensors
+                self.world_graph.add_connected("game/assets/Tilesets/test.json", &tilemap);
                 self.world.insert_resource(tilemap);
+                spawn_map_triggers(&mut self.world);
 
                 // Store current map for save system
                 self.current_map = "game/assets/Tilesets/test.json".to_string();
@@ -671,14 +800,15 @@ impl App for Game {
             }
         };
 
-        // Create player entity with components
-        let player = self.world.spawn();
+        // Create player entity from its prefab
+        let player = self.world.spawn_prefab("player").unwrap_or_else(|e| {
+            error!("Failed to spawn player prefab: {}", e);
+            self.world.spawn()
+        });
         self.world.insert(player, Position::from_vec2(player_start));
-        self.world.insert(player, Velocity::default());
-        self.world.insert(player, PlayerControlled::new(walk_speed));
-        self.world.insert(player, CameraTarget);
-        self.world.insert(player, SpriteRender::new(32.0, 32.0));
-        self.world.insert(player, Collider::new(12.0, 8.0)); // Small hitbox at feet
+        if let Some(controlled) = self.world.get_mut::<PlayerControlled>(player) {
+            controlled.set_speed(walk_speed);
+        }
         self.player_entity = Some(player);
 
         // Initialize camera as resource
@@ -695,6 +825,10 @@ impl App for Game {
     fn update(&mut self) {
         self.game_time.update();
 
+        // Start a new change-detection frame for code outside the schedule
+        self.world.clear_trackers();
+        self.world.update_events();
+
         // Handle state-specific updates
         match self.game_state {
             GameState::MainMenu => {
@@ -815,40 +949,43 @@ impl App for Game {
 
             // Process console commands
             self.process_console_commands();
+            self.apply_inspector_edits();
         }
 
+        let dt = self.game_time.delta as f32;
+        self.world.insert_resource(DeltaTime(dt));
+        self.schedule.run_stage(Stage::PreUpdate, &mut self.world);
+
         // Fixed timestep updates
+        let step = self.game_time.fixed_timestep() as f32;
         while self.game_time.should_fixed_update() {
-            // Run ECS systems
-            input_system(&mut self.world);
-            movement_system(&mut self.world);
-        }
+            self.schedule.run_stage(Stage::FixedUpdate, &mut self.world);
 
-        // Check for map transition triggers
-        let transition: Option<(String, String)> = {
-            let player_pos = self.get_player_position();
-            if let (Some(tilemap), Some(pos)) =
-                (self.world.get_resource::<Tilemap>(), player_pos)
-            {
-                tilemap
-                    .check_trigger(pos)
-                    .map(|t| (t.target_map.clone(), t.target_spawn.clone()))
-            } else {
-                None
+            // NPCs on other maps keep walking their routes
+            for id in simulate_offscreen(&mut self.npcs, &self.current_map, step) {
+                info!("NPC '{}' walked onto {}", id, self.current_map);
             }
-        };
-        if let Some((map_path, spawn_id)) = transition {
+        }
+
+        // Per-frame systems (map triggers send MapTransition events)
+        self.schedule.run_stage(Stage::Update, &mut self.world);
+
+        // Handle map transitions
+        let transition = self
+            .world
+            .get_resource::<Events<MapTransition>>()
+            .and_then(|events| self.map_transitions.read(events).last().cloned());
+        if let Some(transition) = transition {
             #[cfg(feature = "debug-tools")]
             self.debug_overlay.log_game(
                 self.game_time.total_time(),
-                format!("Map transition: {} -> {}", map_path, spawn_id),
+                format!("Map transition: {} -> {}", transition.target_map, transition.target_spawn),
             );
-            self.load_map(&map_path, &spawn_id);
+            self.load_map(&transition.target_map, &transition.target_spawn);
         }
 
-        // Update camera to follow player
-        let dt = self.game_time.delta as f32;
-        camera_system(&mut self.world, dt);
+        // Camera follows player
+        self.schedule.run_stage(Stage::PostUpdate, &mut self.world);
 
         // Update player animator based on ECS velocity
         if let (Some(entity), Some(animator)) = (self.player_entity, &mut self.player_animator) {
@@ -867,6 +1004,8 @@ impl App for Game {
             animator.update_state(vx, vy, is_running);
             animator.update(dt);
         }
+
+        self.schedule.run_stage(Stage::RenderPrep, &mut self.world);
     }
 
     fn render(&mut self) {
@@ -893,21 +1032,19 @@ impl App for Game {
                 self.debug_overlay.set_collision_data(view_matrix, (size.0 as f32, size.1 as f32));
 
                 // Add entity collision boxes
-                for (entity, collider) in self.world.query::<Collider>() {
-                    if let Some(pos) = self.world.get::<Position>(entity) {
-                        let half = collider.half_size();
-                        let min = pos.current - half;
-                        let max = pos.current + half;
-
-                        // Player is green, others are cyan
-                        let color = if Some(entity) == self.player_entity {
-                            engine_debug::DebugColor::GREEN
-                        } else {
-                            engine_debug::DebugColor::LIGHT_BLUE
-                        };
+                for (entity, (collider, pos)) in self.world.query::<(&Collider, &Position)>() {
+                    let half = collider.half_size();
+                    let min = pos.current - half;
+                    let max = pos.current + half;
 
-                        self.debug_overlay.add_entity_box(min, max, color);
-                    }
+                    // Player is green, others are cyan
+                    let color = if Some(entity) == self.player_entity {
+                        engine_debug::DebugColor::GREEN
+                    } else {
+                        engine_debug::DebugColor::LIGHT_BLUE
+                    };
+
+                    self.debug_overlay.add_entity_box(min, max, color);
                 }
 
                 // Add tile collision boxes from tilemap (visible tiles only)
@@ -930,9 +1067,22 @@ impl App for Game {
                             for tx in start_x..end_x {
                                 let idx = (ty * map_w + tx) as usize;
                                 if idx < tilemap.collision.len() && tilemap.collision[idx] {
-                                    let min = Vec2::new(tx as f32 * tile_w, ty as f32 * tile_h);
-                                    let max = min + Vec2::new(tile_w, tile_h);
-                                    self.debug_overlay.add_tile_box(min, max);
+                                    // Sub-tile shapes, as collisions see them
+                                    let boxes = tilemap.tile_collision_boxes(tx as u32, ty as u32);
+                                    for (min, max) in boxes {
+                                        self.debug_overlay.add_tile_box(min, max);
+                                    }
+
+                                    // Polygons collide as strips; outline their real shape too
+                                    let origin = Vec2::new(tx as f32 * tile_w, ty as f32 * tile_h);
+                                    let shapes = tilemap.collision_shapes.get(idx);
+                                    for shape in shapes.into_iter().flatten() {
+                                        use engine_render::TileShape;
+                                        if let TileShape::Polygon { points } = shape {
+                                            let outline = points.iter().map(|&p| origin + p);
+                                            self.debug_overlay.add_tile_outline(outline.collect());
+                                        }
+                                    }
                                 }
                             }
                         }
@@ -958,7 +1108,7 @@ impl App for Game {
                 self.debug_overlay.set_zorder_data(view_matrix, (size.0 as f32, size.1 as f32));
 
                 // Add entity z-order labels (z = y position for y-sorting)
-                for (entity, pos) in self.world.query::<Position>() {
+                for (entity, pos) in self.world.query::<&Position>() {
                     let z_order = pos.current.y;
                     let label = if Some(entity) == self.player_entity {
                         "Player"
@@ -977,7 +1127,7 @@ impl App for Game {
                 if let Some(tilemap) = self.world.get_resource::<Tilemap>() {
                     let below_indices = tilemap.below_layers();
                     let above_indices = tilemap.above_layers();
-                    let entity_count = self.world.query::<Position>().count();
+                    let entity_count = self.world.query::<&Position>().count();
 
                     let mut layer_index = 0;
 
@@ -1030,61 +1180,35 @@ impl App for Game {
             let mut entity_count = 0;
             let mut component_count = 0;
 
-            // Collect entity data
-            for (entity, pos) in self.world.query::<Position>() {
-                entity_count += 1;
-
-                let name = if Some(entity) == self.player_entity {
-                    "Player"
-                } else {
-                    "Entity"
-                };
-
-                let mut entity_info = engine_debug::EntityInfo::new(entity.index, name);
-
-                // Add Position component
-                entity_info.add_component(engine_debug::ComponentInfo::vec2(
-                    "Position",
-                    pos.current.x,
-                    pos.current.y,
-                    false,
-                ));
-                component_count += 1;
-
-                // Add Velocity if present
-                if let Some(vel) = self.world.get::<Velocity>(entity) {
-                    entity_info.add_component(engine_debug::ComponentInfo::vec2(
-                        "Velocity",
-                        vel.x,
-                        vel.y,
-                        false,
-                    ));
-                    component_count += 1;
-                }
+            // Collect entity data from every registered component
+            if let Some(types) = self.world.get_resource::<TypeRegistry>() {
+                for entity in self.world.entities() {
+                    entity_count += 1;
 
-                // Add Collider if present
-                if let Some(collider) = self.world.get::<Collider>(entity) {
-                    let half = collider.half_size();
-                    entity_info.add_component(engine_debug::ComponentInfo::size(
-                        "Collider",
-                        half.x * 2.0,
-                        half.y * 2.0,
-                    ));
-                    component_count += 1;
-                }
+                    let name = if Some(entity) == self.player_entity {
+                        "Player"
+                    } else {
+                        "Entity"
+                    };
 
-                // Add SpriteRender if present
-                if let Some(sprite) = self.world.get::<SpriteRender>(entity) {
-                    let size = sprite.size();
-                    entity_info.add_component(engine_debug::ComponentInfo::size(
-                        "SpriteRender",
-                        size.x,
-                        size.y,
-                    ));
-                    component_count += 1;
-                }
+                    let mut entity_info = engine_debug::EntityInfo::new(entity, name);
+                    for registration in types.components_of(&self.world, entity) {
+                        let fields = registration
+                            .values(&self.world, entity)
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|(info, value)| (info.name.to_string(), value))
+                            .collect();
+                        entity_info.add_component(engine_debug::ComponentInfo::fields(
+                            registration.name(),
+                            fields,
+                            true,
+                        ));
+                        component_count += 1;
+                    }
 
-                self.debug_overlay.add_entity(entity_info);
+                    self.debug_overlay.add_entity(entity_info);
+                }
             }
 
             self.debug_overlay.set_ecs_stats(entity_count, component_count);
@@ -1126,19 +1250,10 @@ impl App for Game {
                         }
                         GameState::Playing | GameState::Paused => {
                             // Get camera and tilemap for rendering
-                            let camera_ptr = self
-                                .world
-                                .get_resource::<Camera2D>()
-                                .map(|c| c as *const Camera2D);
-                            let tilemap_ptr = self
-                                .world
-                                .get_resource::<Tilemap>()
-                                .map(|t| t as *const Tilemap);
-
-                            if let (Some(tm_ptr), Some(cam_ptr)) = (tilemap_ptr, camera_ptr) {
-                                // Safety: we're only reading, and these are borrowed from world
-                                let tilemap = unsafe { &*tm_ptr };
-                                let camera = unsafe { &*cam_ptr };
+                            if let (Some(tilemap), Some(camera)) = (
+                                self.world.get_resource::<Tilemap>(),
+                                self.world.get_resource::<Camera2D>(),
+                            ) {
 
                                 // 1. Render layers BELOW entities (ground, decorations)
                                 // Process layer by layer to maintain correct z-order
@@ -1172,24 +1287,22 @@ impl App for Game {
                                 // 2. Render player with animation texture in SEPARATE batch
                                 if let Some(animator) = &self.player_animator {
                                     let alpha = self.game_time.alpha() as f32;
-                                    for (entity, _sprite_render) in self.world.query::<SpriteRender>() {
-                                        if let Some(pos) = self.world.get::<Position>(entity) {
-                                            let render_pos = pos.interpolated(alpha);
-                                            let frame_size = animator.frame_size() as f32;
-
-                                            // Create sprite with animation region
-                                            let mut sprite = Sprite::new(render_pos, Vec2::new(frame_size, frame_size));
-                                            if let Some(region) = animator.current_region() {
-                                                sprite.region = region;
-                                            }
-
-                                            // Handle horizontal flip for left-facing direction
-                                            if animator.flip_x {
-                                                std::mem::swap(&mut sprite.region.u_min, &mut sprite.region.u_max);
-                                            }
+                                    for (_, pos) in self.world.query_filtered::<&Position, With<SpriteRender>>() {
+                                        let render_pos = pos.interpolated(alpha);
+                                        let frame_size = animator.frame_size() as f32;
+
+                                        // Create sprite with animation region
+                                        let mut sprite = Sprite::new(render_pos, Vec2::new(frame_size, frame_size));
+                                        if let Some(region) = animator.current_region() {
+                                            sprite.region = region;
+                                        }
 
-                                            renderer.draw_sprite(&sprite);
+                                        // Handle horizontal flip for left-facing direction
+                                        if animator.flip_x {
+                                            std::mem::swap(&mut sprite.region.u_min, &mut sprite.region.u_max);
                                         }
+
+                                        renderer.draw_sprite(&sprite);
                                     }
 
                                     // Get the correct texture based on player state
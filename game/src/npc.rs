@@ -4,10 +4,14 @@
 
 #![allow(dead_code)]
 
+use engine_render::glam::Vec2;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::path::Path as FsPath;
 
+use crate::pathfinding::Pathfinder;
+use crate::world_graph::{RouteLeg, WorldGraph};
+
 /// NPC behavior state
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -232,6 +236,15 @@ pub struct Npc {
     /// Known locations for this NPC
     #[serde(default)]
     pub locations: HashMap<String, Position2D>,
+    /// Map the NPC is on
+    #[serde(default)]
+    pub map: String,
+    /// Maps of the locations that aren't on the NPC's own map
+    #[serde(default)]
+    pub location_maps: HashMap<String, String>,
+    /// Legs left to walk on other maps after the current path
+    #[serde(skip)]
+    pub route: VecDeque<RouteLeg>,
     /// Dialogue ID for conversation
     #[serde(default)]
     pub dialogue_id: Option<String>,
@@ -255,6 +268,9 @@ impl Npc {
             schedule: Schedule::new(),
             path: None,
             locations: HashMap::new(),
+            map: String::new(),
+            location_maps: HashMap::new(),
+            route: VecDeque::new(),
             dialogue_id: None,
         }
     }
@@ -293,6 +309,106 @@ impl Npc {
         self.state = NpcState::Walking;
     }
 
+    /// Start moving to a location by name, on a path around obstacles
+    ///
+    /// Returns false if the location is unknown or can't be reached.
+    pub fn path_to(&mut self, location_name: &str, pathfinder: &mut Pathfinder) -> bool {
+        match self.locations.get(location_name).copied() {
+            Some(target) => self.path_to_position(target.x, target.y, pathfinder),
+            None => false,
+        }
+    }
+
+    /// Start moving to a position on a path around obstacles
+    ///
+    /// Returns false if the position can't be reached.
+    pub fn path_to_position(&mut self, x: f32, y: f32, pathfinder: &mut Pathfinder) -> bool {
+        let start = Vec2::new(self.position.x, self.position.y);
+        match pathfinder.find_path(start, Vec2::new(x, y)) {
+            Some(waypoints) => {
+                self.path = Some(NpcPath::new(waypoints));
+                self.state = NpcState::Walking;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Start moving to a location by name, possibly on another map
+    ///
+    /// Returns false if the location is unknown or can't be reached.
+    pub fn travel_to(&mut self, location_name: &str, world: &mut WorldGraph) -> bool {
+        let Some(target) = self.locations.get(location_name).copied() else {
+            return false;
+        };
+        let target_map = self.location_maps.get(location_name).unwrap_or(&self.map).clone();
+        let start = Vec2::new(self.position.x, self.position.y);
+        let Some(legs) = world.route(&self.map, start, &target_map, Vec2::new(target.x, target.y))
+        else {
+            return false;
+        };
+
+        self.route = legs.into();
+        self.path = None;
+        self.state = NpcState::Walking;
+        self.finish_path();
+        true
+    }
+
+    /// Move on to the next leg of the route, or stop
+    /// Returns true if there was no leg left
+    fn finish_path(&mut self) -> bool {
+        match self.route.pop_front() {
+            Some(leg) => {
+                self.map = leg.map;
+                self.position = Position2D::new(leg.start.x, leg.start.y);
+                self.path = Some(NpcPath::new(leg.waypoints));
+                false
+            }
+            None => {
+                self.state = NpcState::Idle;
+                self.path = None;
+                true
+            }
+        }
+    }
+
+    /// Move along the path and route for `seconds` at once, for NPCs that
+    /// aren't on the loaded map
+    /// Returns true if reached destination
+    pub fn fast_forward(&mut self, seconds: f32) -> bool {
+        let mut remaining = self.speed * seconds;
+
+        while self.state == NpcState::Walking {
+            let Some(waypoint) = self.path.as_ref().and_then(NpcPath::current).copied() else {
+                if self.finish_path() {
+                    return true;
+                }
+                continue;
+            };
+
+            let dx = waypoint.x - self.position.x;
+            let dy = waypoint.y - self.position.y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance > 0.0 {
+                self.direction = Direction::from_velocity(dx, dy);
+            }
+            if distance > remaining {
+                self.position.x += dx / distance * remaining;
+                self.position.y += dy / distance * remaining;
+                return false;
+            }
+
+            remaining -= distance;
+            self.position = Position2D::new(waypoint.x, waypoint.y);
+            if let Some(path) = &mut self.path {
+                path.advance();
+            }
+        }
+
+        false
+    }
+
     /// Update NPC movement
     /// Returns true if reached destination
     pub fn update_movement(&mut self, dt: f32) -> bool {
@@ -310,11 +426,7 @@ impl Npc {
 
         let waypoint = match path.current() {
             Some(w) => *w,
-            None => {
-                self.state = NpcState::Idle;
-                self.path = None;
-                return true;
-            }
+            None => return self.finish_path(),
         };
 
         let dx = waypoint.x - self.position.x;
@@ -326,9 +438,7 @@ impl Npc {
             // Reached waypoint
             path.advance();
             if path.is_complete() {
-                self.state = NpcState::Idle;
-                self.path = None;
-                return true;
+                return self.finish_path();
             }
         } else {
             // Move towards waypoint
@@ -396,6 +506,12 @@ pub struct NpcDefinition {
     pub dialogue_id: Option<String>,
     #[serde(default)]
     pub locations: HashMap<String, [f32; 2]>,
+    /// Map the NPC starts on
+    #[serde(default)]
+    pub map: Option<String>,
+    /// Maps of locations that aren't on the starting map
+    #[serde(default)]
+    pub location_maps: HashMap<String, String>,
     #[serde(default)]
     pub schedule: Vec<ScheduleEntryDef>,
 }
@@ -477,6 +593,8 @@ impl NpcDatabase {
         for (name, pos) in &def.locations {
             npc.add_location(name, pos[0], pos[1]);
         }
+        npc.map = def.map.clone().unwrap_or_default();
+        npc.location_maps = def.location_maps.clone();
 
         // Build schedule
         for entry in &def.schedule {
@@ -0,0 +1,517 @@
+//! A* pathfinding on the tilemap collision grid
+//!
+//! [`NavGrid`] marks the tiles NPCs can't walk on, from the tilemap's
+//! collision and from static entity obstacles. [`Pathfinder`] runs A* over
+//! it, smooths the tile path into straight runs and caches tile paths until
+//! the grid changes.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use engine_ecs::{Without, World};
+use engine_physics::{shape_cast_tiles, LayerMask, RigidBody, Sensor, TileGrid, AABB};
+use engine_render::glam::Vec2;
+use engine_render::Tilemap;
+
+use crate::components::{Collider, Position};
+use crate::npc::Waypoint;
+
+/// Tile coordinates
+pub type Tile = (i32, i32);
+
+/// Tile layers NPCs can't walk through
+pub const NPC_BLOCKING: LayerMask = LayerMask(LayerMask::SOLID.0 | LayerMask::WATER.0);
+
+/// Cost of a straight step; diagonal steps cost `DIAGONAL` (about √2 times)
+const STRAIGHT: u32 = 10;
+const DIAGONAL: u32 = 14;
+
+/// Cached tile paths kept before the cache is emptied
+const MAX_CACHED_PATHS: usize = 256;
+
+/// Walkable tiles of a map
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavGrid {
+    width: i32,
+    height: i32,
+    tile_size: Vec2,
+    /// Tiles blocked by the map (row-major order)
+    tiles: Vec<bool>,
+    /// Tiles blocked by entity obstacles (row-major order)
+    obstacles: Vec<bool>,
+}
+
+impl NavGrid {
+    /// Create a grid where every tile is walkable
+    #[must_use]
+    pub fn new(width: u32, height: u32, tile_size: Vec2) -> Self {
+        let size = (width * height) as usize;
+        Self {
+            width: width as i32,
+            height: height as i32,
+            tile_size,
+            tiles: vec![false; size],
+            obstacles: vec![false; size],
+        }
+    }
+
+    /// Create a grid blocking the tiles on any of the `mask` layers
+    ///
+    /// Tiles with sub-tile collision shapes are blocked as a whole.
+    #[must_use]
+    pub fn from_tilemap(tilemap: &Tilemap, mask: LayerMask) -> Self {
+        let tile_size = Vec2::new(tilemap.tile_width as f32, tilemap.tile_height as f32);
+        let mut grid = Self::new(tilemap.width, tilemap.height, tile_size);
+        for y in 0..tilemap.height {
+            for x in 0..tilemap.width {
+                grid.tiles[(y * tilemap.width + x) as usize] =
+                    tilemap.tile_layers(x, y).intersects(mask);
+            }
+        }
+        grid
+    }
+
+    fn index(&self, (x, y): Tile) -> Option<usize> {
+        let in_bounds = (0..self.width).contains(&x) && (0..self.height).contains(&y);
+        in_bounds.then(|| (y * self.width + x) as usize)
+    }
+
+    /// Check if a tile is inside the map and not blocked
+    #[must_use]
+    pub fn is_walkable(&self, tile: Tile) -> bool {
+        self.index(tile)
+            .is_some_and(|i| !self.tiles[i] && !self.obstacles[i])
+    }
+
+    /// Get the tile containing a world position
+    #[must_use]
+    pub fn tile_at(&self, pos: Vec2) -> Tile {
+        let tile = (pos / self.tile_size).floor();
+        (tile.x as i32, tile.y as i32)
+    }
+
+    /// Get the world position of a tile's center
+    #[must_use]
+    pub fn tile_center(&self, (x, y): Tile) -> Vec2 {
+        (Vec2::new(x as f32, y as f32) + 0.5) * self.tile_size
+    }
+
+    /// Walkable neighbors of a tile with their step cost
+    ///
+    /// Diagonal steps need both tiles beside them walkable, so paths never
+    /// cut the corner of a wall.
+    fn neighbors(&self, (x, y): Tile) -> impl Iterator<Item = (Tile, u32)> + '_ {
+        const STEPS: [(i32, i32); 8] = [
+            (1, 0),
+            (-1, 0),
+            (0, 1),
+            (0, -1),
+            (1, 1),
+            (1, -1),
+            (-1, 1),
+            (-1, -1),
+        ];
+        STEPS.iter().filter_map(move |&(dx, dy)| {
+            let next = (x + dx, y + dy);
+            if !self.is_walkable(next) {
+                return None;
+            }
+            if dx != 0 && dy != 0 {
+                let corners_open = self.is_walkable((x + dx, y)) && self.is_walkable((x, y + dy));
+                return corners_open.then_some((next, DIAGONAL));
+            }
+            Some((next, STRAIGHT))
+        })
+    }
+}
+
+impl TileGrid for NavGrid {
+    fn tile_size(&self) -> Vec2 {
+        self.tile_size
+    }
+
+    fn grid_size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    fn tile_layers(&self, x: i32, y: i32) -> LayerMask {
+        if self.is_walkable((x, y)) {
+            LayerMask::NONE
+        } else {
+            LayerMask::SOLID
+        }
+    }
+}
+
+/// Octile distance estimate between two tiles
+fn heuristic(a: Tile, b: Tile) -> u32 {
+    let dx = a.0.abs_diff(b.0);
+    let dy = a.1.abs_diff(b.1);
+    STRAIGHT * dx.max(dy) + (DIAGONAL - STRAIGHT) * dx.min(dy)
+}
+
+/// Find the cheapest tile path from `start` to `goal`, both included
+fn astar(grid: &NavGrid, start: Tile, goal: Tile) -> Option<Vec<Tile>> {
+    let start_index = grid.index(start)?;
+    if !grid.is_walkable(goal) {
+        return None;
+    }
+
+    let size = grid.tiles.len();
+    let mut cost = vec![u32::MAX; size];
+    let mut came_from: Vec<Option<Tile>> = vec![None; size];
+    let mut open = BinaryHeap::new();
+    cost[start_index] = 0;
+    open.push(Reverse((heuristic(start, goal), start)));
+
+    while let Some(Reverse((_, tile))) = open.pop() {
+        if tile == goal {
+            let mut path = vec![goal];
+            let mut current = goal;
+            while let Some(previous) = grid.index(current).and_then(|i| came_from[i]) {
+                path.push(previous);
+                current = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let tile_cost = grid.index(tile).map_or(u32::MAX, |i| cost[i]);
+        for (next, step) in grid.neighbors(tile) {
+            let Some(i) = grid.index(next) else {
+                continue;
+            };
+            let next_cost = tile_cost + step;
+            if next_cost < cost[i] {
+                cost[i] = next_cost;
+                came_from[i] = Some(tile);
+                open.push(Reverse((next_cost + heuristic(next, goal), next)));
+            }
+        }
+    }
+    None
+}
+
+/// Finds and caches NPC paths on a navigation grid (resource)
+#[derive(Debug, Clone)]
+pub struct Pathfinder {
+    grid: NavGrid,
+    /// Half-size of the box smoothed paths keep clear of blocked tiles
+    clearance: Vec2,
+    /// Tile paths by (start, goal) tile; None if unreachable
+    cache: HashMap<(Tile, Tile), Option<Vec<Tile>>>,
+}
+
+impl Pathfinder {
+    /// Create a pathfinder for a grid
+    #[must_use]
+    pub fn new(grid: NavGrid) -> Self {
+        let clearance = grid.tile_size * 0.25;
+        Self {
+            grid,
+            clearance,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Re-read blocked tiles from the tilemap, dropping cached paths if any
+    /// changed
+    ///
+    /// A map of the same size is compared in place, so this is cheap enough
+    /// to run every frame.
+    pub fn update_tiles(&mut self, tilemap: &Tilemap, mask: LayerMask) {
+        let tile_size = Vec2::new(tilemap.tile_width as f32, tilemap.tile_height as f32);
+        let size = (tilemap.width as i32, tilemap.height as i32);
+        if size != (self.grid.width, self.grid.height) || tile_size != self.grid.tile_size {
+            self.grid = NavGrid::from_tilemap(tilemap, mask);
+            self.cache.clear();
+            return;
+        }
+
+        let mut changed = false;
+        for y in 0..tilemap.height {
+            for x in 0..tilemap.width {
+                let blocked = tilemap.tile_layers(x, y).intersects(mask);
+                let tile = &mut self.grid.tiles[(y * tilemap.width + x) as usize];
+                changed |= *tile != blocked;
+                *tile = blocked;
+            }
+        }
+        if changed {
+            self.cache.clear();
+        }
+    }
+
+    /// Replace the entity obstacles, blocking every tile their boxes overlap
+    ///
+    /// Cached paths are dropped if the blocked tiles changed.
+    pub fn set_obstacles(&mut self, obstacles: impl IntoIterator<Item = AABB>) {
+        let mut blocked = vec![false; self.grid.obstacles.len()];
+        for aabb in obstacles {
+            let min = self.grid.tile_at(aabb.min);
+            // Boxes ending on a tile edge don't reach into the next tile
+            let max = self.grid.tile_at(aabb.max - 1e-3);
+            for y in min.1..=max.1 {
+                for x in min.0..=max.0 {
+                    if let Some(i) = self.grid.index((x, y)) {
+                        blocked[i] = true;
+                    }
+                }
+            }
+        }
+        if blocked != self.grid.obstacles {
+            self.grid.obstacles = blocked;
+            self.cache.clear();
+        }
+    }
+
+    /// Find a path from `start` to `goal` around blocked tiles
+    ///
+    /// Returns the waypoints to walk through after `start`, ending exactly
+    /// at `goal`, or None if the goal can't be reached.
+    pub fn find_path(&mut self, start: Vec2, goal: Vec2) -> Option<Vec<Waypoint>> {
+        let key = (self.grid.tile_at(start), self.grid.tile_at(goal));
+        if !self.cache.contains_key(&key) {
+            if self.cache.len() >= MAX_CACHED_PATHS {
+                self.cache.clear();
+            }
+            self.cache.insert(key, astar(&self.grid, key.0, key.1));
+        }
+        let tiles = self.cache.get(&key)?.as_ref()?;
+
+        // Walk from the exact start to the exact goal through tile centers
+        let mut points = vec![start];
+        if tiles.len() > 2 {
+            points.extend(
+                tiles[1..tiles.len() - 1]
+                    .iter()
+                    .map(|&t| self.grid.tile_center(t)),
+            );
+        }
+        points.push(goal);
+
+        Some(
+            self.smooth(&points)
+                .into_iter()
+                .map(|p| Waypoint { x: p.x, y: p.y })
+                .collect(),
+        )
+    }
+
+    /// Drop the points that can be skipped by walking straight past them
+    fn smooth(&self, points: &[Vec2]) -> Vec<Vec2> {
+        let mut smoothed = Vec::new();
+        let mut anchor = 0;
+        while anchor + 1 < points.len() {
+            let mut next = anchor + 1;
+            while next + 1 < points.len() && self.is_clear(points[anchor], points[next + 1]) {
+                next += 1;
+            }
+            smoothed.push(points[next]);
+            anchor = next;
+        }
+        smoothed
+    }
+
+    /// Check if a walker can go straight from `from` to `to`
+    fn is_clear(&self, from: Vec2, to: Vec2) -> bool {
+        let walker = AABB::from_center(from, self.clearance);
+        shape_cast_tiles(&self.grid, &walker, to - from, LayerMask::SOLID).is_none()
+    }
+}
+
+/// Pathfinder system: keeps the `Pathfinder` resource in step with the
+/// `Tilemap`
+///
+/// The grid is re-read from the tilemap every frame, so loading a map or
+/// editing its tiles in place drops the cached paths that changed tiles
+/// made stale, and static colliders (props, fences) become obstacles.
+/// Creates the resource the first time.
+pub fn pathfinder_system(world: &mut World) {
+    if world.get_resource::<Pathfinder>().is_none() {
+        let Some(tilemap) = world.get_resource::<Tilemap>() else {
+            return;
+        };
+        let pathfinder = Pathfinder::new(NavGrid::from_tilemap(tilemap, NPC_BLOCKING));
+        world.insert_resource(pathfinder);
+    }
+
+    let obstacles: Vec<AABB> = world
+        .query_filtered::<(&Position, &Collider, Option<&RigidBody>), Without<Sensor>>()
+        .filter(|(_, (_, _, body))| !body.is_some_and(RigidBody::is_dynamic))
+        .map(|(_, (pos, collider, _))| collider.aabb(pos.current))
+        .collect();
+    world.resource_scope(|world, pathfinder: &mut Pathfinder| {
+        if let Some(tilemap) = world.get_resource::<Tilemap>() {
+            pathfinder.update_tiles(tilemap, NPC_BLOCKING);
+        }
+        pathfinder.set_obstacles(obstacles);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::npc::{Npc, NpcState};
+    use crate::test_map::from_rows;
+
+    /// Grid from rows of `.` (walkable) and `#` (blocked) 16px tiles
+    fn grid(rows: &[&str]) -> NavGrid {
+        NavGrid::from_tilemap(&from_rows(rows), NPC_BLOCKING)
+    }
+
+    fn center(x: i32, y: i32) -> Vec2 {
+        Vec2::new(x as f32, y as f32) * 16.0 + 8.0
+    }
+
+    fn points(path: &[Waypoint]) -> Vec<Vec2> {
+        path.iter().map(|w| Vec2::new(w.x, w.y)).collect()
+    }
+
+    #[test]
+    fn test_path_around_wall() {
+        let mut pathfinder = Pathfinder::new(grid(&["..#...", "..#...", "..#...", "......"]));
+
+        let path = pathfinder.find_path(center(0, 0), center(5, 0)).unwrap();
+        let path = points(&path);
+        assert_eq!(path.last(), Some(&center(5, 0)));
+        // Down the wall, around its end and back up, in few straight runs
+        assert!(path.len() <= 4, "{:?}", path);
+        let mut from = center(0, 0);
+        for &to in &path {
+            assert!(pathfinder.is_clear(from, to), "{:?} -> {:?}", from, to);
+            from = to;
+        }
+    }
+
+    #[test]
+    fn test_open_ground_is_one_straight_run() {
+        let mut pathfinder = Pathfinder::new(grid(&["........", "........", "........"]));
+
+        let goal = Vec2::new(120.0, 40.0);
+        let path = pathfinder.find_path(Vec2::new(4.0, 4.0), goal).unwrap();
+        assert_eq!(points(&path), vec![goal]);
+    }
+
+    #[test]
+    fn test_no_corner_cutting() {
+        let mut pathfinder = Pathfinder::new(grid(&[".#", "#."]));
+        assert!(pathfinder.find_path(center(0, 0), center(1, 1)).is_none());
+
+        // Blocked or outside goals can't be reached either
+        let mut pathfinder = Pathfinder::new(grid(&["..", ".#"]));
+        assert!(pathfinder.find_path(center(0, 0), center(1, 1)).is_none());
+        assert!(pathfinder.find_path(center(0, 0), center(5, 0)).is_none());
+        assert!(pathfinder.find_path(center(0, 0), center(1, 0)).is_some());
+    }
+
+    #[test]
+    fn test_cache_invalidated_by_changes() {
+        let walled = from_rows(&["..#..", "..#..", "....."]);
+        let mut pathfinder = Pathfinder::new(NavGrid::from_tilemap(&walled, NPC_BLOCKING));
+        let (start, goal) = (center(0, 0), center(4, 0));
+
+        assert!(pathfinder.find_path(start, goal).is_some());
+        assert!(pathfinder.find_path(start, goal).is_some());
+        assert_eq!(pathfinder.cache.len(), 1);
+
+        // Re-reading unchanged tiles keeps the cache
+        pathfinder.update_tiles(&walled, NPC_BLOCKING);
+        assert_eq!(pathfinder.cache.len(), 1);
+
+        // A crate in the gap closes the way; moving it away opens it again
+        let gap = AABB::new(32.0, 32.0, 16.0, 16.0);
+        pathfinder.set_obstacles([gap]);
+        assert_eq!(pathfinder.cache.len(), 0);
+        assert!(pathfinder.find_path(start, goal).is_none());
+        pathfinder.set_obstacles([gap.translated(Vec2::new(32.0, 0.0))]);
+        assert!(pathfinder.find_path(start, goal).is_some());
+
+        // Opening the wall gives a straight path
+        pathfinder.set_obstacles([]);
+        pathfinder.update_tiles(&from_rows(&[".....", ".....", "....."]), NPC_BLOCKING);
+        assert_eq!(
+            points(&pathfinder.find_path(start, goal).unwrap()),
+            vec![goal]
+        );
+    }
+
+    /// Tilemap from rows of `.` (walkable) and `#` (solid) 16px tiles
+    #[test]
+    fn test_from_tilemap_layers() {
+        let mut tilemap = from_rows(&[".#."]);
+        tilemap.collision_layers = vec![LayerMask::NONE, LayerMask::WATER, LayerMask::NONE];
+
+        assert!(!NavGrid::from_tilemap(&tilemap, NPC_BLOCKING).is_walkable((1, 0)));
+        assert!(NavGrid::from_tilemap(&tilemap, LayerMask::SOLID).is_walkable((1, 0)));
+    }
+
+    #[test]
+    fn test_pathfinder_follows_the_tilemap() {
+        let mut world = World::new();
+        world.insert_resource(from_rows(&["..#..", "..#..", "....."]));
+        let (start, goal) = (center(0, 0), center(4, 0));
+
+        // A fence in the gap is an obstacle; the sensor and the player aren't
+        let fence = world.spawn();
+        world.insert(fence, Position::from_vec2(center(2, 2)));
+        world.insert(fence, Collider::new(12.0, 12.0));
+        for body in [Some(RigidBody::dynamic()), None] {
+            let entity = world.spawn();
+            world.insert(entity, Position::from_vec2(center(3, 2)));
+            world.insert(entity, Collider::new(12.0, 12.0));
+            match body {
+                Some(body) => world.insert(entity, body),
+                None => world.insert(entity, Sensor),
+            }
+        }
+        pathfinder_system(&mut world);
+        let pathfinder = world.get_resource_mut::<Pathfinder>().unwrap();
+        assert!(pathfinder.find_path(start, goal).is_none());
+        assert!(pathfinder.grid.is_walkable((3, 2)));
+
+        // Loading a map without the wall drops the cached dead end
+        world.despawn(fence);
+        world.insert_resource(from_rows(&[".....", ".....", "....."]));
+        pathfinder_system(&mut world);
+        let pathfinder = world.get_resource_mut::<Pathfinder>().unwrap();
+        assert_eq!(pathfinder.cache.len(), 0);
+        assert_eq!(
+            points(&pathfinder.find_path(start, goal).unwrap()),
+            vec![goal]
+        );
+
+        // So does a tile turned solid in place, as when something is built
+        let tilemap = world.get_resource_mut::<Tilemap>().unwrap();
+        tilemap.collision[2] = true;
+        pathfinder_system(&mut world);
+        let pathfinder = world.get_resource_mut::<Pathfinder>().unwrap();
+        assert_eq!(pathfinder.cache.len(), 0);
+        let path = points(&pathfinder.find_path(start, goal).unwrap());
+        assert!(path.len() > 1, "{:?}", path);
+    }
+
+    #[test]
+    fn test_npc_walks_path_around_wall() {
+        let mut pathfinder = Pathfinder::new(grid(&[".#..", ".#..", "...."]));
+        let mut npc = Npc::new("robin", "Robin").with_position(8.0, 8.0);
+        npc.add_location("shop", 56.0, 8.0);
+
+        assert!(npc.path_to("shop", &mut pathfinder));
+        assert_eq!(npc.state, NpcState::Walking);
+        for _ in 0..200 {
+            npc.update_movement(0.05);
+            let tile = pathfinder
+                .grid
+                .tile_at(Vec2::new(npc.position.x, npc.position.y));
+            assert!(pathfinder.grid.is_walkable(tile), "walked into {:?}", tile);
+        }
+        assert_eq!(npc.state, NpcState::Idle);
+        let arrived = Vec2::new(npc.position.x, npc.position.y);
+        assert!(arrived.distance(Vec2::new(56.0, 8.0)) < 2.0);
+
+        npc.add_location("inside_wall", 24.0, 8.0);
+        assert!(!npc.path_to("inside_wall", &mut pathfinder));
+        assert!(!npc.path_to("unknown", &mut pathfinder));
+    }
+}
@@ -1,6 +1,7 @@
 //! Save data structures for game state serialization
 
 use engine_core::GameClock;
+use engine_ecs::WorldSnapshot;
 use engine_render::glam::Vec2;
 use serde::{Deserialize, Serialize};
 
@@ -23,6 +24,9 @@ pub struct SaveData {
     pub current_map: String,
     /// Player inventory
     pub inventory: Inventory,
+    /// Every entity and registered resource (absent in older saves)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub world: Option<WorldSnapshot>,
 }
 
 impl SaveData {
@@ -39,9 +43,17 @@ impl SaveData {
             game_clock,
             current_map,
             inventory,
+            world: None,
         }
     }
 
+    /// Attach a snapshot of the whole world
+    #[must_use]
+    pub fn with_world(mut self, snapshot: WorldSnapshot) -> Self {
+        self.world = Some(snapshot);
+        self
+    }
+
     /// Check if save version is compatible
     pub fn is_compatible(&self) -> bool {
         self.version == SAVE_VERSION
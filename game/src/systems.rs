@@ -3,16 +3,75 @@
 //! Systems contain the game logic that operates on components.
 
 use engine_core::FIXED_TIMESTEP;
-use engine_ecs::World;
+use engine_ecs::{
+    propagate_transforms, Changed, Entity, EventReader, Events, GlobalTransform, Parent,
+    Schedule, Stage, SystemAccess, SystemWorld, Transform, With, Without, World,
+};
 use engine_input::{Input, KeyCode};
-use engine_physics::AABB;
+use engine_physics::{
+    add_trigger_events, collide, knockback_impulse, raycast_tiles, separate_bodies,
+    shape_cast_tiles, slide, CastHit, CharacterController, CollisionLayers, HitTarget, LayerMask,
+    Momentum, Ray, RigidBody, Sensor, SensorTracker, SpatialGrid, TriggerEnter, AABB,
+};
+use engine_render::glam::Vec2;
 use engine_render::{Camera2D, Tilemap};
 
-use crate::components::{CameraTarget, Collider, PlayerControlled, Position, Velocity};
+use crate::components::{
+    CameraTarget, Collider, MapTrigger, PlayerControlled, Position, Velocity,
+};
+use crate::events::MapTransition;
+use crate::pathfinding::pathfinder_system;
 
 /// Run speed multiplier when holding Shift
 const RUN_MULTIPLIER: f32 = 1.8;
 
+/// Impulse the player shoves props in front of them with
+const SHOVE_STRENGTH: f32 = 200.0;
+
+/// Reach of interactions when there is no tilemap to take the tile size from
+const DEFAULT_REACH: f32 = 16.0;
+
+/// Variable frame delta in seconds (resource, set once per frame)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeltaTime(pub f32);
+
+/// Build the game's system schedule
+pub fn create_schedule() -> Schedule {
+    let mut schedule = Schedule::new();
+    schedule.add_system(Stage::FixedUpdate, "input", input_system);
+    schedule
+        .add_system(Stage::FixedUpdate, "dynamics", dynamics_system)
+        .after("input");
+    schedule
+        .add_system(Stage::FixedUpdate, "movement", movement_system)
+        .after("dynamics");
+    schedule
+        .add_system(Stage::FixedUpdate, "hierarchy", hierarchy_system)
+        .after("movement");
+    schedule
+        .add_system(Stage::FixedUpdate, "spatial_grid", spatial_grid_system)
+        .after("hierarchy");
+    schedule
+        .add_system(Stage::FixedUpdate, "entity_collision", entity_collision_system)
+        .after("spatial_grid");
+    schedule
+        .add_system(Stage::FixedUpdate, "sensors", sensor_system)
+        .after("entity_collision");
+    schedule.add_system(Stage::Update, "map_trigger", map_trigger_system);
+    schedule.add_system(Stage::Update, "interaction", interaction_system);
+    schedule.add_parallel_system(
+        Stage::PostUpdate,
+        "camera",
+        SystemAccess::new()
+            .query::<&Position, With<CameraTarget>>()
+            .read_resource::<DeltaTime>()
+            .write_resource::<Camera2D>(),
+        camera_system,
+    );
+    schedule.add_system(Stage::PostUpdate, "pathfinder", pathfinder_system);
+    schedule
+}
+
 /// Input system: reads input and sets velocity for player-controlled entities
 pub fn input_system(world: &mut World) {
     // Get input resource
@@ -28,19 +87,46 @@ pub fn input_system(world: &mut World) {
     let is_running = input.is_key_pressed(KeyCode::LShift) || input.is_key_pressed(KeyCode::RShift);
     let speed_multiplier = if is_running { RUN_MULTIPLIER } else { 1.0 };
 
-    // We need to collect entity data first, then modify
-    // This avoids borrowing issues
-    let entities_to_update: Vec<_> = world
-        .query::<PlayerControlled>()
-        .map(|(entity, pc)| (entity, pc.speed))
-        .collect();
+    // Update velocities, and facing while moving
+    for (_, (pc, vel)) in world.query_mut::<(&mut PlayerControlled, &mut Velocity)>() {
+        let speed = pc.speed * speed_multiplier;
+        vel.x = direction.x * speed;
+        vel.y = direction.y * speed;
+        if direction != Vec2::ZERO {
+            pc.facing = direction;
+        }
+    }
+}
+
+/// Dynamics system: applies impulses and drag to momentum
+///
+/// Runs on the fixed timestep before movement, which moves bodies by their
+/// momentum on top of their velocity. Entities without a `RigidBody` are
+/// static and keep no momentum.
+pub fn dynamics_system(world: &mut World) {
+    let dt = FIXED_TIMESTEP as f32;
+    for (_, (momentum, body)) in world.query_mut::<(&mut Momentum, Option<&RigidBody>)>() {
+        momentum.integrate(&body.copied().unwrap_or(RigidBody::fixed()), dt);
+    }
+}
 
-    // Update velocities
-    for (entity, base_speed) in entities_to_update {
-        if let Some(vel) = world.get_mut::<Velocity>(entity) {
-            let speed = base_speed * speed_multiplier;
-            vel.x = direction.x * speed;
-            vel.y = direction.y * speed;
+/// Knock an entity away from `source` with an impulse of `strength`
+///
+/// Safe to call outside the fixed timestep (the interaction system shoves
+/// props with it): the impulse waits on the entity's `Momentum` until the
+/// next `dynamics_system` run, and the movement goes through
+/// `movement_system`, so knocked-back entities still stop at walls.
+pub fn apply_knockback(world: &mut World, entity: Entity, source: Vec2, strength: f32) {
+    let Some(pos) = world.get::<Position>(entity).map(|pos| pos.current) else {
+        return;
+    };
+    let impulse = knockback_impulse(source, pos, strength);
+    match world.get_mut::<Momentum>(entity) {
+        Some(momentum) => momentum.apply_impulse(impulse),
+        None => {
+            let mut momentum = Momentum::default();
+            momentum.apply_impulse(impulse);
+            world.insert(entity, momentum);
         }
     }
 }
@@ -58,84 +144,791 @@ pub fn movement_system(world: &mut World) {
         })
         .unwrap_or_else(|| AABB::new(0.0, 0.0, 320.0, 240.0));
 
-    // Collect entities with position, velocity, and collider
-    let entities_to_move: Vec<_> = world
-        .query::<Position>()
-        .filter_map(|(entity, _)| {
-            let vel = world.get::<Velocity>(entity)?;
-            let col = world.get::<Collider>(entity)?;
-            Some((entity, vel.as_vec2(), col.half_size()))
-        })
-        .collect();
-
-    // Get tilemap for collision checking
-    let tilemap_ptr = world.get_resource::<Tilemap>().map(|t| t as *const Tilemap);
+    // Borrow the tilemap alongside the component storages
+    let moved = world.resource_scope(|world, tilemap: &mut Tilemap| {
+        move_entities(world, world_bounds, Some(tilemap), dt);
+    });
+    if moved.is_none() {
+        move_entities(world, world_bounds, None, dt);
+    }
+}
 
+/// Move every entity with a collider, resolving tile collisions and bounds
+///
+/// Movement is swept against the solid tiles it crosses, so fast movers
+/// can't tunnel through thin walls and blocked movement slides along them.
+/// Entities with a `CharacterController` also get nudged around corners,
+/// and `Momentum` bounces off what stopped it.
+fn move_entities(world: &mut World, world_bounds: AABB, tilemap: Option<&Tilemap>, dt: f32) {
     // Move each entity
-    for (entity, velocity, half_size) in entities_to_move {
-        if let Some(pos) = world.get_mut::<Position>(entity) {
-            // Save previous position for interpolation
-            pos.save_previous();
-
-            // Calculate new position
-            let new_position = pos.current + velocity * dt;
-            let mut final_pos = new_position;
-
-            // 1. Check collision with world bounds
-            let new_aabb = AABB::from_center(final_pos, half_size);
-            if new_aabb.min.x < world_bounds.min.x {
-                final_pos.x = world_bounds.min.x + half_size.x;
-            }
-            if new_aabb.max.x > world_bounds.max.x {
-                final_pos.x = world_bounds.max.x - half_size.x;
-            }
-            if new_aabb.min.y < world_bounds.min.y {
-                final_pos.y = world_bounds.min.y + half_size.y;
-            }
-            if new_aabb.max.y > world_bounds.max.y {
-                final_pos.y = world_bounds.max.y - half_size.y;
+    for (_, (pos, vel, col, controller, momentum, body)) in world.query_mut::<(
+        &mut Position,
+        Option<&Velocity>,
+        &Collider,
+        Option<&mut CharacterController>,
+        Option<&mut Momentum>,
+        Option<&RigidBody>,
+    )>() {
+        let velocity = match (vel, &momentum) {
+            (None, None) => continue,
+            _ => {
+                vel.map_or(Vec2::ZERO, Velocity::as_vec2)
+                    + momentum.as_ref().map_or(Vec2::ZERO, |momentum| momentum.velocity)
             }
+        };
+        let delta = velocity * dt;
+        let half_size = col.half_size();
+        // Normals of the surfaces that stopped the movement
+        let mut blocked = Vec::new();
+
+        // Save previous position for interpolation
+        pos.save_previous();
+
+        let mut final_pos = pos.current + delta;
 
-            // 2. Check collision with solid tiles
-            if let Some(tm_ptr) = tilemap_ptr {
-                // Safety: tilemap is borrowed from world resource, still valid
-                let tm = unsafe { &*tm_ptr };
-                if tm.has_collision() {
-                    let player_aabb = AABB::from_center(final_pos, half_size);
-                    let solid_tiles = tm.get_solid_tiles_in_rect(player_aabb.min, player_aabb.max);
-
-                    for (_, _, tile_min, tile_max) in solid_tiles {
-                        let tile_aabb = AABB {
-                            min: tile_min,
-                            max: tile_max,
-                        };
-                        let player_aabb = AABB::from_center(final_pos, half_size);
-
-                        if let Some(collision) = player_aabb.get_collision(&tile_aabb) {
-                            final_pos += collision.mtv;
+        // 1. Sweep against the tiles on its mask along the whole movement
+        if let Some(tm) = tilemap.filter(|tm| tm.has_collision()) {
+            let start = col.aabb(pos.current);
+            let swept = start.swept(delta);
+            let solid_tiles: Vec<AABB> = tm
+                .get_blocking_tiles_in_rect(swept.min, swept.max, col.layers.mask)
+                .into_iter()
+                .map(|(_, _, min, max)| AABB { min, max })
+                .collect();
+
+            final_pos = pos.current
+                + match controller {
+                    Some(controller) => {
+                        let movement = controller.move_and_slide(&start, delta, &solid_tiles);
+                        blocked.extend(controller.touches().iter().map(|touch| touch.normal));
+                        movement
+                    }
+                    None => {
+                        let result = slide(&start, delta, &solid_tiles);
+                        if result.hit_x {
+                            blocked.push(Vec2::new(-delta.x.signum(), 0.0));
+                        }
+                        if result.hit_y {
+                            blocked.push(Vec2::new(0.0, -delta.y.signum()));
                         }
+                        result.movement
                     }
+                };
+
+            // Push out of tiles the entity was already inside of
+            for tile_aabb in &solid_tiles {
+                if let Some(collision) = col.aabb(final_pos).get_collision(tile_aabb) {
+                    final_pos += collision.mtv;
+                }
+            }
+        }
+
+        // 2. Keep inside the world bounds
+        let new_aabb = AABB::from_center(final_pos, half_size);
+        if new_aabb.min.x < world_bounds.min.x {
+            final_pos.x = world_bounds.min.x + half_size.x;
+            blocked.push(Vec2::X);
+        }
+        if new_aabb.max.x > world_bounds.max.x {
+            final_pos.x = world_bounds.max.x - half_size.x;
+            blocked.push(Vec2::NEG_X);
+        }
+        if new_aabb.min.y < world_bounds.min.y {
+            final_pos.y = world_bounds.min.y + half_size.y;
+            blocked.push(Vec2::Y);
+        }
+        if new_aabb.max.y > world_bounds.max.y {
+            final_pos.y = world_bounds.max.y - half_size.y;
+            blocked.push(Vec2::NEG_Y);
+        }
+
+        if let Some(momentum) = momentum {
+            let restitution = body.map_or(0.0, |body| body.restitution);
+            for normal in blocked {
+                momentum.bounce(normal, restitution);
+            }
+        }
+
+        pos.current = final_pos;
+    }
+}
+
+/// Hierarchy system: moves child entities along with their parent
+///
+/// Roots drive their `Transform` from `Position`; children get their
+/// `Position` from the propagated `GlobalTransform`.
+pub fn hierarchy_system(world: &mut World) {
+    for (_, (pos, transform)) in
+        world.query_filtered_mut::<(&Position, &mut Transform), Without<Parent>>()
+    {
+        transform.translation = pos.current;
+    }
+
+    propagate_transforms(world);
+
+    for (_, (pos, global)) in
+        world.query_filtered_mut::<(&mut Position, &GlobalTransform), With<Parent>>()
+    {
+        pos.save_previous();
+        pos.current = global.translation;
+    }
+}
+
+/// Add the `SpatialGrid` resource and keep collider entities registered in it
+///
+/// Entities enter the grid once they have both a `Collider` and a
+/// `Position`, and leave it when either is removed or the entity is
+/// despawned.
+pub fn init_spatial_grid(world: &mut World) {
+    world.insert_resource(SpatialGrid::default());
+    world.on_insert::<Collider>(update_grid_entry);
+    world.on_insert::<Position>(update_grid_entry);
+    world.on_remove::<Collider>(remove_grid_entry);
+    world.on_remove::<Position>(remove_grid_entry);
+}
+
+fn update_grid_entry(world: &mut World, entity: Entity) {
+    let Some(aabb) = collider_aabb(world, entity) else {
+        return;
+    };
+    if let Some(grid) = world.get_resource_mut::<SpatialGrid>() {
+        grid.update(entity, &aabb);
+    }
+}
+
+fn remove_grid_entry(world: &mut World, entity: Entity) {
+    if let Some(grid) = world.get_resource_mut::<SpatialGrid>() {
+        grid.remove(entity);
+    }
+}
+
+/// Spatial grid system: moves the grid entries of entities that moved
+pub fn spatial_grid_system(world: &mut World) {
+    let moved: Vec<(Entity, AABB)> = world
+        .query_filtered::<(&Position, &Collider), Changed<Position>>()
+        .map(|(entity, (pos, col))| (entity, col.aabb(pos.current)))
+        .collect();
+
+    if let Some(grid) = world.get_resource_mut::<SpatialGrid>() {
+        for (entity, aabb) in moved {
+            grid.update(entity, &aabb);
+        }
+    }
+}
+
+/// Entity collision system: pushes dynamic bodies out of the colliders they overlap
+///
+/// Neighbors come from the `SpatialGrid`; sensors and pairs whose collision
+/// layers don't interact are skipped. Colliders without a `RigidBody` are
+/// static and never pushed. Dynamic bodies share the push by mass, and
+/// bodies with `Momentum` exchange it.
+pub fn entity_collision_system(world: &mut World) {
+    world.resource_scope(resolve_entity_collisions);
+}
+
+fn resolve_entity_collisions(world: &mut World, grid: &mut SpatialGrid) {
+    let dynamic: Vec<Entity> = world
+        .query_filtered::<(&RigidBody, &Collider, &Position), Without<Sensor>>()
+        .filter(|(_, (body, _, _))| body.is_dynamic())
+        .map(|(entity, _)| entity)
+        .collect();
+
+    for entity in dynamic {
+        let Some(aabb) = collider_aabb(world, entity) else {
+            continue;
+        };
+        let body = rigid_body(world, entity);
+        for other in grid.query(&aabb) {
+            let other_body = rigid_body(world, other);
+            // Dynamic pairs are resolved once, from the lower index
+            if other == entity
+                || (other_body.is_dynamic() && other.index < entity.index)
+                || world.has::<Sensor>(other)
+                || !interacts(world, entity, other)
+            {
+                continue;
+            }
+            let (Some(aabb), Some(other_aabb)) =
+                (collider_aabb(world, entity), collider_aabb(world, other))
+            else {
+                continue;
+            };
+            let Some((push, other_push)) =
+                separate_bodies(&aabb, &body, &other_aabb, &other_body)
+            else {
+                continue;
+            };
+            if world.has::<Momentum>(entity) || world.has::<Momentum>(other) {
+                let normal = (push - other_push).normalize_or_zero();
+                exchange_momentum(world, (entity, &body), (other, &other_body), normal);
+            }
+            for (target, offset) in [(entity, push), (other, other_push)] {
+                if offset == Vec2::ZERO {
+                    continue;
+                }
+                if let Some(pos) = world.get_mut::<Position>(target) {
+                    pos.current += offset;
+                }
+                if let Some(aabb) = collider_aabb(world, target) {
+                    grid.update(target, &aabb);
                 }
             }
+        }
+    }
+}
+
+/// Get an entity's collision box at its current position
+fn collider_aabb(world: &World, entity: Entity) -> Option<AABB> {
+    let pos = world.get::<Position>(entity)?;
+    Some(world.get::<Collider>(entity)?.aabb(pos.current))
+}
+
+/// Check if two entities' collision layers let them collide
+fn interacts(world: &World, a: Entity, b: Entity) -> bool {
+    match (world.get::<Collider>(a), world.get::<Collider>(b)) {
+        (Some(a), Some(b)) => a.layers.interacts(&b.layers),
+        _ => false,
+    }
+}
+
+/// Get how an entity responds to collisions, static without a `RigidBody`
+fn rigid_body(world: &World, entity: Entity) -> RigidBody {
+    world
+        .get::<RigidBody>(entity)
+        .copied()
+        .unwrap_or(RigidBody::fixed())
+}
+
+/// Exchange the momentum of two bodies pushed apart along `normal`
+///
+/// Dynamic bodies set moving get a `Momentum` if they had none.
+fn exchange_momentum(
+    world: &mut World,
+    (a, a_body): (Entity, &RigidBody),
+    (b, b_body): (Entity, &RigidBody),
+    normal: Vec2,
+) {
+    let mut a_momentum = world.get::<Momentum>(a).copied().unwrap_or_default();
+    let mut b_momentum = world.get::<Momentum>(b).copied().unwrap_or_default();
+    collide(&mut a_momentum, a_body, &mut b_momentum, b_body, normal);
+
+    for (entity, body, momentum) in [(a, a_body, a_momentum), (b, b_body, b_momentum)] {
+        if let Some(current) = world.get_mut::<Momentum>(entity) {
+            *current = momentum;
+        } else if body.is_dynamic() && !momentum.is_resting() {
+            world.insert(entity, momentum);
+        }
+    }
+}
+
+/// Interaction system: when the interact key is pressed, shoves the dynamic
+/// body in front of the player away from them
+///
+/// Tiles in front are found too; they are where tools will act.
+pub fn interaction_system(world: &mut World) {
+    let pressed = world
+        .get_resource::<Input>()
+        .is_some_and(|input| input.is_key_just_pressed(KeyCode::E));
+    if !pressed {
+        return;
+    }
+    let Some((player, pos)) = world
+        .query_filtered::<&Position, With<PlayerControlled>>()
+        .map(|(entity, pos)| (entity, pos.current))
+        .next()
+    else {
+        return;
+    };
+
+    let target = facing_target(world, player).map(|hit| hit.target);
+    if let Some(HitTarget::Entity(target)) = target {
+        if world.get::<RigidBody>(target).is_some_and(RigidBody::is_dynamic) {
+            apply_knockback(world, target, pos, SHOVE_STRENGTH);
+        }
+    }
+}
+
+/// Find the tile or entity in front of a player-controlled entity, within
+/// one tile
+///
+/// Looks straight ahead from its center first, then sweeps its whole
+/// collider, so something slightly off-center is still found.
+pub fn facing_target(world: &World, entity: Entity) -> Option<CastHit> {
+    let facing = world.get::<PlayerControlled>(entity)?.facing;
+    let center = world.get::<Position>(entity)?.current;
+    let reach = world
+        .get_resource::<Tilemap>()
+        .map_or(DEFAULT_REACH, |tilemap| tilemap.tile_width as f32);
+
+    let ray = Ray::new(center, facing, reach);
+    raycast(world, &ray, LayerMask::ALL, Some(entity)).or_else(|| {
+        let aabb = collider_aabb(world, entity)?;
+        shape_cast(world, &aabb, facing * reach, LayerMask::ALL, Some(entity))
+    })
+}
+
+/// Cast a ray against the tiles and colliders on any of the `mask` layers
+///
+/// Sensors and `ignore` (usually the caster) are skipped.
+pub fn raycast(world: &World, ray: &Ray, mask: LayerMask, ignore: Option<Entity>) -> Option<CastHit> {
+    let tile_hit = world
+        .get_resource::<Tilemap>()
+        .and_then(|tilemap| raycast_tiles(tilemap, ray, mask));
+    let entity_hit = world
+        .get_resource::<SpatialGrid>()
+        .and_then(|grid| grid.raycast(ray, cast_shape(world, mask, ignore)));
+    CastHit::nearest(tile_hit, entity_hit)
+}
+
+/// Cast an AABB by `delta` against the tiles and colliders on any of the
+/// `mask` layers, skipping sensors and `ignore`
+pub fn shape_cast(
+    world: &World,
+    aabb: &AABB,
+    delta: Vec2,
+    mask: LayerMask,
+    ignore: Option<Entity>,
+) -> Option<CastHit> {
+    let tile_hit = world
+        .get_resource::<Tilemap>()
+        .and_then(|tilemap| shape_cast_tiles(tilemap, aabb, delta, mask));
+    let entity_hit = world
+        .get_resource::<SpatialGrid>()
+        .and_then(|grid| grid.shape_cast(aabb, delta, cast_shape(world, mask, ignore)));
+    CastHit::nearest(tile_hit, entity_hit)
+}
+
+/// Collision box of an entity a cast can hit
+fn cast_shape(
+    world: &World,
+    mask: LayerMask,
+    ignore: Option<Entity>,
+) -> impl Fn(Entity) -> Option<AABB> + '_ {
+    move |entity| {
+        let hittable = Some(entity) != ignore
+            && !world.has::<Sensor>(entity)
+            && world
+                .get::<Collider>(entity)
+                .is_some_and(|col| mask.intersects(col.layers.layers));
+        if hittable {
+            collider_aabb(world, entity)
+        } else {
+            None
+        }
+    }
+}
+
+/// Add the trigger events and the resources sensor systems keep between runs
+pub fn init_sensors(world: &mut World) {
+    add_trigger_events(world);
+    world.insert_resource(SensorTracker::new());
+    world.insert_resource(MapTriggerReader::default());
+}
+
+/// Sensor system: sends trigger events for the colliders overlapping sensors
+///
+/// A sensor detects the colliders on its mask layers, whatever their own
+/// mask, so the player can walk through pickups while firing them.
+pub fn sensor_system(world: &mut World) {
+    let Some(grid) = world.get_resource::<SpatialGrid>() else {
+        return;
+    };
+
+    let mut overlaps = Vec::new();
+    for (sensor, (_, col, pos)) in world.query::<(&Sensor, &Collider, &Position)>() {
+        let aabb = col.aabb(pos.current);
+        for other in grid.query(&aabb) {
+            let detected = other != sensor
+                && world.get::<Collider>(other).is_some_and(|other_col| {
+                    col.layers.mask.intersects(other_col.layers.layers)
+                })
+                && collider_aabb(world, other).is_some_and(|other_aabb| aabb.intersects(&other_aabb));
+            if detected {
+                overlaps.push((sensor, other));
+            }
+        }
+    }
 
-            pos.current = final_pos;
+    world.resource_scope(|world, tracker: &mut SensorTracker| tracker.update(world, overlaps));
+}
+
+/// Cursor of `map_trigger_system` into the `TriggerEnter` events (resource)
+#[derive(Default)]
+pub struct MapTriggerReader(EventReader<TriggerEnter>);
+
+/// Spawn a sensor for each trigger of the current map, replacing the previous map's
+pub fn spawn_map_triggers(world: &mut World) {
+    let previous: Vec<Entity> = world
+        .query_filtered::<&MapTrigger, With<Sensor>>()
+        .map(|(entity, _)| entity)
+        .collect();
+    for entity in previous {
+        world.despawn(entity);
+    }
+
+    let Some(triggers) = world.get_resource::<Tilemap>().map(|tm| tm.triggers.clone()) else {
+        return;
+    };
+    for trigger in triggers {
+        let entity = world.spawn();
+        let center = Vec2::new(
+            trigger.x + trigger.width * 0.5,
+            trigger.y + trigger.height * 0.5,
+        );
+        world.insert(entity, Position::new(center.x, center.y));
+        world.insert(
+            entity,
+            Collider::new(trigger.width, trigger.height)
+                .with_layers(CollisionLayers::new(LayerMask::TRIGGER, LayerMask::PLAYER)),
+        );
+        world.insert(entity, Sensor);
+        world.insert(
+            entity,
+            MapTrigger {
+                target_map: trigger.target_map,
+                target_spawn: trigger.target_spawn,
+            },
+        );
+    }
+}
+
+/// Map trigger system: sends a MapTransition when the player enters a map trigger
+pub fn map_trigger_system(world: &mut World) {
+    let entered: Vec<TriggerEnter> = world
+        .resource_scope(|world, reader: &mut MapTriggerReader| {
+            world
+                .get_resource::<Events<TriggerEnter>>()
+                .map(|events| reader.0.read(events).copied().collect())
+                .unwrap_or_default()
+        })
+        .unwrap_or_default();
+
+    let transition = entered.iter().find_map(|enter| {
+        if !world.has::<PlayerControlled>(enter.other) {
+            return None;
         }
+        world
+            .get::<MapTrigger>(enter.sensor)
+            .map(|trigger| MapTransition {
+                target_map: trigger.target_map.clone(),
+                target_spawn: trigger.target_spawn.clone(),
+            })
+    });
+
+    if let Some(transition) = transition {
+        world.send_event(transition);
     }
 }
 
 /// Camera system: makes camera follow entities with CameraTarget component
-pub fn camera_system(world: &mut World, dt: f32) {
+pub fn camera_system(world: &mut SystemWorld) {
+    let dt = world.resource::<DeltaTime>().map_or(0.0, |d| d.0);
+
     // Find the first entity with CameraTarget and Position
     let target_pos = world
-        .query::<CameraTarget>()
-        .filter_map(|(entity, _)| world.get::<Position>(entity).map(|p| p.current))
+        .query_filtered::<&Position, With<CameraTarget>>()
+        .map(|(_, pos)| pos.current)
         .next();
 
     // Update camera to follow target
     if let (Some(camera), Some(target)) =
-        (world.get_resource_mut::<Camera2D>(), target_pos)
+        (world.resource_mut::<Camera2D>(), target_pos)
     {
         camera.follow(target, 5.0);
         camera.update(dt);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_map::map;
+
+    fn grid_hits(world: &World, aabb: &AABB) -> Vec<Entity> {
+        world.get_resource::<SpatialGrid>().unwrap().query(aabb)
+    }
+
+    #[test]
+    fn test_spatial_grid_follows_colliders() {
+        let mut world = World::new();
+        crate::components::configure_storage(&mut world);
+        init_spatial_grid(&mut world);
+        let near_origin = AABB::new(0.0, 0.0, 16.0, 16.0);
+
+        // Registered once both components are present, in either order
+        let crate_entity = world.spawn();
+        world.insert(crate_entity, Collider::new(8.0, 8.0));
+        assert!(grid_hits(&world, &near_origin).is_empty());
+        world.insert(crate_entity, Position::new(8.0, 8.0));
+        assert_eq!(grid_hits(&world, &near_origin), vec![crate_entity]);
+
+        let post = world.spawn();
+        world.insert(post, Position::new(300.0, 300.0));
+        world.insert(post, Collider::new(8.0, 8.0));
+        assert_eq!(world.get_resource::<SpatialGrid>().unwrap().len(), 2);
+
+        // Moving is picked up by the grid system
+        world.get_mut::<Position>(crate_entity).unwrap().current = Vec2::new(300.0, 290.0);
+        spatial_grid_system(&mut world);
+        assert!(grid_hits(&world, &near_origin).is_empty());
+
+        world.remove::<Collider>(post);
+        world.despawn(crate_entity);
+        assert!(world.get_resource::<SpatialGrid>().unwrap().is_empty());
+    }
+
+    fn spawn_body(world: &mut World, x: f32, y: f32, body: Option<RigidBody>) -> Entity {
+        let entity = world.spawn();
+        world.insert(entity, Position::new(x, y));
+        world.insert(entity, Collider::new(10.0, 10.0));
+        if let Some(body) = body {
+            world.insert(entity, body);
+        }
+        entity
+    }
+
+    #[test]
+    fn test_entity_collisions_push_dynamic_bodies() {
+        let mut world = World::new();
+        init_spatial_grid(&mut world);
+        let player = spawn_body(&mut world, 0.0, 0.0, Some(RigidBody::dynamic()));
+        let prop = spawn_body(&mut world, 8.0, 0.0, None);
+        let rock = spawn_body(&mut world, 0.0, -8.0, Some(RigidBody::fixed()));
+        let npc = spawn_body(&mut world, 100.0, 0.0, Some(RigidBody::dynamic()));
+        let other_npc = spawn_body(&mut world, 100.0, 6.0, Some(RigidBody::dynamic()));
+
+        entity_collision_system(&mut world);
+
+        // Static bodies stay put; the player is pushed out of both
+        let position = |world: &World, entity| world.get::<Position>(entity).unwrap().current;
+        assert_eq!(position(&world, prop), Vec2::new(8.0, 0.0));
+        assert_eq!(position(&world, rock), Vec2::new(0.0, -8.0));
+        assert_eq!(position(&world, player), Vec2::new(-2.0, 2.0));
+
+        // Two dynamic bodies split the separation
+        assert_eq!(position(&world, npc), Vec2::new(100.0, -2.0));
+        assert_eq!(position(&world, other_npc), Vec2::new(100.0, 8.0));
+
+        // The grid follows the pushes
+        let grid = world.get_resource::<SpatialGrid>().unwrap();
+        assert!(grid.query(&AABB::new(100.0, 12.0, 1.0, 1.0)).contains(&other_npc));
+    }
+
+    #[test]
+    fn test_fast_movement_stops_at_walls() {
+        // One-tile wall column at x = 4, walkable everywhere else
+        let tilemap = map(10, 3, |x, _| x == 4);
+        let mut world = World::new();
+        world.insert_resource(tilemap);
+        let player = world.spawn();
+        world.insert(player, Position::new(24.0, 24.0));
+        world.insert(player, Velocity::new(4000.0, 600.0));
+        world.insert(player, Collider::new(12.0, 8.0));
+
+        // Each step moves far more than a tile
+        for _ in 0..10 {
+            movement_system(&mut world);
+        }
+
+        // Stopped flush against the wall, having slid down along it
+        let pos = world.get::<Position>(player).unwrap().current;
+        assert!((pos.x - (64.0 - 6.0)).abs() < 1e-3, "tunneled to {}", pos.x);
+        assert!((pos.y - (48.0 - 4.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_knockback_stops_at_walls() {
+        // One-tile wall column at x = 4
+        let tilemap = map(10, 3, |x, _| x == 4);
+        let mut world = World::new();
+        world.insert_resource(tilemap);
+        let body = RigidBody::dynamic().with_restitution(0.5);
+        let player = spawn_body(&mut world, 24.0, 24.0, Some(body));
+        world.insert(player, Velocity::new(0.0, 0.0));
+
+        // 600px/s to the right: reaches the wall during the 4th step
+        apply_knockback(&mut world, player, Vec2::new(0.0, 24.0), 600.0);
+        for _ in 0..10 {
+            dynamics_system(&mut world);
+            movement_system(&mut world);
+            let pos = world.get::<Position>(player).unwrap().current;
+            assert!(pos.x <= 64.0 - 5.0 + 1e-3, "went through the wall to {}", pos.x);
+        }
+
+        // Bounced back at half speed
+        let momentum = world.get::<Momentum>(player).unwrap();
+        assert!((momentum.velocity - Vec2::new(-300.0, 0.0)).length() < 1e-3);
+        let pos = world.get::<Position>(player).unwrap().current;
+        assert!((pos - Vec2::new(59.0 - 30.0, 24.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn test_knocked_back_barrel_pushes_another() {
+        let mut world = World::new();
+        init_spatial_grid(&mut world);
+        init_sensors(&mut world);
+        let barrel = RigidBody::dynamic().with_mass(2.0);
+        let rolling = spawn_body(&mut world, 50.0, 50.0, Some(barrel));
+        let resting = spawn_body(&mut world, 70.0, 50.0, Some(barrel));
+
+        // 300px/s: touching after the 2nd step, overlapping after the 3rd
+        apply_knockback(&mut world, rolling, Vec2::new(40.0, 50.0), 600.0);
+        let mut schedule = create_schedule();
+        for _ in 0..5 {
+            schedule.run_stage(Stage::FixedUpdate, &mut world);
+        }
+
+        // Pushed apart and rolling on together at half the speed
+        let velocity = |entity| world.get::<Momentum>(entity).unwrap().velocity;
+        assert!((velocity(rolling) - Vec2::new(150.0, 0.0)).length() < 1e-3);
+        assert!((velocity(resting) - Vec2::new(150.0, 0.0)).length() < 1e-3);
+        let a = collider_aabb(&world, rolling).unwrap();
+        let b = collider_aabb(&world, resting).unwrap();
+        assert!(b.min.x > 70.0 && !a.intersects(&b));
+    }
+
+    #[test]
+    fn test_character_controller_walks_around_corners() {
+        // A single block at tile (4, 0); both walkers brush its bottom 2px
+        let tilemap = map(10, 3, |x, y| (x, y) == (4, 0));
+        let mut world = World::new();
+        world.insert_resource(tilemap);
+        let [player, crate_entity] = [0, 1].map(|_| {
+            let entity = world.spawn();
+            world.insert(entity, Position::new(24.0, 18.0));
+            world.insert(entity, Velocity::new(120.0, 0.0));
+            world.insert(entity, Collider::new(12.0, 8.0));
+            entity
+        });
+        world.insert(player, CharacterController::default());
+
+        for _ in 0..60 {
+            movement_system(&mut world);
+        }
+
+        // Nudged below the block and on its way; a plain slide stops dead
+        let pos = world.get::<Position>(player).unwrap().current;
+        assert!(pos.x > 80.0 + 6.0, "stuck at {}", pos.x);
+        assert_eq!(pos.y, 20.0);
+        assert!(world.get::<CharacterController>(player).unwrap().touches().is_empty());
+        let pos = world.get::<Position>(crate_entity).unwrap().current;
+        assert!((pos.x - (64.0 - 6.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_collision_layers_filter_entity_pairs() {
+        let mut world = World::new();
+        init_spatial_grid(&mut world);
+        let npc = spawn_body(&mut world, 0.0, 0.0, Some(RigidBody::dynamic()));
+        let crop = spawn_body(&mut world, 4.0, 0.0, None);
+        let fence = spawn_body(&mut world, 0.0, 8.0, None);
+        world.get_mut::<Collider>(npc).unwrap().layers =
+            CollisionLayers::new(LayerMask::NPC, LayerMask::SOLID | LayerMask::PLAYER);
+        world.get_mut::<Collider>(crop).unwrap().layers =
+            CollisionLayers::new(LayerMask::CROP, LayerMask::ALL);
+
+        entity_collision_system(&mut world);
+
+        // Walks over the crop, but not through the fence
+        assert_eq!(world.get::<Position>(npc).unwrap().current, Vec2::new(0.0, -2.0));
+        assert_eq!(world.get::<Position>(fence).unwrap().current, Vec2::new(0.0, 8.0));
+    }
+
+    #[test]
+    fn test_map_trigger_sensor_sends_one_transition() {
+        use engine_render::Trigger;
+
+        let mut tilemap = map(10, 10, |_, _| false);
+        tilemap.triggers = vec![Trigger {
+            x: 32.0,
+            y: 0.0,
+            width: 16.0,
+            height: 16.0,
+            target_map: "house.json".to_string(),
+            target_spawn: "door".to_string(),
+        }];
+        let mut world = World::new();
+        world.add_event::<MapTransition>();
+        init_spatial_grid(&mut world);
+        init_sensors(&mut world);
+        world.insert_resource(tilemap);
+        spawn_map_triggers(&mut world);
+        // Respawning for the same map replaces the sensors
+        spawn_map_triggers(&mut world);
+        assert_eq!(world.query::<&MapTrigger>().count(), 1);
+
+        let player = spawn_body(&mut world, 8.0, 8.0, Some(RigidBody::dynamic()));
+        world.insert(player, PlayerControlled::new(120.0));
+        world.get_mut::<Collider>(player).unwrap().layers =
+            CollisionLayers::new(LayerMask::PLAYER, LayerMask::SOLID);
+        let mut transitions = EventReader::<MapTransition>::new();
+        let mut step = |world: &mut World, x: f32| {
+            world.get_mut::<Position>(player).unwrap().current.x = x;
+            spatial_grid_system(world);
+            entity_collision_system(world);
+            sensor_system(world);
+            map_trigger_system(world);
+            let events = world.get_resource::<Events<MapTransition>>().unwrap();
+            transitions.read(events).cloned().collect::<Vec<_>>()
+        };
+
+        assert!(step(&mut world, 8.0).is_empty());
+        let sent = step(&mut world, 30.0);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].target_map, "house.json");
+        assert_eq!(sent[0].target_spawn, "door");
+        // Standing in the zone doesn't resend, and the sensor never pushes
+        assert!(step(&mut world, 40.0).is_empty());
+        assert_eq!(world.get::<Position>(player).unwrap().current, Vec2::new(40.0, 8.0));
+    }
+
+    #[test]
+    fn test_raycast_in_front_of_player() {
+        // Wall column at x = 4
+        let tilemap = map(8, 4, |x, _| x == 4);
+        let mut world = World::new();
+        world.insert_resource(tilemap);
+        init_spatial_grid(&mut world);
+        let player = spawn_body(&mut world, 24.0, 24.0, Some(RigidBody::dynamic()));
+        let npc = spawn_body(&mut world, 24.0, 44.0, Some(RigidBody::dynamic()));
+        world.get_mut::<Collider>(npc).unwrap().layers =
+            CollisionLayers::new(LayerMask::NPC, LayerMask::ALL);
+
+        // One tile ahead of the player: the NPC below, nothing to the right
+        let reach = |dir: Vec2| Ray::new(Vec2::new(24.0, 24.0), dir, 16.0);
+        let hit = raycast(&world, &reach(Vec2::Y), LayerMask::ALL, Some(player)).unwrap();
+        assert_eq!(hit.target, HitTarget::Entity(npc));
+        assert_eq!(hit.distance, 15.0);
+        assert!(raycast(&world, &reach(Vec2::X), LayerMask::ALL, Some(player)).is_none());
+        assert!(raycast(&world, &reach(Vec2::Y), LayerMask::SOLID, Some(player)).is_none());
+
+        // Further right, the wall is hit before the map edge
+        let far = Ray::new(Vec2::new(24.0, 24.0), Vec2::X, 500.0);
+        let hit = raycast(&world, &far, LayerMask::SOLID, Some(player)).unwrap();
+        assert_eq!(hit.target, HitTarget::Tile { x: 4, y: 1 });
+        assert_eq!(hit.point, Vec2::new(64.0, 24.0));
+
+        // The player's box swept down stops against the NPC
+        let aabb = collider_aabb(&world, player).unwrap();
+        let hit = shape_cast(&world, &aabb, Vec2::new(0.0, 20.0), LayerMask::ALL, Some(player));
+        assert_eq!(hit.map(|hit| hit.target), Some(HitTarget::Entity(npc)));
+
+        // Facing down, slightly off-center from the NPC, it is still in front
+        world.insert(player, PlayerControlled::new(120.0));
+        world.get_mut::<Position>(player).unwrap().current.x = 30.0;
+        let target = facing_target(&world, player).map(|hit| hit.target);
+        assert_eq!(target, Some(HitTarget::Entity(npc)));
+
+        // The interact key shoves it away from the player
+        let mut input = Input::new();
+        input.press_key(KeyCode::E);
+        world.insert_resource(input);
+        interaction_system(&mut world);
+        let impulse = world.get::<Momentum>(npc).unwrap().pending_impulse();
+        assert!(impulse.y > 0.0 && (impulse.length() - SHOVE_STRENGTH).abs() < 1e-3);
+
+        // Facing right, the wall is more than a tile away
+        world.get_mut::<PlayerControlled>(player).unwrap().facing = Vec2::X;
+        assert!(facing_target(&world, player).is_none());
+    }
+}
@@ -0,0 +1,30 @@
+//! Tilemaps for tests
+
+use engine_render::Tilemap;
+
+/// Map of 16px tiles, solid where `solid(x, y)` says
+pub fn map(width: u32, height: u32, solid: impl Fn(u32, u32) -> bool) -> Tilemap {
+    Tilemap {
+        name: "map".to_string(),
+        width,
+        height,
+        tile_width: 16,
+        tile_height: 16,
+        tilesets: vec![],
+        layers: vec![],
+        collision: (0..width * height)
+            .map(|i| solid(i % width, i / width))
+            .collect(),
+        collision_layers: vec![],
+        collision_shapes: vec![],
+        spawns: vec![],
+        triggers: vec![],
+    }
+}
+
+/// Map of 16px tiles drawn as rows, solid where a row has a `#`
+pub fn from_rows(rows: &[&str]) -> Tilemap {
+    map(rows[0].len() as u32, rows.len() as u32, |x, y| {
+        rows[y as usize].as_bytes()[x as usize] == b'#'
+    })
+}
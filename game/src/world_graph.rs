@@ -0,0 +1,416 @@
+//! World graph for routing NPCs across maps
+//!
+//! Maps are the nodes and their triggers the edges: walking into a trigger
+//! puts you at `target_spawn` on `target_map`. Routes are planned as one
+//! leg per map, each walked on that map's [`Pathfinder`], and NPCs keep
+//! walking them while their map isn't the loaded one.
+//!
+//! The game adds every map it loads to the graph, together with the maps
+//! its triggers lead to, and steps [`simulate_offscreen`] at the fixed
+//! timestep.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use engine_render::glam::Vec2;
+use engine_render::Tilemap;
+use log::warn;
+
+use crate::npc::{Npc, Waypoint};
+use crate::pathfinding::{NavGrid, Pathfinder, NPC_BLOCKING};
+
+/// A trigger leading to another map
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapExit {
+    /// Center of the trigger zone
+    pub position: Vec2,
+    pub target_map: String,
+    pub target_spawn: String,
+}
+
+/// What the world graph knows about one map
+#[derive(Debug, Clone)]
+pub struct MapNode {
+    spawns: HashMap<String, Vec2>,
+    default_spawn: Vec2,
+    exits: Vec<MapExit>,
+    pathfinder: Pathfinder,
+}
+
+impl MapNode {
+    /// Build the node of a map
+    #[must_use]
+    pub fn new(tilemap: &Tilemap) -> Self {
+        let exits = tilemap
+            .triggers
+            .iter()
+            .map(|trigger| {
+                let (min, max) = trigger.bounds();
+                MapExit {
+                    position: (min + max) * 0.5,
+                    target_map: trigger.target_map.clone(),
+                    target_spawn: trigger.target_spawn.clone(),
+                }
+            })
+            .collect();
+        Self {
+            spawns: tilemap
+                .spawns
+                .iter()
+                .map(|spawn| (spawn.id.clone(), spawn.position()))
+                .collect(),
+            default_spawn: tilemap.default_spawn(),
+            exits,
+            pathfinder: Pathfinder::new(NavGrid::from_tilemap(tilemap, NPC_BLOCKING)),
+        }
+    }
+
+    /// Get where arriving at a spawn point puts you, as when loading the map
+    #[must_use]
+    pub fn spawn(&self, id: &str) -> Vec2 {
+        self.spawns.get(id).copied().unwrap_or(self.default_spawn)
+    }
+}
+
+/// Part of a route on a single map
+#[derive(Debug, Clone)]
+pub struct RouteLeg {
+    pub map: String,
+    /// Where the leg starts: the walker's position or an arrival spawn
+    pub start: Vec2,
+    /// Waypoints to the next map's trigger, or to the destination
+    pub waypoints: Vec<Waypoint>,
+}
+
+/// Maps connected by their triggers, keyed by map path (resource)
+#[derive(Debug, Clone, Default)]
+pub struct WorldGraph {
+    maps: HashMap<String, MapNode>,
+}
+
+/// A place to plan from in `WorldGraph::route`
+struct Visit {
+    map: String,
+    position: Vec2,
+    /// Distance walked so far
+    cost: f32,
+    legs: Vec<RouteLeg>,
+    /// (map, spawn) arrived at; None for the start
+    arrival: Option<(String, String)>,
+}
+
+/// Length of walking from `start` through the waypoints
+fn path_length(start: Vec2, waypoints: &[Waypoint]) -> f32 {
+    waypoints
+        .iter()
+        .fold((start, 0.0), |(from, length), waypoint| {
+            let to = Vec2::new(waypoint.x, waypoint.y);
+            (to, length + from.distance(to))
+        })
+        .1
+}
+
+impl WorldGraph {
+    /// Create an empty graph
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace a map under the path its triggers are referred by
+    pub fn add_map(&mut self, path: &str, tilemap: &Tilemap) {
+        self.maps.insert(path.to_string(), MapNode::new(tilemap));
+    }
+
+    /// Add or replace a loaded map, then load every map reachable through
+    /// triggers from it that the graph doesn't have yet
+    ///
+    /// Maps that fail to load are skipped with a warning.
+    pub fn add_connected(&mut self, path: &str, tilemap: &Tilemap) {
+        self.add_map(path, tilemap);
+
+        let mut queue: VecDeque<String> = VecDeque::from([path.to_string()]);
+        while let Some(path) = queue.pop_front() {
+            let targets: Vec<String> = self.maps[&path]
+                .exits
+                .iter()
+                .map(|exit| exit.target_map.clone())
+                .collect();
+            for target in targets {
+                if self.maps.contains_key(&target) {
+                    continue;
+                }
+                match Tilemap::load(&target) {
+                    Ok(tilemap) => {
+                        self.add_map(&target, &tilemap);
+                        queue.push_back(target);
+                    }
+                    Err(e) => warn!("Skipping map '{}' linked from '{}': {}", target, path, e),
+                }
+            }
+        }
+    }
+
+    /// Plan the shortest walk from a position on one map to a position on
+    /// another, going through map triggers
+    ///
+    /// Returns one leg per map crossed, or None if there is no way there.
+    pub fn route(
+        &mut self,
+        from_map: &str,
+        from: Vec2,
+        to_map: &str,
+        to: Vec2,
+    ) -> Option<Vec<RouteLeg>> {
+        let mut frontier = vec![Visit {
+            map: from_map.to_string(),
+            position: from,
+            cost: 0.0,
+            legs: Vec::new(),
+            arrival: None,
+        }];
+        let mut visited = HashSet::new();
+        let mut best: Option<(f32, Vec<RouteLeg>)> = None;
+
+        // Dijkstra over map arrivals; the graph is small, so a linear scan
+        // finds the nearest visit
+        while let Some(nearest) = frontier
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.cost.total_cmp(&b.1.cost))
+            .map(|(i, _)| i)
+        {
+            let visit = frontier.swap_remove(nearest);
+            if best.as_ref().is_some_and(|(cost, _)| *cost <= visit.cost) {
+                break;
+            }
+            if let Some(arrival) = &visit.arrival {
+                if !visited.insert(arrival.clone()) {
+                    continue;
+                }
+            }
+            let Some(node) = self.maps.get_mut(&visit.map) else {
+                continue;
+            };
+
+            if visit.map == to_map {
+                if let Some(waypoints) = node.pathfinder.find_path(visit.position, to) {
+                    let cost = visit.cost + path_length(visit.position, &waypoints);
+                    if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+                        let mut legs = visit.legs.clone();
+                        legs.push(RouteLeg {
+                            map: visit.map.clone(),
+                            start: visit.position,
+                            waypoints,
+                        });
+                        best = Some((cost, legs));
+                    }
+                }
+            }
+
+            for exit in node.exits.clone() {
+                let Some(waypoints) = self
+                    .maps
+                    .get_mut(&visit.map)
+                    .and_then(|node| node.pathfinder.find_path(visit.position, exit.position))
+                else {
+                    continue;
+                };
+                let Some(arrival) = self
+                    .maps
+                    .get(&exit.target_map)
+                    .map(|target| target.spawn(&exit.target_spawn))
+                else {
+                    continue;
+                };
+
+                let mut legs = visit.legs.clone();
+                let cost = visit.cost + path_length(visit.position, &waypoints);
+                legs.push(RouteLeg {
+                    map: visit.map.clone(),
+                    start: visit.position,
+                    waypoints,
+                });
+                frontier.push(Visit {
+                    map: exit.target_map.clone(),
+                    position: arrival,
+                    cost,
+                    legs,
+                    arrival: Some((exit.target_map, exit.target_spawn)),
+                });
+            }
+        }
+
+        best.map(|(_, legs)| legs)
+    }
+}
+
+/// Walk the NPCs that aren't on the loaded map for `seconds`
+///
+/// Returns the IDs of the NPCs that walked onto the loaded map, so they can
+/// be shown.
+pub fn simulate_offscreen(npcs: &mut [Npc], loaded_map: &str, seconds: f32) -> Vec<String> {
+    let mut arrived = Vec::new();
+    for npc in npcs.iter_mut().filter(|npc| npc.map != loaded_map) {
+        npc.fast_forward(seconds);
+        if npc.map == loaded_map {
+            arrived.push(npc.id.clone());
+        }
+    }
+    arrived
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::npc::NpcState;
+    use crate::test_map::map;
+    use engine_render::{SpawnPoint, Trigger};
+
+    fn spawn(id: &str, x: f32, y: f32) -> SpawnPoint {
+        SpawnPoint {
+            id: id.to_string(),
+            x,
+            y,
+        }
+    }
+
+    /// One-tile trigger at tile (x, y)
+    fn door(x: u32, y: u32, target_map: &str, target_spawn: &str) -> Trigger {
+        Trigger {
+            x: x as f32 * 16.0,
+            y: y as f32 * 16.0,
+            width: 16.0,
+            height: 16.0,
+            target_map: target_map.to_string(),
+            target_spawn: target_spawn.to_string(),
+        }
+    }
+
+    fn house() -> Tilemap {
+        let mut house = map(4, 4, |_, _| false);
+        house.spawns = vec![spawn("front_door", 24.0, 40.0)];
+        house.triggers = vec![door(1, 3, "farm.json", "house_door")];
+        house
+    }
+
+    /// The farm, with a fence between the house door and the road east and
+    /// any other `walls`
+    fn farm(walls: &[(u32, u32)]) -> Tilemap {
+        let walls = [&[(3, 0), (3, 1), (3, 2)], walls].concat();
+        let mut farm = map(8, 4, |x, y| walls.contains(&(x, y)));
+        farm.spawns = vec![spawn("house_door", 24.0, 24.0), spawn("west", 104.0, 24.0)];
+        farm.triggers = vec![
+            door(1, 0, "house.json", "front_door"),
+            door(7, 1, "town.json", "west"),
+        ];
+        farm
+    }
+
+    fn town() -> Tilemap {
+        let mut town = map(6, 6, |_, _| false);
+        town.spawns = vec![spawn("west", 24.0, 24.0)];
+        town.triggers = vec![door(0, 1, "farm.json", "west")];
+        town
+    }
+
+    /// house <-> farm <-> town
+    fn world() -> WorldGraph {
+        let mut graph = WorldGraph::new();
+        graph.add_map("house.json", &house());
+        graph.add_map("farm.json", &farm(&[]));
+        graph.add_map("town.json", &town());
+        graph
+    }
+
+    #[test]
+    fn test_route_across_maps() {
+        let mut graph = world();
+        let square = Vec2::new(72.0, 72.0);
+
+        let legs = graph
+            .route("house.json", Vec2::new(24.0, 8.0), "town.json", square)
+            .unwrap();
+        let maps: Vec<&str> = legs.iter().map(|leg| leg.map.as_str()).collect();
+        assert_eq!(maps, ["house.json", "farm.json", "town.json"]);
+        assert_eq!(legs[1].start, Vec2::new(24.0, 24.0));
+        assert_eq!(legs[2].start, Vec2::new(24.0, 24.0));
+        let end = legs[2].waypoints.last().unwrap();
+        assert_eq!((end.x, end.y), (72.0, 72.0));
+
+        // Each leg ends on the trigger leading to the next map
+        let first_exit = legs[0].waypoints.last().unwrap();
+        assert_eq!((first_exit.x, first_exit.y), (24.0, 56.0));
+
+        // Same-map destinations are a single leg
+        let legs = graph
+            .route("town.json", square, "town.json", Vec2::new(8.0, 8.0))
+            .unwrap();
+        assert_eq!(legs.len(), 1);
+    }
+
+    #[test]
+    fn test_unreachable_routes() {
+        let mut graph = world();
+        assert!(graph
+            .route("house.json", Vec2::new(24.0, 8.0), "beach.json", Vec2::ZERO)
+            .is_none());
+
+        // Walling off the farm's road east cuts the town off
+        graph.add_map("farm.json", &farm(&[(3, 3)]));
+        assert!(graph
+            .route(
+                "house.json",
+                Vec2::new(24.0, 8.0),
+                "town.json",
+                Vec2::new(72.0, 72.0)
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn test_maps_join_the_graph_as_they_load() {
+        let (door_step, field) = (Vec2::new(24.0, 8.0), Vec2::new(120.0, 40.0));
+
+        // The farm isn't on disk, so the house leads nowhere yet
+        let mut graph = WorldGraph::new();
+        graph.add_connected("house.json", &house());
+        assert!(graph.route("house.json", door_step, "farm.json", field).is_none());
+
+        // Loading the farm links it back to the house; the town is skipped
+        graph.add_connected("farm.json", &farm(&[]));
+        let legs = graph.route("house.json", door_step, "farm.json", field).unwrap();
+        assert_eq!(legs.len(), 2);
+        assert!(graph.route("farm.json", field, "town.json", Vec2::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_npc_walks_to_other_map_offscreen() {
+        let mut graph = world();
+        let mut robin = Npc::new("robin", "Robin").with_position(24.0, 8.0);
+        robin.map = "house.json".to_string();
+        robin.add_location("square", 72.0, 72.0);
+        robin
+            .location_maps
+            .insert("square".to_string(), "town.json".to_string());
+
+        assert!(robin.travel_to("square", &mut graph));
+        assert_eq!(robin.state, NpcState::Walking);
+
+        // The player is on the farm: Robin shows up there, then leaves
+        let mut npcs = vec![robin];
+        assert!(simulate_offscreen(&mut npcs, "farm.json", 0.1).is_empty());
+        assert_eq!(simulate_offscreen(&mut npcs, "farm.json", 1.0), ["robin"]);
+        assert_eq!(npcs[0].map, "farm.json");
+
+        // Not simulated while on the loaded map
+        let before = npcs[0].position.y;
+        assert!(simulate_offscreen(&mut npcs, "farm.json", 1.0).is_empty());
+        assert_eq!(npcs[0].position.y, before);
+
+        assert!(simulate_offscreen(&mut npcs, "house.json", 60.0).is_empty());
+        let robin = &npcs[0];
+        assert_eq!(robin.map, "town.json");
+        assert_eq!(robin.state, NpcState::Idle);
+        assert_eq!((robin.position.x, robin.position.y), (72.0, 72.0));
+    }
+}